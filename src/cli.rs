@@ -0,0 +1,361 @@
+//! COMMAND LINE
+//!
+//! Flags and subcommands of the jellyfin-tui binary. Without a subcommand the TUI starts.
+//!
+//! $ jellyfin-tui --profile office --no-banner
+//! $ jellyfin-tui --config ~/music.yaml --log-file /tmp/jellyfin-tui.log
+//! $ jellyfin-tui list artists --json
+//! $ jellyfin-tui search "hey jude"
+//! $ jellyfin-tui play "abbey road"
+//! $ jellyfin-tui status
+//! $ jellyfin-tui config check
+//!
+//! Subcommands print their results to stdout and errors to stderr, and exit with 1 on failure,
+//! so they can be used from scripts. `play`, `status` and `remote` talk to a running
+//! jellyfin-tui over the control socket, see remote.rs.
+use crate::client::{self, Client, DiscographySong};
+use crate::config::{self, Config};
+use crate::log;
+use crate::profile::{self, Profile};
+use crate::remote;
+use crate::sort::Sorting;
+use crate::tui::Song;
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::json;
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(name = "jellyfin-tui", version, about = "A terminal music player for Jellyfin")]
+pub struct Cli {
+    /// Use this config file instead of ~/.config/jellyfin-tui/config.yaml
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+    /// Use this profile of the config file, see profile.rs
+    #[arg(long, value_name = "NAME", global = true)]
+    pub profile: Option<String>,
+    /// Don't print the banner when starting
+    #[arg(long)]
+    pub no_banner: bool,
+    /// Append status messages and errors to this file
+    #[arg(long, value_name = "PATH", global = true)]
+    pub log_file: Option<PathBuf>,
+    /// Play without a terminal, TUIs started later attach to it
+    #[arg(long)]
+    pub daemon: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Log in with the password from the config and save the token
+    Login,
+    /// End the session on the server and forget the saved token
+    Logout,
+    /// Search tracks by name
+    Search {
+        #[arg(required = true)]
+        term: Vec<String>,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Play an item by id, or the tracks found searching for the words given
+    Play {
+        #[arg(required = true, value_name = "ITEM_ID|QUERY")]
+        target: Vec<String>,
+    },
+    /// Show what the running jellyfin-tui is playing
+    Status {
+        #[arg(long)]
+        json: bool,
+    },
+    /// List artists, playlists, genres, libraries or profiles
+    List {
+        what: ListKind,
+        #[arg(long)]
+        json: bool,
+    },
+    /// Send a command to the running jellyfin-tui, like the ones typed after `:`
+    Remote {
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Work with the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Report unknown options and invalid values, with their line
+    Check,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ListKind {
+    Artists,
+    Playlists,
+    Genres,
+    Libraries,
+    Profiles,
+}
+
+/// Runs a subcommand and returns the exit code
+pub async fn run(command: Command, wanted_profile: Option<&str>) -> i32 {
+    let result = match command {
+        Command::Remote { args } => return remote::run_client(&args),
+        Command::Status { json } => status(json),
+        Command::Config { action: ConfigAction::Check } => check_config(),
+        Command::Login => login(wanted_profile).await,
+        Command::Logout => logout(wanted_profile).await,
+        Command::List { what: ListKind::Profiles, json } => list_profiles(json),
+        Command::List { what, json } => match connect(wanted_profile).await {
+            Ok(client) => list(&client, what, json).await,
+            Err(e) => Err(e),
+        },
+        Command::Search { term, json } => match connect(wanted_profile).await {
+            Ok(client) => search(&client, &term.join(" "), json).await,
+            Err(e) => Err(e),
+        },
+        Command::Play { target } => match connect(wanted_profile).await {
+            Ok(client) => play(&client, &target).await,
+            Err(e) => Err(e),
+        },
+    };
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            log::write(&format!("[!!] {}", e));
+            eprintln!("[!!] {}", e);
+            1
+        }
+    }
+}
+
+fn profile(wanted: Option<&str>) -> Result<Profile, String> {
+    if !config::config_file().is_some_and(|file| file.exists()) {
+        return Err(String::from("there is no config file, run jellyfin-tui once to create it"));
+    }
+    profile::select(&config::load_effective()?, wanted)
+}
+
+async fn connect(wanted: Option<&str>) -> Result<Client, String> {
+    let profile = profile(wanted)?;
+    profile::connect(&profile, None).await.map_err(|e| format!("Failed to authenticate: {}", e))
+}
+
+/// Logs in again even if a token is saved, asking for the password. Creates the config on the
+/// first run
+async fn login(wanted: Option<&str>) -> Result<(), String> {
+    let mut password = None;
+    if !config::config_file().is_some_and(|file| file.exists()) {
+        password = Some(profile::create_config());
+    }
+    let profile = profile(wanted)?;
+    profile.forget_login()?;
+    let client = profile::connect(&profile, password).await.map_err(|e| format!("Failed to authenticate: {}", e))?;
+    println!("[OK] Logged in to {} as {}", client.base_url, profile.username);
+    Ok(())
+}
+
+/// Ends the saved token's session on the server, then forgets the token. It's forgotten even
+/// when the server can't be reached, the session then lingers until the server drops it
+async fn logout(wanted: Option<&str>) -> Result<(), String> {
+    let profile = profile(wanted)?;
+    if let Some(login) = profile.load_login() {
+        if let Err(e) = client::log_out(&login).await {
+            eprintln!("[!!] Could not end the session on {}: {}", profile.server, e);
+        }
+    }
+    profile.forget_login()?;
+    println!("[OK] Logged out of {}", profile.server);
+    Ok(())
+}
+
+/// Prints what is wrong with the config file, failing if anything falls back to its default
+fn check_config() -> Result<(), String> {
+    let path = config::config_file().ok_or("could not find the config directory")?;
+    if !path.exists() {
+        return Err(format!("there is no config file at {}", path.display()));
+    }
+    let (_, issues) = Config::read();
+    for issue in &issues {
+        match issue.error {
+            true => println!("error: {}", issue),
+            false => println!("warning: {}", issue),
+        }
+    }
+    match issues.iter().filter(|issue| issue.error).count() {
+        0 => {
+            println!("[OK] {} is valid", path.display());
+            Ok(())
+        }
+        errors => Err(format!("{} has {} error(s)", path.display(), errors)),
+    }
+}
+
+fn status(json: bool) -> Result<(), String> {
+    let status = remote::Daemon::connect()
+        .map_err(|e| format!("Is jellyfin-tui running? {}", e))?
+        .status()?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status).unwrap_or_default());
+        return Ok(());
+    }
+    match status.song {
+        Some(song) => {
+            let state = if status.paused { "paused" } else { "playing" };
+            println!("{}: {} - {} ({})", state, song.artist, song.name, song.album);
+            println!(
+                "{} / {}, track {} of {}",
+                time(status.position),
+                time(status.duration),
+                status.queue_position + 1,
+                status.queue_length
+            );
+        }
+        None => println!("stopped"),
+    }
+    Ok(())
+}
+
+/// 83.5 -> "1:23"
+fn time(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+fn print_songs(songs: &[Song], json: bool) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(songs).unwrap_or_default());
+        return;
+    }
+    for song in songs {
+        println!("{}\t{} - {} ({})", song.id, song.artist, song.name, song.album);
+    }
+}
+
+async fn search(client: &Client, term: &str, json: bool) -> Result<(), String> {
+    let tracks = client.search(term).await.map_err(|e| e.to_string())?;
+    let songs = tracks
+        .items
+        .iter()
+        .map(|track| Song::from_track(client, track))
+        .collect::<Vec<Song>>();
+    print_songs(&songs, json);
+    Ok(())
+}
+
+/// Tracks of an album or playlist, a track itself, or the tracks found searching
+async fn resolve(client: &Client, target: &[String]) -> Result<Vec<DiscographySong>, String> {
+    if let [id] = target {
+        let items = client.items(std::slice::from_ref(id)).await.map(|found| found.items).unwrap_or_default();
+        match items.first() {
+            Some(item) if !item.is_folder => return Ok(items),
+            Some(_) => {
+                let tracks = client.album(id).await.map_err(|e| e.to_string())?.items;
+                if !tracks.is_empty() {
+                    return Ok(tracks);
+                }
+                return Ok(client.playlist_items(id).await.map_err(|e| e.to_string())?.items);
+            }
+            None => {}
+        }
+    }
+    Ok(client.search(&target.join(" ")).await.map_err(|e| e.to_string())?.items)
+}
+
+async fn play(client: &Client, target: &[String]) -> Result<(), String> {
+    let tracks = resolve(client, target).await?;
+    if tracks.is_empty() {
+        return Err(format!("nothing found for '{}'", target.join(" ")));
+    }
+    let mut daemon = remote::Daemon::connect().map_err(|e| format!("Is jellyfin-tui running? {}", e))?;
+    let ids = tracks.iter().map(|track| track.id.as_str()).collect::<Vec<&str>>();
+    daemon.command(&format!("play {}", ids.join(" ")))?;
+    println!("[OK] Playing {} tracks", tracks.len());
+    Ok(())
+}
+
+async fn list(client: &Client, what: ListKind, json: bool) -> Result<(), String> {
+    // (id, name, json of the entry)
+    let entries: Vec<(String, String, serde_json::Value)> = match what {
+        ListKind::Artists => {
+            let sorting = Sorting::from_config(&config::load_effective()?).unwrap_or_default();
+            let artists = client.artists(&sorting).await.map_err(|e| e.to_string())?;
+            artists
+                .into_iter()
+                .map(|artist| {
+                    let entry = json!({ "id": artist.id, "name": artist.name });
+                    (artist.id, artist.name, entry)
+                })
+                .collect()
+        }
+        ListKind::Playlists => {
+            let playlists = client.playlists().await.map_err(|e| e.to_string())?;
+            playlists
+                .into_iter()
+                .map(|playlist| {
+                    let entry = json!({ "id": playlist.id, "name": playlist.name, "tracks": playlist.child_count });
+                    (playlist.id, playlist.name, entry)
+                })
+                .collect()
+        }
+        ListKind::Genres => {
+            let genres = client.genres().await.map_err(|e| e.to_string())?;
+            genres
+                .into_iter()
+                .map(|genre| {
+                    let entry = json!({ "id": genre.id, "name": genre.name });
+                    (genre.id, genre.name, entry)
+                })
+                .collect()
+        }
+        ListKind::Libraries => {
+            let libraries = client.music_libraries().await.map_err(|e| e.to_string())?;
+            libraries
+                .into_iter()
+                .map(|library| {
+                    let browsed = client.libraries().contains(&library.id);
+                    let entry = json!({ "id": library.id, "name": library.name, "browsed": browsed });
+                    (library.id, library.name, entry)
+                })
+                .collect()
+        }
+        ListKind::Profiles => return list_profiles(json),
+    };
+    match json {
+        true => {
+            let entries = entries.into_iter().map(|(_, _, entry)| entry).collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&entries).unwrap_or_default());
+        }
+        false => {
+            for (id, name, _) in entries {
+                println!("{}\t{}", id, name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Profiles don't need a server, so they are listed without logging in
+fn list_profiles(json: bool) -> Result<(), String> {
+    let profiles = profile::list(&config::load_effective()?)?;
+    match json {
+        true => {
+            let entries = profiles
+                .iter()
+                .map(|profile| json!({ "name": profile.name, "server": profile.server, "username": profile.username }))
+                .collect::<Vec<_>>();
+            println!("{}", serde_json::to_string_pretty(&entries).unwrap_or_default());
+        }
+        false => {
+            for profile in profiles {
+                println!("{}\t{}", profile.name, profile.server);
+            }
+        }
+    }
+    Ok(())
+}
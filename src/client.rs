@@ -1,19 +1,36 @@
+use crate::columns;
+use crate::config;
+use crate::profile::{Profile, Login};
+use crate::sort::{SortBy, Sorting};
+use rand::seq::SliceRandom;
 use reqwest;
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use serde_yaml;
-use dirs::config_dir;
 use std::io::Cursor;
-use std::io;
 use std::error::Error;
+use std::sync::OnceLock;
 
-#[derive(Debug)]
+/// Ids asked for in one request, the server limits how long a URL can be
+const IDS_PER_REQUEST: usize = 100;
+
+/// Songs a search comes back with at most
+const SEARCH_LIMIT: usize = 200;
+
+#[derive(Debug, Clone)]
 pub struct Client {
     pub base_url: String,
     http_client: reqwest::Client,
     pub access_token: String,
     user_id: String,
+    pub profile: Profile,
+    /// music libraries being browsed, each browsing query runs once per library with it as
+    /// the ParentId. Empty if the server has none, then the whole server is searched
+    libraries: Vec<String>,
+    /// kbit/s to transcode down to, from the streaming section of the config
+    pub max_bitrate: Option<u64>,
+    /// the device the server knows our login by, see device_id
+    pub device_id: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,246 +42,793 @@ pub struct Credentials {
 }
 
 impl Client {
-    /// Creates a new client with the given base URL
-    /// If the configuration file does not exist, it will be created with stdin input
+    /// Logs in to the server of a profile. A login saved by an earlier run is reused as long
+    /// as the server still accepts it, otherwise the password in the config is used if there
+    /// is one
+    ///
+    pub async fn new(profile: &Profile) -> Result<Self, String> {
+        if let Some(client) = Self::resume(profile).await {
+            return Ok(client);
+        }
+        match profile.password.is_empty() {
+            true => Err(format!("not logged in, run `jellyfin-tui login --profile {}`", profile.name)),
+            false => Self::log_in(profile, &profile.password).await,
+        }
+    }
+
+    /// Uses the login saved for the profile, if the server still accepts it
+    pub async fn resume(profile: &Profile) -> Option<Self> {
+        let login = profile.load_login()?;
+        let mut client = Self::unauthenticated(profile);
+        if !client.login_valid(&login).await {
+            return None;
+        }
+        client.access_token = login.access_token;
+        client.user_id = login.user_id;
+        client.device_id = login.device_id;
+        client.libraries = client.choose_libraries(&profile.libraries).await;
+        Some(client)
+    }
+
+    /// Logs in with a password and saves the token the server gives back. The password
+    /// itself is not kept anywhere
+    pub async fn log_in(profile: &Profile, password: &str) -> Result<Self, String> {
+        let mut client = Self::unauthenticated(profile);
+        client.authenticate(password).await?;
+        profile.save_login(&Login {
+            server: profile.server.clone(),
+            username: profile.username.clone(),
+            user_id: client.user_id.clone(),
+            access_token: client.access_token.clone(),
+            device_id: client.device_id.clone(),
+        })?;
+        client.libraries = client.choose_libraries(&profile.libraries).await;
+        Ok(client)
+    }
+
+    fn unauthenticated(profile: &Profile) -> Self {
+        Self {
+            base_url: profile.server.clone(),
+            http_client: reqwest::Client::new(),
+            access_token: String::new(),
+            user_id: String::new(),
+            profile: profile.clone(),
+            libraries: vec![],
+            max_bitrate: None,
+            device_id: device_id().to_string(),
+        }
+    }
+
+    /// Logs in with the profile's username
+    async fn authenticate(&mut self, password: &str) -> Result<(), String> {
+        let credentials = Credentials {
+            username: self.profile.username.clone(),
+            password: password.to_string(),
+        };
+        let url: String = String::new() + &self.base_url + "/Users/authenticatebyname";
+        let response = self.http_client
+            .post(url)
+            .header("Content-Type", "text/json")
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .json(&credentials)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?
+            .error_for_status()
+            .map_err(|e| e.to_string())?;
+
+        let value = response.json::<Value>().await.map_err(|e| e.to_string())?;
+        self.access_token = value["AccessToken"].as_str().ok_or("could not get access token")?.to_string();
+        self.user_id = value["User"]["Id"].as_str().ok_or("could not get user id")?.to_string();
+        Ok(())
+    }
+
+    /// Whether a saved login is still good
+    async fn login_valid(&self, login: &Login) -> bool {
+        let response = self.http_client
+            .get(format!("{}/Users/{}", self.base_url, login.user_id))
+            .header("X-MediaBrowser-Token", login.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .send()
+            .await;
+        matches!(response, Ok(response) if response.status().is_success())
+    }
+
+    /// The music libraries to browse: the ones named (by name or id) in the profile's
+    /// `libraries` list, or all of them. If none of the named ones exist, all of them too
+    async fn choose_libraries(&self, chosen: &[String]) -> Vec<String> {
+        let libraries = match self.music_libraries().await {
+            Ok(libraries) => libraries,
+            // browse everything rather than nothing
+            Err(_) => return vec![],
+        };
+        let picked = libraries
+            .iter()
+            .filter(|library| chosen.iter().any(|c| *c == library.id || c.eq_ignore_ascii_case(&library.name)))
+            .map(|library| library.id.clone())
+            .collect::<Vec<String>>();
+        match picked.is_empty() {
+            true => libraries.into_iter().map(|library| library.id).collect(),
+            false => picked,
+        }
+    }
+
+    /// The user's libraries holding music
+    ///
+    pub async fn music_libraries(&self) -> Result<Vec<Library>, reqwest::Error> {
+        let response = self.http_client
+            .get(format!("{}/Users/{}/Views", self.base_url, self.user_id))
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let views: Libraries = response.json().await?;
+        Ok(views.items.into_iter().filter(|view| view.collection_type == "music").collect())
+    }
+
+    /// The libraries being browsed
+    pub fn libraries(&self) -> &[String] {
+        &self.libraries
+    }
+
+    /// Browses these libraries from now on, all of them when empty
+    pub fn set_libraries(&mut self, libraries: Vec<String>) {
+        self.libraries = libraries;
+    }
+
+    /// One ParentId per library being browsed, or a single None to query the whole server
+    fn parents(&self) -> Vec<Option<&str>> {
+        match self.libraries.is_empty() {
+            true => vec![None],
+            false => self.libraries.iter().map(|id| Some(id.as_str())).collect(),
+        }
+    }
+
+    /// Whether a query runs more than once. Each answer is sorted and limited on its own, so
+    /// the merged list has to be sorted and limited again
+    fn merging(&self) -> bool {
+        self.libraries.len() > 1
+    }
+
+    /// Produces a list of artists, called by the main function before initializing the app
     /// 
-    pub async fn new() -> Self {
+    pub async fn artists(&self, sorting: &Sorting) -> Result<Vec<Artist>, reqwest::Error> {
+        let url = match sorting.album_artists {
+            true => format!("{}/Artists/AlbumArtists", self.base_url),
+            false => format!("{}/Artists", self.base_url),
+        };
+        let (sort_by, sort_order) = sorting.artists.artist_query();
+
+        let mut artists: Vec<Artist> = vec![];
+        for parent in self.parents() {
+            let request = self.http_client
+                .get(&url)
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "text/json")
+                .query(&[
+                    ("SortBy", sort_by),
+                    ("SortOrder", sort_order),
+                    ("Recursive", "true"), 
+                    ("Fields", "SortName,DateCreated,ProductionYear"), 
+                    ("ImageTypeLimit", "-1"),
+                    ("UserId", self.user_id.as_str()),
+                ])
+                .query(&[("StartIndex", "0")]);
+            let response = in_library(request, parent).send().await;
 
-        let config_dir = match config_dir() {
-            Some(dir) => dir,
-            None => {
-                println!("[!!] Could not find config directory");
-                std::process::exit(1);
+            let found = match response {
+                Ok(json) => {
+                    let found: Artists = json.json().await.unwrap_or_else(|_| Artists {
+                        items: vec![],
+                        start_index: 0,
+                        total_record_count: 0,
+                    });
+                    found
+                },
+                Err(_) => continue,
+            };
+            // an artist can be in more than one library
+            for artist in found.items {
+                if !artists.iter().any(|a| a.id == artist.id) {
+                    artists.push(artist);
+                }
             }
+        }
+        if self.merging() {
+            sort_artists(&mut artists, sorting.artists);
+        }
+
+        Ok(artists)
+    }
+
+    /// Produces a list of songs by an artist, sorted as chosen in the sort menu
+    /// 
+    pub async fn discography(&self, id: &str, sorting: &Sorting) -> Result<Discography, reqwest::Error> {
+        let url = format!("{}/Users/{}/Items", self.base_url, self.user_id);
+        let (sort_by, sort_order) = sorting.tracks.track_query();
+        let artist_filter = match sorting.album_artists {
+            true => "AlbumArtistIds",
+            false => "ArtistIds",
         };
 
-        let config_file = config_dir.join("jellyfin-tui").join("config.yaml");
-        
-        if !config_file.exists() {
-            let mut server = String::new();
-            let mut username = String::new();
-            let mut password = String::new();
-
-            println!("\n[!!] The configuration file does not exist. Please fill in the following details:");
-            println!("--- Jellyfin TUI Configuration ---");
-            println!("The expected format is:");
-            println!("- server: http://localhost:8096");
-            println!("- username: admin");
-            println!("- password: password\n");
-            let mut ok = false;
-            while !ok {
-                while server.is_empty() || !server.contains("http") {
-                    println!("host: ");
-                    io::stdin().read_line(&mut server).unwrap();
-                    if server.is_empty() {
-                        println!("[!!] Host cannot be empty");
-                    } else if !server.contains("http") {
-                        println!("[!!] Host must be a valid URL including http or https");
-                    }
-                    server = "".to_string();
-                }
-                println!("username: ");
-                io::stdin().read_line(&mut username).expect("Failed to read username");
-                println!("password: ");
-                io::stdin().read_line(&mut password).expect("Failed to read password");
-
-                println!("\nHost: '{}' Username: '{}' Password: '{}'", server.trim(), username.trim(), password.trim());
-                println!("[!!] Is this correct? (Y/n)");
-                let mut confirm = String::new();
-                io::stdin().read_line(&mut confirm).expect("Failed to read confirmation");
-                // y is default
-                if confirm.contains("n") || confirm.contains("N") {
-                    server = "".to_string();
-                    username = "".to_string();
-                    password = "".to_string();
-                } else {
-                    ok = true;
-                }
+        let mut discog = Discography { items: vec![] };
+        for parent in self.parents() {
+            let request = self.http_client
+                .get(&url)
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "text/json")
+                .query(&[
+                    ("SortBy", sort_by),
+                    ("SortOrder", sort_order),
+                    ("Recursive", "true"), 
+                    ("IncludeItemTypes", "Audio"),
+                    ("Fields", "Genres, DateCreated, MediaSources, ParentId"),
+                    ("StartIndex", "0"),
+                    ("ImageTypeLimit", "1"),
+                    (artist_filter, id)
+                ])
+                .query(&[("StartIndex", "0")]);
+            let response = in_library(request, parent).send().await;
+
+            if let Ok(json) = response {
+                let found: Discography = json.json().await.unwrap_or_else(|_| Discography {
+                    items: vec![],
+                });
+                discog.items.extend(found.items);
             }
+        }
+        if self.merging() {
+            sort_tracks(&mut discog.items, sorting.tracks);
+        }
 
-            // create the config file
-            let default_config = serde_yaml::to_string(&serde_json::json!({
-                "server": server.trim(),
-                "username": username.trim(),
-                "password": password.trim(),
-            })).unwrap();
-
-            match std::fs::create_dir_all(config_dir.join("jellyfin-tui")) {
-                Ok(_) => {
-                    std::fs::write(config_file.clone(), default_config).expect("[!!] Could not write default config");
-                    println!("\n[OK] Created default config file at: {}", config_file.to_str().unwrap());
-                },
-                Err(_) => {
-                    println!("[!!] Could not create config directory");
-                    std::process::exit(1);
+        return Ok(discog);
+    }
+
+    /// Searches all songs by name
+    ///
+    pub async fn search(&self, term: &str) -> Result<Discography, reqwest::Error> {
+        let url = format!("{}/Users/{}/Items", self.base_url, self.user_id);
+
+        let mut results = Discography { items: vec![] };
+        for parent in self.parents() {
+            let request = self.http_client
+                .get(&url)
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "text/json")
+                .query(&[
+                    ("SearchTerm", term),
+                    ("SortBy", "Album,IndexNumber"),
+                    ("SortOrder", "Ascending"),
+                    ("Recursive", "true"),
+                    ("IncludeItemTypes", "Audio"),
+                    ("Fields", "Genres, DateCreated, MediaSources, ParentId"),
+                    ("ImageTypeLimit", "1"),
+                    ("Limit", SEARCH_LIMIT.to_string().as_str()),
+                ]);
+            let response = in_library(request, parent).send().await;
+
+            if let Ok(json) = response {
+                let found: Discography = json.json().await.unwrap_or_else(|_| Discography {
+                    items: vec![],
+                });
+                results.items.extend(found.items);
+            }
+        }
+        if self.merging() {
+            sort_tracks(&mut results.items, SortBy::Album);
+            results.items.truncate(SEARCH_LIMIT);
+        }
+
+        Ok(results)
+    }
+
+    /// Fetches tracks by id, in the order the ids are given. Long lists take several requests
+    ///
+    pub async fn items(&self, ids: &[String]) -> Result<Discography, reqwest::Error> {
+        let url = format!("{}/Users/{}/Items", self.base_url, self.user_id);
+
+        let mut results = Discography { items: vec![] };
+        for chunk in ids.chunks(IDS_PER_REQUEST) {
+            let response = self.http_client
+                .get(&url)
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "text/json")
+                .query(&[
+                    ("Ids", chunk.join(",").as_str()),
+                    ("Fields", "Genres, DateCreated, MediaSources, ParentId"),
+                    ("ImageTypeLimit", "1"),
+                ])
+                .send()
+                .await?
+                .error_for_status()?;
+            let chunk: Discography = response.json().await?;
+            results.items.extend(chunk.items);
+        }
+        // the server returns them in its own order
+        results.items.sort_by_key(|item| ids.iter().position(|id| *id == item.id));
+        Ok(results)
+    }
+
+    /// Tracks of an album in disc and track order
+    ///
+    pub async fn album(&self, album_id: &str) -> Result<Discography, reqwest::Error> {
+        let url = format!("{}/Users/{}/Items", self.base_url, self.user_id);
+
+        let response = self.http_client
+            .get(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .query(&[
+                ("ParentId", album_id),
+                ("SortBy", "ParentIndexNumber,IndexNumber"),
+                ("SortOrder", "Ascending"),
+                ("IncludeItemTypes", "Audio"),
+                ("Fields", "Genres, DateCreated, MediaSources, ParentId"),
+                ("ImageTypeLimit", "1"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let tracks: Discography = response.json().await?;
+        Ok(tracks)
+    }
+
+    /// Favorite artists, then albums, then songs, each sorted by name
+    ///
+    pub async fn favorites(&self) -> Result<Vec<Item>, reqwest::Error> {
+        let mut artists: Vec<Item> = vec![];
+        let mut items: Vec<Item> = vec![];
+        for parent in self.parents() {
+            // artists are not found by a recursive item query, they have their own endpoint
+            let request = self.http_client
+                .get(format!("{}/Artists", self.base_url))
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "application/json")
+                .query(&[
+                    ("UserId", self.user_id.as_str()),
+                    ("Filters", "IsFavorite"),
+                    ("SortBy", "SortName"),
+                    ("SortOrder", "Ascending"),
+                ]);
+            let response = in_library(request, parent).send().await?.error_for_status()?;
+            let found: Items = response.json().await?;
+            for artist in found.items {
+                if !artists.iter().any(|a| a.id == artist.id) {
+                    artists.push(artist);
                 }
             }
-        } else {
-            println!("[OK] Found config file at: {}", config_file.to_str().unwrap());
+
+            let request = self.http_client
+                .get(format!("{}/Users/{}/Items", self.base_url, self.user_id))
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "application/json")
+                .query(&[
+                    ("Filters", "IsFavorite"),
+                    ("IncludeItemTypes", "MusicAlbum,Audio"),
+                    ("Recursive", "true"),
+                    ("SortBy", "SortName"),
+                    ("SortOrder", "Ascending"),
+                ]);
+            let response = in_library(request, parent).send().await?.error_for_status()?;
+            let found: Items = response.json().await?;
+            items.extend(found.items);
+        }
+        if self.merging() {
+            artists.sort_by_key(|artist| artist.name.to_lowercase());
+            items.sort_by_key(|item| item.name.to_lowercase());
+        }
+        // albums first
+        items.sort_by_key(|item| item.type_ != "MusicAlbum");
+
+        let mut favorites = artists;
+        for artist in favorites.iter_mut() {
+            artist.type_ = String::from("MusicArtist");
+        }
+        favorites.extend(items);
+        Ok(favorites)
+    }
+
+    /// Tracks similar to an item: a track, album, artist, genre or playlist. The server picks
+    /// them, the item's own tracks usually come first. Not limited to the libraries being
+    /// browsed: the endpoint takes no ParentId and the mix is meant to wander off the seed
+    ///
+    pub async fn instant_mix(&self, item_id: &str, limit: u64) -> Result<Discography, reqwest::Error> {
+        let url = format!("{}/Items/{}/InstantMix", self.base_url, item_id);
+
+        let response = self.http_client
+            .get(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .query(&[
+                ("UserId", self.user_id.as_str()),
+                ("Limit", limit.to_string().as_str()),
+                ("Fields", "Genres, DateCreated, MediaSources, ParentId"),
+                ("ImageTypeLimit", "1"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let tracks: Discography = response.json().await?;
+        Ok(tracks)
+    }
+
+    /// Albums and songs most recently added to the library, newest first. Songs added together
+    /// come back as their album
+    ///
+    pub async fn latest(&self, limit: u64) -> Result<Vec<Item>, reqwest::Error> {
+        let url = format!("{}/Users/{}/Items/Latest", self.base_url, self.user_id);
+
+        let mut items: Vec<Item> = vec![];
+        for parent in self.parents() {
+            let request = self.http_client
+                .get(&url)
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "application/json")
+                .query(&[
+                    ("IncludeItemTypes", "Audio"),
+                    ("GroupItems", "true"),
+                    ("Fields", "DateCreated"),
+                    ("Limit", limit.to_string().as_str()),
+                ]);
+            let response = in_library(request, parent).send().await?.error_for_status()?;
+
+            // a plain list, not wrapped in Items
+            let found: Vec<Item> = response.json().await?;
+            items.extend(found);
+        }
+        if self.merging() {
+            sort_items(&mut items, "DateCreated");
+            items.truncate(limit as usize);
+        }
+        Ok(items)
+    }
+
+    /// The first items of a type in some order, highest first, e.g. the most played songs
+    ///
+    pub async fn top_items(&self, item_type: &str, sort_by: &str, filters: Option<&str>, limit: u64) -> Result<Vec<Item>, reqwest::Error> {
+        let url = format!("{}/Users/{}/Items", self.base_url, self.user_id);
+
+        let mut items: Vec<Item> = vec![];
+        for parent in self.parents() {
+            let mut request = self.http_client
+                .get(&url)
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "application/json")
+                .query(&[
+                    ("IncludeItemTypes", item_type),
+                    ("SortBy", sort_by),
+                    ("SortOrder", "Descending"),
+                    ("Recursive", "true"),
+                    ("Fields", "DateCreated"),
+                    ("Limit", limit.to_string().as_str()),
+                ]);
+            if let Some(filters) = filters {
+                request = request.query(&[("Filters", filters)]);
+            }
+            let response = in_library(request, parent).send().await?.error_for_status()?;
+
+            let found: Items = response.json().await?;
+            items.extend(found.items);
+        }
+        if self.merging() {
+            sort_items(&mut items, sort_by);
+            items.truncate(limit as usize);
         }
+        Ok(items)
+    }
 
-        let f = std::fs::File::open(config_file).unwrap();
-        let d: Value = serde_yaml::from_reader(f).unwrap();
+    /// All music genres, sorted by name
+    ///
+    pub async fn genres(&self) -> Result<Vec<Genre>, reqwest::Error> {
+        let mut genres: Vec<Genre> = vec![];
+        for parent in self.parents() {
+            let request = self.http_client
+                .get(format!("{}/MusicGenres", self.base_url))
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "application/json")
+                .query(&[
+                    ("UserId", self.user_id.as_str()),
+                    ("SortBy", "SortName"),
+                    ("SortOrder", "Ascending"),
+                    ("Recursive", "true"),
+                ]);
+            let response = in_library(request, parent).send().await?.error_for_status()?;
 
-        let http_client = reqwest::Client::new();
-        let _credentials: Credentials = {
-            let username = match d["username"].as_str() {
-                Some(s) => String::from(s),
-                None => {
-                    println!("[!!] Could not find username in config file");
-                    std::process::exit(1);
+            let found: Genres = response.json().await?;
+            for genre in found.items {
+                if !genres.iter().any(|g| g.id == genre.id) {
+                    genres.push(genre);
                 }
-            };
-            let password = match d["password"].as_str() {
-                Some(s) => String::from(s),
-                None => {
-                    println!("[!!] Could not find password in config file");
-                    std::process::exit(1);
+            }
+        }
+        if self.merging() {
+            genres.sort_by_key(|genre| genre.name.to_lowercase());
+        }
+        Ok(genres)
+    }
+
+    /// Artists, then albums of a genre, each sorted by name
+    ///
+    pub async fn genre_items(&self, genre_id: &str) -> Result<Vec<Item>, reqwest::Error> {
+        let mut artists: Vec<Item> = vec![];
+        let mut albums: Vec<Item> = vec![];
+        for parent in self.parents() {
+            let request = self.http_client
+                .get(format!("{}/Artists", self.base_url))
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "application/json")
+                .query(&[
+                    ("UserId", self.user_id.as_str()),
+                    ("GenreIds", genre_id),
+                    ("SortBy", "SortName"),
+                    ("SortOrder", "Ascending"),
+                ]);
+            let response = in_library(request, parent).send().await?.error_for_status()?;
+            let found: Items = response.json().await?;
+            for artist in found.items {
+                if !artists.iter().any(|a| a.id == artist.id) {
+                    artists.push(artist);
                 }
-            };
-            Credentials {
-                username, password
             }
-        };
 
-        let server = match d["server"].as_str() {
-            Some(s) => s,
-            None => {
-                println!("[!!] Could not find server in config file");
-                std::process::exit(1);
+            let request = self.http_client
+                .get(format!("{}/Users/{}/Items", self.base_url, self.user_id))
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "application/json")
+                .query(&[
+                    ("GenreIds", genre_id),
+                    ("IncludeItemTypes", "MusicAlbum"),
+                    ("Recursive", "true"),
+                    ("SortBy", "SortName"),
+                    ("SortOrder", "Ascending"),
+                ]);
+            let response = in_library(request, parent).send().await?.error_for_status()?;
+            let found: Items = response.json().await?;
+            albums.extend(found.items);
+        }
+        if self.merging() {
+            artists.sort_by_key(|artist| artist.name.to_lowercase());
+            albums.sort_by_key(|album| album.name.to_lowercase());
+        }
+
+        let mut items = artists;
+        for artist in items.iter_mut() {
+            artist.type_ = String::from("MusicArtist");
+        }
+        items.extend(albums);
+        Ok(items)
+    }
+
+    /// Tracks of a genre. With SortBy::Random and a limit this is a shuffled sample of it
+    ///
+    pub async fn genre_tracks(&self, genre_id: &str, sort: SortBy, limit: Option<u64>) -> Result<Discography, reqwest::Error> {
+        let url = format!("{}/Users/{}/Items", self.base_url, self.user_id);
+        let (sort_by, sort_order) = sort.track_query();
+
+        let mut tracks = Discography { items: vec![] };
+        for parent in self.parents() {
+            let mut request = self.http_client
+                .get(&url)
+                .header("X-MediaBrowser-Token", self.access_token.to_string())
+                .header("x-emby-authorization", authorization(&self.device_id))
+                .header("Content-Type", "application/json")
+                .query(&[
+                    ("GenreIds", genre_id),
+                    ("SortBy", sort_by),
+                    ("SortOrder", sort_order),
+                    ("Recursive", "true"),
+                    ("IncludeItemTypes", "Audio"),
+                    ("Fields", "Genres, DateCreated, MediaSources, ParentId"),
+                    ("ImageTypeLimit", "1"),
+                ]);
+            if let Some(limit) = limit {
+                request = request.query(&[("Limit", limit)]);
+            }
+            let response = in_library(request, parent).send().await?.error_for_status()?;
+
+            let found: Discography = response.json().await?;
+            tracks.items.extend(found.items);
+        }
+        if self.merging() {
+            sort_tracks(&mut tracks.items, sort);
+            if let Some(limit) = limit {
+                tracks.items.truncate(limit as usize);
             }
+        }
+        Ok(tracks)
+    }
+
+    /// Marks an item (track, album, artist) as favorite or takes it off the favorites
+    ///
+    pub async fn set_favorite(&self, item_id: &str, favorite: bool) -> Result<(), reqwest::Error> {
+        let url = format!("{}/Users/{}/FavoriteItems/{}", self.base_url, self.user_id, item_id);
+
+        let request = match favorite {
+            true => self.http_client.post(url),
+            false => self.http_client.delete(url),
         };
+        request
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
 
-        let url: String = String::new() + server + "/Users/authenticatebyname";
-        let response = http_client
+    /// Creates a playlist on the server containing the given items, returns its id
+    ///
+    pub async fn create_playlist(&self, name: &str, item_ids: Vec<String>) -> Result<String, Box<dyn Error>> {
+        let url = format!("{}/Playlists", self.base_url);
+
+        let response = self.http_client
             .post(url)
-            .header("Content-Type", "text/json")
-            .header("x-emby-authorization", "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"None\", Version=\"10.4.3\"")
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
             .json(&serde_json::json!({
-                "Username": _credentials.username,
-                "Pw": _credentials.password,
+                "Name": name,
+                "Ids": item_ids,
+                "UserId": self.user_id,
+                "MediaType": "Audio",
             }))
             .send()
-            .await;
+            .await?
+            .error_for_status()?;
 
-        match response {
-            Ok(json) => {
-                let value = match json.json::<Value>().await {
-                    Ok(v) => v,
-                    Err(e) => {
-                        println!("[!!] Error authenticating: {}", e);
-                        std::process::exit(1);
-                    }
-                };
-                let access_token = value["AccessToken"].as_str().unwrap_or_else(|| {
-                    println!("[!!] Could not get access token");
-                    std::process::exit(1);
-                });
-                let user_id = value["User"]["Id"].as_str().unwrap_or_else(|| {
-                    println!("[!!] Could not get user id");
-                    std::process::exit(1);
-                });
-                return Self {
-                    base_url: server.to_string(),
-                    http_client,
-                    access_token: access_token.to_string(),
-                    user_id: user_id.to_string(),
-                }
-            },
-            Err(e) => {
-                println!("[!!] Error authenticating: {}", e);
-                std::process::exit(1);
-            }
-        }
+        let created: Value = response.json().await?;
+        Ok(created["Id"].as_str().unwrap_or("").to_string())
     }
 
-    /// Produces a list of artists, called by the main function before initializing the app
-    /// 
-    pub async fn artists(&self) -> Result<Vec<Artist>, reqwest::Error> {
-        let url = format!("{}/Artists", self.base_url);
-        println!("[OK] Streaming from jellyfin at: {}", url);
+    /// Lists the user's music playlists. The server has no listing under /Playlists, they are
+    /// regular items. They belong to the user rather than to a library, so all of them are
+    /// listed whichever libraries are browsed
+    ///
+    pub async fn playlists(&self) -> Result<Vec<Playlist>, reqwest::Error> {
+        let url = format!("{}/Users/{}/Items", self.base_url, self.user_id);
 
-        let response: Result<reqwest::Response, reqwest::Error> = self.http_client
+        let response = self.http_client
             .get(url)
             .header("X-MediaBrowser-Token", self.access_token.to_string())
-            .header("x-emby-authorization", "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"None\", Version=\"10.4.3\"")
-            .header("Content-Type", "text/json")
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
             .query(&[
+                ("IncludeItemTypes", "Playlist"),
+                ("MediaTypes", "Audio"),
+                ("Recursive", "true"),
                 ("SortBy", "SortName"),
-                ("SortOrder", "Ascending"), 
-                ("Recursive", "true"), 
-                ("Fields", "SortName"), 
-                ("ImageTypeLimit", "-1")
+                ("SortOrder", "Ascending"),
+                ("Fields", "ChildCount"),
             ])
-            .query(&[("StartIndex", "0")])
             .send()
-            .await;
+            .await?
+            .error_for_status()?;
 
-        let artists = match response {
-            Ok(json) => {
-                let artists: Artists = json.json().await.unwrap_or_else(|_| Artists {
-                    items: vec![],
-                    start_index: 0,
-                    total_record_count: 0,
-                });
-                artists
-            },
-            Err(_) => {
-                return Ok(vec![]);
-            }
-        };
-
-        Ok(artists.items)
+        let playlists: Playlists = response.json().await?;
+        Ok(playlists.items)
     }
 
-    /// Produces a list of songs by an artist sorted by album and index
-    /// 
-    pub async fn discography(&self, id: &str) -> Result<Discography, reqwest::Error> {
-        let url = format!("{}/Users/{}/Items", self.base_url, self.user_id);
+    /// Tracks of a playlist in playlist order. Each carries its playlist_item_id, which is what
+    /// removing and moving entries work with
+    ///
+    pub async fn playlist_items(&self, playlist_id: &str) -> Result<Discography, reqwest::Error> {
+        let url = format!("{}/Playlists/{}/Items", self.base_url, playlist_id);
 
         let response = self.http_client
             .get(url)
             .header("X-MediaBrowser-Token", self.access_token.to_string())
-            .header("x-emby-authorization", "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"None\", Version=\"10.4.3\"")
-            .header("Content-Type", "text/json")
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
             .query(&[
-                ("SortBy", "Album,IndexNumber"),
-                ("SortOrder", "Ascending"),
-                ("Recursive", "true"), 
-                ("IncludeItemTypes", "Audio"),
+                ("UserId", self.user_id.as_str()),
                 ("Fields", "Genres, DateCreated, MediaSources, ParentId"),
-                ("StartIndex", "0"),
                 ("ImageTypeLimit", "1"),
-                ("ArtistIds", id)
             ])
-            .query(&[("StartIndex", "0")])
             .send()
-            .await;
+            .await?
+            .error_for_status()?;
 
-        let discog = match response {
-            Ok(json) => {
-                let discog: Discography = json.json().await.unwrap_or_else(|_| Discography {
-                    items: vec![],
-                });
-                discog
-            },
-            Err(_) => {
-                return Ok(Discography { items: vec![] });
-            }
-        };
+        let items: Discography = response.json().await?;
+        Ok(items)
+    }
 
-        return Ok(discog);
+    /// Appends items to a playlist
+    ///
+    pub async fn add_to_playlist(&self, playlist_id: &str, item_ids: &[String]) -> Result<(), reqwest::Error> {
+        let url = format!("{}/Playlists/{}/Items", self.base_url, playlist_id);
+
+        self.http_client
+            .post(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .query(&[
+                ("Ids", item_ids.join(",").as_str()),
+                ("UserId", self.user_id.as_str()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Removes entries, by playlist_item_id, from a playlist
+    ///
+    pub async fn remove_from_playlist(&self, playlist_id: &str, entry_ids: &[String]) -> Result<(), reqwest::Error> {
+        let url = format!("{}/Playlists/{}/Items", self.base_url, playlist_id);
+
+        self.http_client
+            .delete(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .query(&[("EntryIds", entry_ids.join(","))])
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Moves a playlist entry to a new position
+    ///
+    pub async fn move_playlist_item(&self, playlist_id: &str, entry_id: &str, new_index: usize) -> Result<(), reqwest::Error> {
+        let url = format!("{}/Playlists/{}/Items/{}/Move/{}", self.base_url, playlist_id, entry_id, new_index);
+
+        self.http_client
+            .post(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
     }
 
-    /// Returns a list of lyrics lines for a song
+    /// Deletes a playlist from the server
     ///
-    pub async fn lyrics(&self, song_id: String) -> Result<Vec<String>, reqwest::Error> {
+    pub async fn delete_playlist(&self, playlist_id: &str) -> Result<(), reqwest::Error> {
+        let url = format!("{}/Items/{}", self.base_url, playlist_id);
+
+        self.http_client
+            .delete(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Returns a list of lyrics lines for a song. Synced lyrics carry their start time
+    ///
+    pub async fn lyrics(&self, song_id: String) -> Result<Vec<Lyric>, reqwest::Error> {
         let url = format!("{}/Audio/{}/Lyrics", self.base_url, song_id);
 
         let response = self.http_client
             .get(url)
             .header("X-MediaBrowser-Token", self.access_token.to_string())
-            .header("x-emby-authorization", "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"None\", Version=\"10.4.3\"")
+            .header("x-emby-authorization", authorization(&self.device_id))
             .header("Content-Type", "application/json")
             .send()
             .await;
@@ -287,7 +851,7 @@ impl Client {
             Err(_) => {
                 return Ok(vec![]);
             }
-        }.lyrics.iter().map(|l| format!(" {}", l.text)).collect();
+        }.lyrics.into_iter().map(|l| Lyric { text: format!(" {}", l.text), start: l.start }).collect();
 
         return Ok(lyric);
     }
@@ -300,7 +864,7 @@ impl Client {
         let response = self.http_client
             .get(url)
             .header("X-MediaBrowser-Token", self.access_token.to_string())
-            .header("x-emby-authorization", "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"None\", Version=\"10.4.3\"")
+            .header("x-emby-authorization", authorization(&self.device_id))
             .header("Content-Type", "application/json")
             .send()
             .await?;
@@ -332,14 +896,33 @@ impl Client {
 
     }
 
-    /// Downloads cover art for an album and saves it as cover.*, filename is returned
+    /// The user's play state of an item: resume position, last played date, ...
+    ///
+    pub async fn user_data(&self, item_id: &str) -> Result<UserData, Box<dyn Error>> {
+        let url = format!("{}/Users/{}/Items/{}", self.base_url, self.user_id, item_id);
+
+        let response = self.http_client
+            .get(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let item: Value = response.json().await?;
+        Ok(serde_json::from_value(item["UserData"].clone())?)
+    }
+
+    /// Downloads cover art for an album and saves it as cover.* in the profile's cache, the
+    /// path is returned
     /// 
     pub async fn download_cover_art(&self, album_id: String) -> Result<String, Box<dyn Error>> {
         let url = format!("{}/Items/{}/Images/Primary?fillHeight=512&fillWidth=512&quality=96&tag=be2a8642e97e2151ef0580fc72f3505a", self.base_url, album_id);
         let response = self.http_client
             .get(url)
             .header("X-MediaBrowser-Token", self.access_token.to_string())
-            .header("x-emby-authorization", "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"None\", Version=\"10.4.3\"")
+            .header("x-emby-authorization", authorization(&self.device_id))
             .header("Content-Type", "application/json")
             .send()
             .await?;
@@ -361,20 +944,25 @@ impl Client {
             _ => "png",
         };
 
-        std::fs::create_dir_all("covers")?;
+        let dir = self.profile.cache_dir().ok_or("could not find the cache directory")?.join("covers");
+        std::fs::create_dir_all(&dir)?;
 
-        let mut file = std::fs::File::create("covers/cover.".to_string() + extension)?;
+        let path = dir.join("cover.".to_string() + extension);
+        let mut file = std::fs::File::create(&path)?;
         let mut content =  Cursor::new(response.bytes().await?);
         std::io::copy(&mut content, &mut file)?;
 
-        Ok("cover.".to_string() + extension)
+        Ok(path.to_string_lossy().to_string())
     }
 
     /// Produces URL of a song from its ID
     pub fn song_url_sync(&self, song_id: String) -> String {
         let url = format!("{}/Audio/{}/universal", self.base_url, song_id);
         let url = url + &format!("?UserId={}&Container=opus,webm|opus,mp3,aac,m4a|aac,m4b|aac,flac,webma,webm|webma,wav,ogg&TranscodingContainer=mp4&TranscodingProtocol=hls&AudioCodec=aac&api_key={}&StartTimeTicks=0&EnableRedirection=true&EnableRemoteMedia=false", self.user_id, self.access_token);
-        url
+        match self.max_bitrate {
+            Some(kbps) => url + &format!("&MaxStreamingBitrate={}", kbps * 1000),
+            None => url,
+        }
     }
     /// Sends a 'playing' event to the server
     /// 
@@ -383,7 +971,7 @@ impl Client {
         let _response = self.http_client
             .post(url)
             .header("X-MediaBrowser-Token", self.access_token.to_string())
-            .header("x-emby-authorization", "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"None\", Version=\"10.4.3\"")
+            .header("x-emby-authorization", authorization(&self.device_id))
             .header("Content-Type", "application/json")
             .json(&serde_json::json!({
                 "ItemId": song_id,
@@ -403,7 +991,7 @@ impl Client {
         let _response = self.http_client
             .post(url)
             .header("X-MediaBrowser-Token", self.access_token.to_string())
-            .header("x-emby-authorization", "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"None\", Version=\"10.4.3\"")
+            .header("x-emby-authorization", authorization(&self.device_id))
             .header("Content-Type", "application/json")
             .json(&serde_json::json!({
                 "ItemId": song_id,
@@ -416,21 +1004,237 @@ impl Client {
 
         Ok(())
     }
+
+    /// Lists the other sessions this user can remote control, most recently active first
+    ///
+    pub async fn sessions(&self) -> Result<Vec<Session>, reqwest::Error> {
+        let url = format!("{}/Sessions", self.base_url);
+
+        let response = self.http_client
+            .get(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .query(&[
+                ("ControllableByUserId", self.user_id.as_str()),
+                ("ActiveWithinSeconds", "960"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let sessions: Vec<Session> = response.json().await?;
+        // our own session is controlled from right here
+        Ok(sessions.into_iter().filter(|s| s.device_id != self.device_id).collect())
+    }
+
+    /// Sends a playstate command (Pause, Unpause, PlayPause, NextTrack, PreviousTrack, Stop, Seek)
+    /// to another session
+    ///
+    pub async fn session_playstate(&self, session_id: &str, command: &str, seek_ticks: Option<u64>) -> Result<(), reqwest::Error> {
+        let url = format!("{}/Sessions/{}/Playing/{}", self.base_url, session_id, command);
+
+        let mut request = self.http_client
+            .post(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json");
+        if let Some(ticks) = seek_ticks {
+            request = request.query(&[("SeekPositionTicks", ticks.to_string())]);
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+
+    /// Makes another session play items. `play_command` is PlayNow, PlayNext or PlayLast
+    ///
+    pub async fn session_play(&self, session_id: &str, item_ids: &[String], play_command: &str) -> Result<(), reqwest::Error> {
+        let url = format!("{}/Sessions/{}/Playing", self.base_url, session_id);
+
+        self.http_client
+            .post(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .query(&[
+                ("ItemIds", item_ids.join(",").as_str()),
+                ("PlayCommand", play_command),
+            ])
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Sends a general command like SetVolume or ToggleMute to another session. Argument values
+    /// are strings, {"Volume": "40"}
+    ///
+    pub async fn session_command(&self, session_id: &str, name: &str, arguments: Value) -> Result<(), reqwest::Error> {
+        let url = format!("{}/Sessions/{}/Command", self.base_url, session_id);
+
+        self.http_client
+            .post(url)
+            .header("X-MediaBrowser-Token", self.access_token.to_string())
+            .header("x-emby-authorization", authorization(&self.device_id))
+            .header("Content-Type", "application/json")
+            .json(&serde_json::json!({
+                "Name": name,
+                "Arguments": arguments,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// The x-emby-authorization header sent with every request, for the device the token was
+/// given to
+fn authorization(device_id: &str) -> String {
+    format!(
+        "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"{}\", Version=\"10.4.3\"",
+        device_id
+    )
+}
+
+/// Tells this install apart from other jellyfin-tuis logged in as the same user, so they show
+/// up as sessions of their own. Made up on the first run and kept in the state directory
+pub fn device_id() -> &'static str {
+    static DEVICE_ID: OnceLock<String> = OnceLock::new();
+    DEVICE_ID.get_or_init(|| {
+        let path = config::state_dir().map(|dir| dir.join("device_id"));
+        let saved = path.as_ref().and_then(|path| std::fs::read_to_string(path).ok());
+        if let Some(id) = saved.map(|id| id.trim().to_string()).filter(|id| !id.is_empty()) {
+            return id;
+        }
+        let id = format!("{:032x}", rand::random::<u128>());
+        if let Some(path) = path {
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::create_dir_all(dir);
+            }
+            let _ = std::fs::write(path, &id);
+        }
+        id
+    })
+}
+
+/// Limits a query to one library, see Client::parents
+fn in_library(request: reqwest::RequestBuilder, parent: Option<&str>) -> reqwest::RequestBuilder {
+    match parent {
+        Some(parent) => request.query(&[("ParentId", parent)]),
+        None => request,
+    }
+}
+
+/// Sorts artists merged from several libraries the way Sorting::artist_query asks the server to
+fn sort_artists(artists: &mut [Artist], sort: SortBy) {
+    let name = |artist: &Artist| match artist.sort_name.is_empty() {
+        true => artist.name.to_lowercase(),
+        false => artist.sort_name.to_lowercase(),
+    };
+    match sort {
+        SortBy::Name | SortBy::Album => artists.sort_by_key(name),
+        SortBy::DateAdded => artists.sort_by(|a, b| {
+            (&b.date_created, name(a)).cmp(&(&a.date_created, name(b)))
+        }),
+        SortBy::Year => artists.sort_by_key(|a| (a.production_year, name(a))),
+        SortBy::PlayCount => artists.sort_by(|a, b| {
+            (b.user_data.play_count, name(a)).cmp(&(a.user_data.play_count, name(b)))
+        }),
+        SortBy::Random => artists.shuffle(&mut rand::thread_rng()),
+    }
+}
+
+/// Sorts tracks merged from several libraries the way SortBy::track_query asks the server to
+fn sort_tracks(tracks: &mut [DiscographySong], sort: SortBy) {
+    match sort.local_key() {
+        // local_key only gives keys columns::sort knows
+        Some(key) => { let _ = columns::sort(tracks, key, false); },
+        None => tracks.shuffle(&mut rand::thread_rng()),
+    }
+}
+
+/// Sorts items merged from several libraries by one of the server's SortBy fields, descending
+/// as top_items and latest ask for
+fn sort_items(items: &mut [Item], sort_by: &str) {
+    match sort_by {
+        "DateCreated" => items.sort_by(|a, b| b.date_created.cmp(&a.date_created)),
+        "DatePlayed" => items.sort_by(|a, b| {
+            let played = |item: &Item| item.user_data.as_ref().and_then(|u| u.last_played_date.clone());
+            played(b).cmp(&played(a))
+        }),
+        "PlayCount" => items.sort_by_key(|item| {
+            std::cmp::Reverse(item.user_data.as_ref().map(|u| u.play_count).unwrap_or(0))
+        }),
+        "Random" => items.shuffle(&mut rand::thread_rng()),
+        _ => items.sort_by_key(|item| item.name.to_lowercase()),
+    }
+}
+
+/// Asks a server for its name and version without logging in, to see if it can be reached
+pub async fn server_info(base_url: &str) -> Result<PublicInfo, reqwest::Error> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()?;
+    client
+        .get(format!("{}/System/Info/Public", base_url.trim_end_matches('/')))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<PublicInfo>()
+        .await
+}
+
+/// Ends the session a saved login's token belongs to, so the server stops accepting it
+pub async fn log_out(login: &Login) -> Result<(), reqwest::Error> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()?;
+    client
+        .post(format!("{}/Sessions/Logout", login.server.trim_end_matches('/')))
+        .header("X-MediaBrowser-Token", login.access_token.to_string())
+        .header("x-emby-authorization", authorization(&login.device_id))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+/// Tells the server this session can be remote controlled, so it shows up as a cast target
+/// in the web and mobile apps. Commands then arrive over the WebSocket
+pub async fn report_capabilities(base_url: String, access_token: String, device_id: String) -> Result<(), reqwest::Error> {
+    let url = format!("{}/Sessions/Capabilities/Full", base_url);
+    let client = reqwest::Client::new();
+    client
+        .post(url)
+        .header("X-MediaBrowser-Token", access_token.to_string())
+        .header("x-emby-authorization", authorization(&device_id))
+        .header("Content-Type", "application/json")
+        .json(&serde_json::json!({
+            "PlayableMediaTypes": ["Audio"],
+            "SupportedCommands": ["SetVolume", "ToggleMute", "Mute", "Unmute", "VolumeUp", "VolumeDown"],
+            "SupportsMediaControl": true,
+            "SupportsPersistentIdentifier": false,
+        }))
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
 }
 
 /// {"VolumeLevel":94,"IsMuted":true,"IsPaused":false,"RepeatMode":"RepeatNone","ShuffleMode":"Sorted","MaxStreamingBitrate":4203311,"PositionTicks":31637660,"PlaybackStartTimeTicks":17171041814570000,"PlaybackRate":1,"SecondarySubtitleStreamIndex":-1,"BufferedRanges":[{"start":0,"end":1457709999.9999998}],"PlayMethod":"Transcode","PlaySessionId":"1717104167942","PlaylistItemId":"playlistItem0","MediaSourceId":"77fb3ec1b0c2a027c2651771c7268e79","CanSeek":true,"ItemId":"77fb3ec1b0c2a027c2651771c7268e79","EventName":"timeupdate"}
-pub async fn report_progress(base_url: String, access_token: String, pr: ProgressReport) -> Result<(), reqwest::Error> {
+pub async fn report_progress(base_url: String, access_token: String, device_id: String, pr: ProgressReport) -> Result<(), reqwest::Error> {
     let url = format!("{}/Sessions/Playing/Progress", base_url);
     // new http client, this is a pure function so we can create a new one
     let client = reqwest::Client::new();
     let _response = client
         .post(url)
         .header("X-MediaBrowser-Token", access_token.to_string())
-        .header("x-emby-authorization", "MediaBrowser Client=\"jellyfin-tui\", Device=\"jellyfin-tui\", DeviceId=\"None\", Version=\"10.4.3\"")
+        .header("x-emby-authorization", authorization(&device_id))
         .header("Content-Type", "application/json")
         .json(&serde_json::json!({
             "VolumeLevel": pr.volume_level,
-            "IsMuted": false,
+            "IsMuted": pr.is_muted,
             "IsPaused": pr.is_paused,
             "ShuffleMode": "Sorted",
             "PositionTicks": pr.position_ticks,
@@ -483,6 +1287,14 @@ pub async fn report_progress(base_url: String, access_token: String, pr: Progres
   "LocationType": "FileSystem",
   "MediaType": "Unknown"
 } */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicInfo {
+    #[serde(rename = "ServerName", default)]
+    pub server_name: String,
+    #[serde(rename = "Version", default)]
+    pub version: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Artists {
     #[serde(rename = "Items")]
@@ -500,12 +1312,16 @@ pub struct Artist {
     pub id: String,
     #[serde(rename = "SortName", default)]
     sort_name: String,
+    #[serde(rename = "DateCreated", default)]
+    date_created: Option<String>,
+    #[serde(rename = "ProductionYear", default)]
+    production_year: Option<u64>,
     #[serde(rename = "RunTimeTicks", default)]
     run_time_ticks: u64,
     #[serde(rename = "Type", default)]
     type_: String,
     #[serde(rename = "UserData")]
-    user_data: UserData,
+    pub user_data: UserData,
     #[serde(rename = "ImageTags", default)]
     image_tags: serde_json::Value,
     #[serde(rename = "ImageBlurHashes", default)]
@@ -519,15 +1335,17 @@ pub struct Artist {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserData {
     #[serde(rename = "PlaybackPositionTicks")]
-    playback_position_ticks: u64,
+    pub playback_position_ticks: u64,
     #[serde(rename = "PlayCount")]
     play_count: u64,
     #[serde(rename = "IsFavorite")]
-    is_favorite: bool,
+    pub is_favorite: bool,
     #[serde(rename = "Played")]
     played: bool,
     #[serde(rename = "Key")]
     key: String,
+    #[serde(rename = "LastPlayedDate", default)]
+    pub last_played_date: Option<String>,
 }
 
 /// DISCOGRAPHY
@@ -710,6 +1528,86 @@ Object {
     },
 }, */
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Items {
+    #[serde(rename = "Items")]
+    items: Vec<Item>,
+}
+
+/// An artist, album or song in a list mixing them, like the favorites, a genre or a home section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Item {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    // MusicArtist, MusicAlbum or Audio
+    #[serde(rename = "Type", default)]
+    pub type_: String,
+    #[serde(rename = "AlbumArtist", default)]
+    pub album_artist: String,
+    #[serde(rename = "DateCreated", default)]
+    date_created: Option<String>,
+    #[serde(rename = "UserData", default)]
+    user_data: Option<UserData>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Libraries {
+    #[serde(rename = "Items")]
+    items: Vec<Library>,
+}
+
+/// One of the user's views, a library of some collection type
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Library {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "CollectionType", default)]
+    pub collection_type: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Genres {
+    #[serde(rename = "Items")]
+    items: Vec<Genre>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Genre {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "Name", default)]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Playlists {
+    #[serde(rename = "Items")]
+    items: Vec<Playlist>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Playlist {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "ChildCount", default)]
+    pub child_count: u64,
+}
+
+/// A genre as listed on a track
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenreItem {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "Id", default)]
+    pub id: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Discography {
     #[serde(rename = "Items")]
@@ -726,13 +1624,18 @@ pub struct DiscographySongUserData {
     #[serde(rename = "PlaybackPositionTicks")]
     playback_position_ticks: u64,
     #[serde(rename = "PlayCount")]
-    play_count: u64,
+    pub play_count: u64,
     #[serde(rename = "IsFavorite")]
-    is_favorite: bool,
+    pub is_favorite: bool,
     #[serde(rename = "Played")]
-    played: bool,
+    pub played: bool,
     #[serde(rename = "Key")]
     key: String,
+    #[serde(rename = "LastPlayedDate", default)]
+    pub last_played_date: Option<String>,
+    // the user's own rating, 0-10
+    #[serde(rename = "Rating", default)]
+    pub rating: Option<f64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -756,11 +1659,11 @@ pub struct DiscographySong {
     #[serde(rename = "ChannelId", default)]
     channel_id: Option<String>,
     #[serde(rename = "DateCreated", default)]
-    date_created: String,
-    // #[serde(rename = "GenreItems")]
-    // genre_items: Vec<Genre>,
+    pub date_created: String,
+    #[serde(rename = "GenreItems", default)]
+    pub genre_items: Vec<GenreItem>,
     #[serde(rename = "Genres", default)]
-    genres: Vec<String>,
+    pub genres: Vec<String>,
     #[serde(rename = "HasLyrics", default)]
     pub has_lyrics: bool,
     #[serde(rename = "Id", default)]
@@ -769,10 +1672,10 @@ pub struct DiscographySong {
     // image_blur_hashes: ImageBlurHashes,
     // #[serde(rename = "ImageTags")]
     // image_tags: ImageTags,
-    #[serde(rename = "IndexNumber")]
-    index_number: u64,
+    #[serde(rename = "IndexNumber", default)]
+    pub index_number: u64,
     #[serde(rename = "IsFolder", default)]
-    is_folder: bool,
+    pub is_folder: bool,
     // #[serde(rename = "LocationType")]
     // location_type: String,
     #[serde(rename = "MediaSources")]
@@ -781,6 +1684,8 @@ pub struct DiscographySong {
     media_type: String,
     #[serde(rename = "Name", default)]
     pub name: String,
+    #[serde(rename = "CommunityRating", default)]
+    pub community_rating: Option<f64>,
     #[serde(rename = "NormalizationGain", default)]
     normalization_gain: f64,
     // #[serde(rename = "ParentBackdropImageTags")]
@@ -790,7 +1695,10 @@ pub struct DiscographySong {
     #[serde(rename = "ParentId", default)]
     pub parent_id: String,
     #[serde(rename = "ParentIndexNumber", default)]
-    parent_index_number: u64,
+    pub parent_index_number: u64,
+    // only set for tracks fetched as part of a playlist
+    #[serde(rename = "PlaylistItemId", default)]
+    pub playlist_item_id: String,
     #[serde(rename = "PremiereDate", default)]
     premiere_date: String,
     #[serde(rename = "ProductionYear", default)]
@@ -802,7 +1710,7 @@ pub struct DiscographySong {
     // #[serde(rename = "Type")]
     // type_: String,
     #[serde(rename = "UserData")]
-    user_data: DiscographySongUserData,
+    pub user_data: DiscographySongUserData,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -835,10 +1743,12 @@ pub struct MediaStream {
     "Metadata": {},
     "Lyrics": [
         {
-            "Text": "Inside you\u0027re pretending"
+            "Text": "Inside you\u0027re pretending",
+            "Start": 112300000
         },
         {
-            "Text": "Crimes have been swept aside"
+            "Text": "Crimes have been swept aside",
+            "Start": 151200000
         },
     ]
 }
@@ -854,7 +1764,10 @@ pub struct Lyrics {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Lyric {
     #[serde(rename = "Text", default)]
-    text: String,
+    pub text: String,
+    // only present for synced lyrics, in ticks
+    #[serde(rename = "Start", default)]
+    pub start: Option<u64>,
 }
 
 /// {"VolumeLevel":94,"IsMuted":true,"IsPaused":false,"RepeatMode":"RepeatNone","ShuffleMode":"Sorted","MaxStreamingBitrate":4203311,"PositionTicks":31637660,"PlaybackStartTimeTicks":17171041814570000,"PlaybackRate":1,"SecondarySubtitleStreamIndex":-1,"BufferedRanges":[{"start":0,"end":1457709999.9999998}],"PlayMethod":"Transcode","PlaySessionId":"1717104167942","PlaylistItemId":"playlistItem0","MediaSourceId":"77fb3ec1b0c2a027c2651771c7268e79","CanSeek":true,"ItemId":"77fb3ec1b0c2a027c2651771c7268e79","EventName":"timeupdate"}
@@ -862,8 +1775,8 @@ pub struct Lyric {
 pub struct ProgressReport {
     #[serde(rename = "VolumeLevel")]
     pub volume_level: u64,
-    // #[serde(rename = "IsMuted")]
-    // is_muted: bool,
+    #[serde(rename = "IsMuted")]
+    pub is_muted: bool,
     #[serde(rename = "IsPaused")]
     pub is_paused: bool,
     // #[serde(rename = "RepeatMode")]
@@ -894,4 +1807,51 @@ pub struct ProgressReport {
     pub item_id: String,
     #[serde(rename = "EventName")]
     pub event_name: String,
-}
\ No newline at end of file
+}
+/// One entry of /Sessions, only what the Sessions view shows
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    #[serde(rename = "Id")]
+    pub id: String,
+    #[serde(rename = "DeviceId", default)]
+    pub device_id: String,
+    #[serde(rename = "DeviceName", default)]
+    pub device_name: String,
+    #[serde(rename = "Client", default)]
+    pub client: String,
+    #[serde(rename = "UserName", default)]
+    pub user_name: String,
+    #[serde(rename = "SupportsRemoteControl", default)]
+    pub supports_remote_control: bool,
+    #[serde(rename = "NowPlayingItem", default)]
+    pub now_playing_item: Option<NowPlayingItem>,
+    #[serde(rename = "PlayState", default)]
+    pub play_state: PlayState,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NowPlayingItem {
+    #[serde(rename = "Id", default)]
+    pub id: String,
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "AlbumArtist", default)]
+    pub album_artist: String,
+    #[serde(rename = "Album", default)]
+    pub album: String,
+    #[serde(rename = "RunTimeTicks", default)]
+    pub run_time_ticks: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlayState {
+    #[serde(rename = "PositionTicks", default)]
+    pub position_ticks: u64,
+    #[serde(rename = "IsPaused", default)]
+    pub is_paused: bool,
+    #[serde(rename = "IsMuted", default)]
+    pub is_muted: bool,
+    // missing when the client does not report it
+    #[serde(rename = "VolumeLevel", default)]
+    pub volume_level: Option<i64>,
+}
@@ -0,0 +1,117 @@
+//! TRACK COLUMNS
+//!
+//! Extra columns of the track list, shown after the duration. Which ones, and in what order, is
+//! set with the `columns` key of config.yaml:
+//!
+//! columns: [favorite, play_count, last_played]
+//!
+//! favorite     ♥ for favorites (the default)
+//! played       ✓ once the track has been played
+//! play_count   how often it was played
+//! last_played  the date it was last played
+//! rating       the user's rating, or the community rating when there is none
+//!
+//! `:sort <column>` orders the loaded tracks by a column, or by album, title, duration, year or
+//! date_added.
+use crate::client::DiscographySong;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Column {
+    Favorite,
+    Played,
+    PlayCount,
+    LastPlayed,
+    Rating,
+}
+
+impl Column {
+    /// Characters the column takes, including the gap before it
+    pub fn width(self) -> usize {
+        match self {
+            Column::Favorite | Column::Played => 2,
+            Column::PlayCount => 6,
+            Column::LastPlayed => 12,
+            Column::Rating => 6,
+        }
+    }
+
+    /// The cell for a track, right aligned to the column width
+    pub fn cell(self, track: &DiscographySong) -> String {
+        let user_data = &track.user_data;
+        let text = match self {
+            Column::Favorite if user_data.is_favorite => String::from("♥"),
+            Column::Played if user_data.played => String::from("✓"),
+            Column::PlayCount if user_data.play_count > 0 => format!("{}×", user_data.play_count),
+            Column::LastPlayed => match user_data.last_played_date {
+                // 2024-05-30T21:42:47.943Z
+                Some(ref date) => date.chars().take(10).collect(),
+                None => String::new(),
+            },
+            Column::Rating => match rating(track) {
+                Some(rating) => format!("★{:.1}", rating),
+                None => String::new(),
+            },
+            _ => String::new(),
+        };
+        format!("{:>width$}", text, width = self.width())
+    }
+}
+
+fn rating(track: &DiscographySong) -> Option<f64> {
+    track.user_data.rating.or(track.community_rating)
+}
+
+/// The `columns` key of config.yaml, just the favorite marker when it's not set
+pub fn from_config(config: &Value) -> Result<Vec<Column>, String> {
+    match config.get("columns") {
+        Some(Value::Null) | None => Ok(vec![Column::Favorite]),
+        Some(columns) => serde_yaml::from_value(columns.clone()).map_err(|e| e.to_string()),
+    }
+}
+
+/// What `:sort` accepts
+pub const SORT_KEYS: [&str; 10] = [
+    "album",
+    "title",
+    "duration",
+    "year",
+    "date_added",
+    "favorite",
+    "played",
+    "play_count",
+    "last_played",
+    "rating",
+];
+
+/// Sorts tracks by one of SORT_KEYS. Album, title, duration and year go up by default, the
+/// others start with the most played, most recent, ... `reverse` flips that
+pub fn sort(tracks: &mut [DiscographySong], key: &str, reverse: bool) -> Result<(), String> {
+    let compare: fn(&DiscographySong, &DiscographySong) -> Ordering = match key {
+        "album" => |a, b| {
+            (&a.album, a.parent_index_number, a.index_number).cmp(&(&b.album, b.parent_index_number, b.index_number))
+        },
+        "title" => |a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        "duration" => |a, b| a.run_time_ticks.cmp(&b.run_time_ticks),
+        "year" => |a, b| {
+            (a.production_year, &a.album, a.parent_index_number, a.index_number)
+                .cmp(&(b.production_year, &b.album, b.parent_index_number, b.index_number))
+        },
+        // ISO 8601 dates compare fine as strings
+        "date_added" => |a, b| b.date_created.cmp(&a.date_created),
+        "favorite" => |a, b| b.user_data.is_favorite.cmp(&a.user_data.is_favorite),
+        "played" => |a, b| b.user_data.played.cmp(&a.user_data.played),
+        "play_count" => |a, b| b.user_data.play_count.cmp(&a.user_data.play_count),
+        "last_played" => |a, b| b.user_data.last_played_date.cmp(&a.user_data.last_played_date),
+        "rating" => |a, b| rating(b).partial_cmp(&rating(a)).unwrap_or(Ordering::Equal),
+        _ => return Err(format!("Can't sort by '{}', try one of {}", key, SORT_KEYS.join(", "))),
+    };
+    match reverse {
+        true => tracks.sort_by(|a, b| compare(b, a)),
+        false => tracks.sort_by(compare),
+    }
+    Ok(())
+}
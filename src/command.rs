@@ -0,0 +1,299 @@
+//! COMMAND LINE
+//!
+//! cmus-style `:` commands. A command is parsed into the same Action a key binding produces, so
+//! everything the command line can do goes through App::dispatch.
+//!
+//! :seek +30     relative seek, also -30, 1:30 or 90 for absolute
+//! :vol 60       set volume, +5 / -5 to change it
+//! :mute         mute or unmute
+//! :play / :pause / :toggle
+//! :play <item id>...  replace the queue with these tracks
+//! :jump 3       play the third song in the queue
+//! :next / :prev
+//! :add          add the selected track to the queue, or :add <item id>... to add tracks by id
+//! :addto <playlist>  add the selected track to a playlist
+//! :clear        clear the queue
+//! :profile <name>  switch to another server profile, :profile lists them
+//! :radio        start a radio from the selection, or :radio <item id>
+//! :shuffle      toggle shuffle
+//! :sort play_count [reverse]  order the track list, see columns.rs
+//! :repeat       cycle repeat, or :repeat off / queue / track
+//! :save <name>  save the queue as a playlist on the server
+//! :search <term>
+//! :settings    edit config.yaml
+//! :quit
+use crate::config;
+use crate::keymap::Action;
+use crate::tui::Repeat;
+use std::fs;
+use std::path::PathBuf;
+
+/// (name, usage) of every command, used for completion and error messages
+pub const COMMANDS: [(&str, &str); 22] = [
+    ("add", ":add [item id...]"),
+    ("addto", ":addto <playlist name>"),
+    ("clear", ":clear"),
+    ("help", ":help"),
+    ("jump", ":jump <queue position>"),
+    ("mute", ":mute"),
+    ("next", ":next"),
+    ("pause", ":pause"),
+    ("play", ":play [item id...]"),
+    ("prev", ":prev"),
+    ("profile", ":profile [name]"),
+    ("quit", ":quit"),
+    ("radio", ":radio [item id]"),
+    ("repeat", ":repeat [off|queue|track]"),
+    ("save", ":save <playlist name>"),
+    ("search", ":search <term>"),
+    ("seek", ":seek [+-]<seconds|m:ss>"),
+    ("settings", ":settings"),
+    ("shuffle", ":shuffle"),
+    ("sort", ":sort <column> [reverse]"),
+    ("toggle", ":toggle"),
+    ("vol", ":vol [+-]<0-100>"),
+];
+
+const HISTORY_LIMIT: usize = 500;
+
+/// Turns a command line (without the leading colon) into an action
+pub fn parse(line: &str) -> Result<Action, String> {
+    let line = line.trim().trim_start_matches(':');
+    let (name, args) = match line.split_once(char::is_whitespace) {
+        Some((name, args)) => (name, args.trim()),
+        None => (line, ""),
+    };
+
+    // unambiguous prefixes are fine, :sh is :shuffle
+    let candidates = COMMANDS
+        .iter()
+        .filter(|(c, _)| c.starts_with(name))
+        .collect::<Vec<_>>();
+    let (name, usage) = match COMMANDS.iter().find(|(c, _)| *c == name) {
+        Some(command) => *command,
+        None => match candidates.as_slice() {
+            [command] => **command,
+            [] => return Err(format!("Unknown command: {}", name)),
+            _ => return Err(format!("Ambiguous command: {}", name)),
+        },
+    };
+
+    match name {
+        "add" if args.is_empty() => Ok(Action::Enqueue),
+        "add" => Ok(Action::EnqueueIds(args.split_whitespace().map(String::from).collect())),
+        "next" => Ok(Action::Next),
+        "prev" => Ok(Action::Previous),
+        "play" if args.is_empty() => Ok(Action::SetPaused(false)),
+        "play" => Ok(Action::PlayIds(args.split_whitespace().map(String::from).collect())),
+        "profile" if args.is_empty() => Ok(Action::ShowProfiles),
+        "profile" => Ok(Action::SwitchProfile(args.to_string())),
+        "radio" if args.is_empty() => Ok(Action::Radio),
+        "radio" => Ok(Action::StartRadio(args.to_string())),
+        "jump" => match args.parse::<usize>() {
+            Ok(position) if position > 0 => Ok(Action::PlayIndex(position - 1)),
+            _ => Err(format!("Usage: {}", usage)),
+        },
+        "pause" => Ok(Action::SetPaused(true)),
+        "toggle" => Ok(Action::PlayPause),
+        "clear" => Ok(Action::ClearQueue),
+        "help" => Ok(Action::Help),
+        "quit" => Ok(Action::Quit),
+        "settings" => Ok(Action::Settings),
+        "shuffle" => Ok(Action::Shuffle),
+        "mute" => Ok(Action::ToggleMute),
+        "repeat" => match args {
+            "" => Ok(Action::CycleRepeat),
+            "off" | "none" => Ok(Action::SetRepeat(Repeat::None)),
+            "queue" | "all" => Ok(Action::SetRepeat(Repeat::Queue)),
+            "track" | "one" => Ok(Action::SetRepeat(Repeat::Track)),
+            _ => Err(format!("Usage: {}", usage)),
+        },
+        "save" | "search" | "addto" | "sort" if args.is_empty() => Err(format!("Usage: {}", usage)),
+        "sort" => match args.split_whitespace().collect::<Vec<&str>>().as_slice() {
+            [key] => Ok(Action::SortTracks(key.to_string(), false)),
+            [key, "reverse"] => Ok(Action::SortTracks(key.to_string(), true)),
+            _ => Err(format!("Usage: {}", usage)),
+        },
+        "addto" => Ok(Action::AddToPlaylist(args.to_string())),
+        "save" => Ok(Action::SavePlaylist(args.to_string())),
+        "search" => Ok(Action::Search(args.to_string())),
+        "seek" => {
+            let (relative, sign, value) = split_sign(args);
+            let seconds = parse_time(value).ok_or(format!("Usage: {}", usage))?;
+            match relative {
+                true => Ok(Action::Seek(sign * seconds)),
+                false => Ok(Action::SeekTo(seconds)),
+            }
+        }
+        "vol" => {
+            let (relative, sign, value) = split_sign(args);
+            let volume: i64 = value.parse().map_err(|_| format!("Usage: {}", usage))?;
+            match relative {
+                true => Ok(Action::ChangeVolume(sign as i64 * volume)),
+                false => Ok(Action::SetVolume(volume)),
+            }
+        }
+        _ => Err(format!("Unknown command: {}", name)),
+    }
+}
+
+/// "+30" -> (true, 1.0, "30"), "90" -> (false, 1.0, "90")
+fn split_sign(args: &str) -> (bool, f64, &str) {
+    if let Some(rest) = args.strip_prefix('+') {
+        (true, 1.0, rest.trim())
+    } else if let Some(rest) = args.strip_prefix('-') {
+        (true, -1.0, rest.trim())
+    } else {
+        (false, 1.0, args)
+    }
+}
+
+/// Seconds from "90", "1:30" or "1:02:03"
+fn parse_time(s: &str) -> Option<f64> {
+    if s.is_empty() {
+        return None;
+    }
+    let mut seconds = 0.0;
+    for part in s.split(':') {
+        seconds = seconds * 60.0 + part.parse::<f64>().ok()?;
+    }
+    Some(seconds)
+}
+
+/// State of the `:` prompt: what has been typed, the history and tab completion
+#[derive(Default)]
+pub struct CommandLine {
+    pub active: bool,
+    pub input: String,
+    history: Vec<String>,
+    history_index: Option<usize>, // position while browsing with up/down
+    completions: Vec<String>,
+    completion_index: usize,
+}
+
+impl CommandLine {
+    pub fn new() -> Self {
+        CommandLine {
+            history: load_history(),
+            ..Default::default()
+        }
+    }
+
+    pub fn open(&mut self) {
+        self.active = true;
+        self.input.clear();
+        self.history_index = None;
+        self.completions.clear();
+    }
+
+    pub fn close(&mut self) {
+        self.active = false;
+        self.input.clear();
+        self.completions.clear();
+    }
+
+    pub fn insert(&mut self, c: char) {
+        self.input.push(c);
+        self.completions.clear();
+    }
+
+    pub fn backspace(&mut self) {
+        if self.input.pop().is_none() {
+            self.close();
+        }
+        self.completions.clear();
+    }
+
+    /// Completes the command name, repeated presses cycle through the candidates
+    pub fn complete(&mut self) {
+        if self.input.contains(char::is_whitespace) {
+            return;
+        }
+        if self.completions.is_empty() {
+            self.completions = COMMANDS
+                .iter()
+                .filter(|(name, _)| name.starts_with(self.input.as_str()))
+                .map(|(name, _)| name.to_string())
+                .collect();
+            self.completion_index = 0;
+        } else {
+            self.completion_index = (self.completion_index + 1) % self.completions.len();
+        }
+        if let Some(completion) = self.completions.get(self.completion_index) {
+            self.input = completion.clone();
+        }
+    }
+
+    /// Usage of the commands matching what has been typed so far
+    pub fn hint(&self) -> String {
+        let name = self.input.split_whitespace().next().unwrap_or("");
+        COMMANDS
+            .iter()
+            .filter(|(c, _)| c.starts_with(name) || name.starts_with(c))
+            .map(|(_, usage)| *usage)
+            .collect::<Vec<&str>>()
+            .join("  ")
+    }
+
+    pub fn history_prev(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = match self.history_index {
+            Some(0) => 0,
+            Some(i) => i - 1,
+            None => self.history.len() - 1,
+        };
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+        self.completions.clear();
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            Some(i) if i + 1 < self.history.len() => {
+                self.history_index = Some(i + 1);
+                self.input = self.history[i + 1].clone();
+            }
+            _ => {
+                self.history_index = None;
+                self.input.clear();
+            }
+        }
+        self.completions.clear();
+    }
+
+    /// Closes the prompt and returns the entered line, remembering it in the history
+    pub fn submit(&mut self) -> String {
+        let line = self.input.trim().to_string();
+        self.close();
+        if !line.is_empty() && self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > HISTORY_LIMIT {
+                self.history.remove(0);
+            }
+            save_history(&self.history);
+        }
+        line
+    }
+}
+
+fn history_file() -> Option<PathBuf> {
+    config::state_dir().map(|dir| dir.join("command_history"))
+}
+
+fn load_history() -> Vec<String> {
+    match history_file().and_then(|path| fs::read_to_string(path).ok()) {
+        Some(history) => history.lines().map(String::from).collect(),
+        None => vec![],
+    }
+}
+
+fn save_history(history: &[String]) {
+    if let Some(path) = history_file() {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let _ = fs::write(path, history.join("\n") + "\n");
+    }
+}
@@ -0,0 +1,560 @@
+//! Helpers for locating the configuration and state directories and reading config.yaml
+//!
+//! CONFIG FILE
+//!
+//! Every option is optional and has a default. Config::read turns the file into a typed
+//! Config, reporting unknown options as warnings and invalid ones as errors with the line
+//! they are on. An invalid option falls back to its default, so a typo never keeps the player
+//! from starting. `jellyfin-tui config check` prints the same report.
+//!
+//! server: http://localhost:8096
+//! username: me
+//! theme: light
+//! streaming:
+//!   max_bitrate: 320
+//! cache:
+//!   cover_art: true
+//! scrobbling:
+//!   enabled: true
+//!
+//! Any option can be set from the environment as JELLYFIN_TUI_<OPTION>, with the value written
+//! as in the file. Options of a section join with an underscore. These win over the file and
+//! are never written back to it:
+//!
+//! $ JELLYFIN_TUI_SERVER=http://10.0.0.2:8096 JELLYFIN_TUI_STREAMING_MAX_BITRATE=128 jellyfin-tui
+use crate::columns::{self, Column};
+use crate::home;
+use crate::keymap::KeyMap;
+use crate::panes::PaneLayout;
+use crate::profile::{self, Profile};
+use crate::sort::Sorting;
+use crate::theme::Theme;
+use crate::tui::LeftView;
+use dirs::config_dir;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::fmt;
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Set by --config
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// ~/.config/jellyfin-tui
+pub fn app_config_dir() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("jellyfin-tui"))
+}
+
+/// ~/.config/jellyfin-tui/config.yaml, unless another file was given with --config
+pub fn config_file() -> Option<PathBuf> {
+    match CONFIG_FILE.get() {
+        Some(path) => Some(path.clone()),
+        None => app_config_dir().map(|dir| dir.join("config.yaml")),
+    }
+}
+
+/// Reads and writes the config at this path from now on
+pub fn set_config_file(path: PathBuf) {
+    let _ = CONFIG_FILE.set(path);
+}
+
+/// Reads config.yaml as an untyped value. A missing file gives Value::Null
+pub fn load() -> Result<Value, String> {
+    let path = match config_file() {
+        Some(path) => path,
+        None => return Ok(Value::Null),
+    };
+    if !path.exists() {
+        return Ok(Value::Null);
+    }
+    let f = std::fs::File::open(&path).map_err(|e| e.to_string())?;
+    serde_yaml::from_reader(f).map_err(|e| e.to_string())
+}
+
+/// config.yaml with the JELLYFIN_TUI_* environment overrides applied, for reading options.
+/// Anything that writes the file back uses load, so the overrides never end up in it
+pub fn load_effective() -> Result<Value, String> {
+    let mut config = load()?;
+    apply_overrides(&mut config);
+    Ok(config)
+}
+
+/// Where runtime state such as the command history is kept.
+/// ~/.local/state/jellyfin-tui on Linux, the local data directory elsewhere
+pub fn state_dir() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("jellyfin-tui"))
+}
+
+/// Replaces one top level key of config.yaml, keeping everything else as it is
+pub fn save_section(key: &str, value: Value) -> Result<(), String> {
+    let mut config = match load()? {
+        Value::Mapping(mapping) => mapping,
+        _ => serde_yaml::Mapping::new(),
+    };
+    config.insert(Value::String(key.to_string()), value);
+    save(&Value::Mapping(config))
+}
+
+/// Replaces the whole of config.yaml
+pub fn save(config: &Value) -> Result<(), String> {
+    let path = config_file().ok_or("could not find the config directory")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    // check it reads back before replacing anything
+    let yaml = serde_yaml::to_string(config).map_err(|e| e.to_string())?;
+    serde_yaml::from_str::<Value>(&yaml).map_err(|e| e.to_string())?;
+    // write next to the config and rename, so a crash never leaves half a file behind
+    let tmp = path.with_extension("yaml.tmp");
+    write_private(&tmp, yaml.as_bytes()).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
+
+/// Writes a file only the user can read, for anything holding credentials
+pub fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // mode only applies to new files
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Takes away the access of others to config.yaml, which older versions created readable by
+/// everyone
+pub fn restrict_permissions() -> Result<(), String> {
+    let path = match config_file() {
+        Some(path) if path.exists() => path,
+        _ => return Ok(()),
+    };
+    let mode = std::fs::metadata(&path).map_err(|e| e.to_string())?.permissions().mode();
+    if mode & 0o077 != 0 {
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode & 0o700)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+const ENV_PREFIX: &str = "JELLYFIN_TUI_";
+
+/// The options config.yaml knows, each with the options of its section that are checked one
+/// by one. Sections without any are checked as a whole by their own parser
+const OPTIONS: [(&str, &[&str]); 15] = [
+    ("server", &[]),
+    ("username", &[]),
+    ("password", &[]),
+    ("libraries", &[]),
+    ("profiles", &[]),
+    ("default_profile", &[]),
+    ("start_view", &[]),
+    ("theme", &[]),
+    ("keys", &[]),
+    (
+        "layout",
+        &["mode", "artists_width", "queue_width", "lyrics_width", "show_artists", "show_queue", "show_lyrics"],
+    ),
+    ("columns", &[]),
+    ("sort", &["artists", "tracks", "album_artists"]),
+    ("streaming", &["max_bitrate"]),
+    ("cache", &["cover_art"]),
+    ("scrobbling", &["enabled"]),
+];
+
+/// Every option of config.yaml, each at its default when it isn't set or isn't valid
+pub struct Config {
+    /// the servers and users, see profile.rs
+    pub profiles: Vec<Profile>,
+    pub default_profile: Option<String>,
+    /// built-in theme to use when theme.yaml doesn't pick one, see theme.rs
+    pub theme: Option<String>,
+    pub keys: KeyMap,
+    pub layout: PaneLayout,
+    pub columns: Vec<Column>,
+    pub sort: Sorting,
+    pub start_view: LeftView,
+    pub streaming: Streaming,
+    pub cache: Cache,
+    pub scrobbling: Scrobbling,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Streaming {
+    /// kbit/s, anything above is transcoded down. The original file is streamed when not set
+    pub max_bitrate: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Cache {
+    /// download cover art to ~/.cache/jellyfin-tui and show it
+    pub cover_art: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Scrobbling {
+    /// tell the server what is played, for play counts and resuming elsewhere
+    pub enabled: bool,
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Cache { cover_art: true }
+    }
+}
+
+impl Default for Scrobbling {
+    fn default() -> Self {
+        Scrobbling { enabled: true }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            profiles: vec![],
+            default_profile: None,
+            theme: None,
+            keys: KeyMap::default(),
+            layout: PaneLayout::default(),
+            columns: vec![Column::Favorite],
+            sort: Sorting::default(),
+            start_view: LeftView::Home,
+            streaming: Streaming::default(),
+            cache: Cache::default(),
+            scrobbling: Scrobbling::default(),
+        }
+    }
+}
+
+/// Something wrong in config.yaml
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    /// None when it isn't on a line, like a value from the environment
+    pub line: Option<usize>,
+    pub message: String,
+    /// errors leave the option at its default, warnings are ignored
+    pub error: bool,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl Config {
+    /// Reads config.yaml, a missing file gives the defaults
+    pub fn read() -> (Config, Vec<Issue>) {
+        let text = match config_file() {
+            Some(path) if path.exists() => match std::fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) => {
+                    let issue = Issue { line: None, message: e.to_string(), error: true };
+                    return (Config::default(), vec![issue]);
+                }
+            },
+            _ => String::new(),
+        };
+        Config::parse(&text)
+    }
+
+    /// Builds the Config from the text of a config file and the environment
+    pub fn parse(text: &str) -> (Config, Vec<Issue>) {
+        let mut issues = vec![];
+        let mut yaml = match serde_yaml::from_str::<Value>(text) {
+            Ok(Value::Mapping(mapping)) => Value::Mapping(mapping),
+            Ok(Value::Null) => Value::Null,
+            Ok(_) => {
+                let message = String::from("the config must be a mapping of option: value");
+                issues.push(Issue { line: Some(1), message, error: true });
+                Value::Null
+            }
+            // an empty file isn't an error
+            Err(_) if text.trim().is_empty() => Value::Null,
+            Err(e) => {
+                issues.push(Issue { line: None, message: e.to_string(), error: true });
+                Value::Null
+            }
+        };
+        issues.extend(unknown_options(&yaml, text));
+        let overridden = apply_overrides(&mut yaml);
+        Config::build(yaml, Checker { text, overridden: &overridden, issues })
+    }
+
+    /// Checks a config about to be saved, without the environment. The first error if any
+    pub fn validate(config: &Value) -> Result<(), String> {
+        let (_, issues) = Config::build(config.clone(), Checker { text: "", overridden: &[], issues: vec![] });
+        match issues.into_iter().find(|issue| issue.error) {
+            Some(issue) => Err(issue.message),
+            None => Ok(()),
+        }
+    }
+
+    fn build(yaml: Value, mut checker: Checker) -> (Config, Vec<Issue>) {
+        let profiles = checker.check("profiles", profile::list(&yaml), vec![]);
+        let default_profile = match typed::<Option<String>>(&yaml, "default_profile") {
+            Ok(Some(name)) if !profiles.iter().any(|profile| profile.name == name) => {
+                Err(format!("there is no profile called '{}'", name))
+            }
+            result => result,
+        };
+        let default_profile = checker.check("default_profile", default_profile, None);
+        let theme = match typed::<Option<String>>(&yaml, "theme") {
+            Ok(Some(name)) if Theme::builtin(&name).is_none() => Err(format!("unknown theme '{}'", name)),
+            result => result,
+        };
+        let config = Config {
+            theme: checker.check("theme", theme, None),
+            keys: checker.check("keys", KeyMap::from_config(&yaml), KeyMap::default()),
+            layout: checker.check("layout", PaneLayout::from_config(&yaml), PaneLayout::default()),
+            columns: checker.check("columns", columns::from_config(&yaml), vec![Column::Favorite]),
+            sort: checker.check("sort", Sorting::from_config(&yaml), Sorting::default()),
+            start_view: checker.check("start_view", home::start_view(&yaml), LeftView::Home),
+            streaming: checker.check("streaming", typed(&yaml, "streaming"), Streaming::default()),
+            cache: checker.check("cache", typed(&yaml, "cache"), Cache::default()),
+            scrobbling: checker.check("scrobbling", typed(&yaml, "scrobbling"), Scrobbling::default()),
+            profiles,
+            default_profile,
+        };
+        (config, checker.issues)
+    }
+
+    /// The profile to use: the one asked for, else `default_profile`, else the first one
+    pub fn profile(&self, wanted: Option<&str>) -> Result<Profile, String> {
+        profile::pick(self.profiles.clone(), wanted.or(self.default_profile.as_deref()))
+    }
+}
+
+/// Collects the errors of the options parsed one after another
+struct Checker<'a> {
+    text: &'a str,
+    /// top level options set from the environment, with the variable that did it
+    overridden: &'a [(String, String)],
+    issues: Vec<Issue>,
+}
+
+impl Checker<'_> {
+    fn check<T>(&mut self, key: &str, result: Result<T, String>, default: T) -> T {
+        let e = match result {
+            Ok(value) => return value,
+            Err(e) => e,
+        };
+        let issue = match self.overridden.iter().find(|(option, _)| option == key) {
+            Some((_, variable)) => Issue { line: None, message: format!("{}: {}", variable, e), error: true },
+            None => Issue { line: line_of(self.text, &[key]), message: format!("{}: {}", key, e), error: true },
+        };
+        self.issues.push(issue);
+        default
+    }
+}
+
+/// A section deserialized as is, its default when it isn't set
+fn typed<T: DeserializeOwned + Default>(config: &Value, key: &str) -> Result<T, String> {
+    match config.get(key) {
+        Some(Value::Null) | None => Ok(T::default()),
+        Some(value) => serde_yaml::from_value(value.clone()).map_err(|e| e.to_string()),
+    }
+}
+
+/// Warns about options config.yaml doesn't know, mostly typos that would go unnoticed
+fn unknown_options(config: &Value, text: &str) -> Vec<Issue> {
+    let mapping = match config {
+        Value::Mapping(mapping) => mapping,
+        _ => return vec![],
+    };
+    let mut issues = vec![];
+    for (key, value) in mapping {
+        let key = match key.as_str() {
+            Some(key) => key,
+            None => {
+                let message = format!("option names must be strings, not {:?}", key);
+                issues.push(Issue { line: None, message, error: false });
+                continue;
+            }
+        };
+        let children = match OPTIONS.iter().find(|(option, _)| *option == key) {
+            Some((_, children)) => *children,
+            None => {
+                let message = format!("unknown option '{}'", key);
+                issues.push(Issue { line: line_of(text, &[key]), message, error: false });
+                continue;
+            }
+        };
+        let section = match value {
+            Value::Mapping(section) if !children.is_empty() => section,
+            _ => continue,
+        };
+        for child in section.iter().filter_map(|(child, _)| child.as_str()) {
+            if !children.contains(&child) {
+                let message = format!("unknown option '{}' in {}, expected one of {}", child, key, children.join(", "));
+                let line = line_of(text, &[key, child]).or(line_of(text, &[key]));
+                issues.push(Issue { line, message, error: false });
+            }
+        }
+    }
+    issues
+}
+
+/// The line an option is written on, found by its indentation. Options written inline, as in
+/// `cache: { cover_art: false }`, aren't found
+fn line_of(text: &str, path: &[&str]) -> Option<usize> {
+    let mut depth = 0;
+    let mut parent_indent = 0;
+    for (number, line) in text.lines().enumerate() {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let indent = line.len() - trimmed.len();
+        if depth > 0 && indent <= parent_indent {
+            return None; // left the section
+        }
+        let key = match trimmed.split_once(':') {
+            Some((key, _)) => key.trim().trim_matches(['"', '\'']),
+            None => continue,
+        };
+        if key != path[depth] || (depth == 0 && indent != 0) {
+            continue;
+        }
+        if depth + 1 == path.len() {
+            return Some(number + 1);
+        }
+        parent_indent = indent;
+        depth += 1;
+    }
+    None
+}
+
+/// Sets the options given as JELLYFIN_TUI_<OPTION> environment variables, each parsed as YAML.
+/// Returns which top level options they set and by which variable
+pub fn apply_overrides(config: &mut Value) -> Vec<(String, String)> {
+    let mut overridden = vec![];
+    for (key, children) in OPTIONS {
+        let paths = std::iter::once(vec![key]).chain(children.iter().map(|child| vec![key, *child]));
+        for path in paths {
+            let variable = format!("{}{}", ENV_PREFIX, path.join("_").to_uppercase());
+            let text = match std::env::var(&variable) {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+            set(config, &path, env_value(key, &text));
+            overridden.push((key.to_string(), variable));
+        }
+    }
+    overridden
+}
+
+/// A YAML value, or the text itself if it isn't one. Lists can also be given comma separated,
+/// as in JELLYFIN_TUI_LIBRARIES=Music,Audiobooks
+fn env_value(key: &str, text: &str) -> Value {
+    match serde_yaml::from_str::<Value>(text) {
+        Ok(Value::String(text)) if ["libraries", "columns"].contains(&key) => Value::Sequence(
+            text.split(',').map(|item| Value::String(item.trim().to_string())).collect(),
+        ),
+        Ok(value) => value,
+        Err(_) => Value::String(text.to_string()),
+    }
+}
+
+/// Puts a value at a path of mappings, creating the ones missing on the way
+fn set(config: &mut Value, path: &[&str], value: Value) {
+    if !matches!(config, Value::Mapping(_)) {
+        *config = Value::Mapping(serde_yaml::Mapping::new());
+    }
+    let mapping = match config {
+        Value::Mapping(mapping) => mapping,
+        _ => return,
+    };
+    let key = Value::String(path[0].to_string());
+    match path.len() {
+        1 => {
+            mapping.insert(key, value);
+        }
+        _ => {
+            let child = mapping.entry(key).or_insert(Value::Null);
+            set(child, &path[1..], value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Config::parse reads the environment, tests setting variables hold this meanwhile
+    static ENVIRONMENT: Mutex<()> = Mutex::new(());
+
+    fn parse(text: &str) -> (Config, Vec<Issue>) {
+        let _environment = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+        Config::parse(text)
+    }
+
+    #[test]
+    fn unknown_options_are_warnings_on_their_line() {
+        let text = "server: http://localhost:8096\nusername: me\n\ncolour: blue\nlayout:\n  show_queue: true\n  queue_widht: 30\n";
+        let (_, issues) = parse(text);
+        assert_eq!(issues, vec![
+            Issue { line: Some(4), message: String::from("unknown option 'colour'"), error: false },
+            Issue {
+                line: Some(7),
+                message: String::from(
+                    "unknown option 'queue_widht' in layout, expected one of mode, artists_width, queue_width, \
+                     lyrics_width, show_artists, show_queue, show_lyrics",
+                ),
+                error: false,
+            },
+        ]);
+    }
+
+    #[test]
+    fn invalid_values_fall_back_to_the_default() {
+        let text = "scrobbling:\n  enabled: false\ncache:\n  cover_art: maybe\n";
+        let (config, issues) = parse(text);
+        assert_eq!(config.cache, Cache::default());
+        assert!(!config.scrobbling.enabled);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(3));
+        assert!(issues[0].message.starts_with("cache: "));
+        assert!(issues[0].error);
+    }
+
+    #[test]
+    fn inline_options_have_no_line_of_their_own() {
+        let text = "cache: { cover_atr: false }\n";
+        assert_eq!(line_of(text, &["cache"]), Some(1));
+        assert_eq!(line_of(text, &["cache", "cover_atr"]), None);
+        // the warning points at the section instead
+        let (_, issues) = parse(text);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(1));
+    }
+
+    #[test]
+    fn the_environment_overrides_the_file() {
+        let text = "server: http://localhost:8096\nusername: me\nlibraries: [Films]\nstreaming:\n  max_bitrate: 320\n";
+        let _environment = ENVIRONMENT.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::set_var("JELLYFIN_TUI_STREAMING_MAX_BITRATE", "128");
+        std::env::set_var("JELLYFIN_TUI_LIBRARIES", "Music, Audiobooks");
+        let (config, issues) = Config::parse(text);
+        std::env::remove_var("JELLYFIN_TUI_STREAMING_MAX_BITRATE");
+        std::env::remove_var("JELLYFIN_TUI_LIBRARIES");
+
+        assert_eq!(issues, vec![]);
+        assert_eq!(config.streaming.max_bitrate, Some(128));
+        assert_eq!(config.profiles[0].libraries, vec![String::from("Music"), String::from("Audiobooks")]);
+    }
+}
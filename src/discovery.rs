@@ -0,0 +1,99 @@
+//! SERVER DISCOVERY
+//!
+//! Jellyfin servers answer a UDP broadcast of "Who is JellyfinServer?" on port 7359 with
+//! a line of JSON:
+//!
+//! {"Address":"http://192.168.1.10:8096","Id":"2f6c...","Name":"living room","EndpointAddress":null}
+//!
+//! The first-run prompt lists the servers that answer, so the address doesn't have to be
+//! typed. Anything that answers like this on another address works too, e.g. for testing:
+//!
+//! $ JELLYFIN_TUI_DISCOVERY=127.0.0.1:7359 jellyfin-tui login
+use serde::Deserialize;
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+pub const PORT: u16 = 7359;
+const MESSAGE: &[u8] = b"Who is JellyfinServer?";
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Server {
+    #[serde(rename = "Name", default)]
+    pub name: String,
+    #[serde(rename = "Address")]
+    pub address: String,
+    #[serde(rename = "Id", default)]
+    pub id: String,
+}
+
+/// The broadcast address, or the one set in $JELLYFIN_TUI_DISCOVERY
+pub fn target() -> SocketAddr {
+    std::env::var("JELLYFIN_TUI_DISCOVERY")
+        .ok()
+        .and_then(|address| address.parse().ok())
+        .unwrap_or(SocketAddr::from(([255, 255, 255, 255], PORT)))
+}
+
+/// Asks who is there and collects the answers that arrive within `wait`. Servers answering
+/// more than once are listed once
+pub fn discover(target: SocketAddr, wait: Duration) -> Result<Vec<Server>, String> {
+    let bind: SocketAddr = match target {
+        SocketAddr::V4(_) => SocketAddr::from(([0, 0, 0, 0], 0)),
+        SocketAddr::V6(_) => SocketAddr::from(([0u16; 8], 0)),
+    };
+    let socket = UdpSocket::bind(bind).map_err(|e| e.to_string())?;
+    socket.set_broadcast(true).map_err(|e| e.to_string())?;
+    socket.send_to(MESSAGE, target).map_err(|e| e.to_string())?;
+
+    let deadline = Instant::now() + wait;
+    let mut servers: Vec<Server> = vec![];
+    let mut buffer = [0u8; 2048];
+    loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            break;
+        }
+        socket.set_read_timeout(Some(left)).map_err(|e| e.to_string())?;
+        let length = match socket.recv_from(&mut buffer) {
+            Ok((length, _)) => length,
+            Err(_) => break, // timed out
+        };
+        // other things may share the port, skip whatever isn't an answer
+        let server: Server = match serde_json::from_slice(&buffer[..length]) {
+            Ok(server) => server,
+            Err(_) => continue,
+        };
+        if !servers.iter().any(|known| known.address == server.address) {
+            servers.push(server);
+        }
+    }
+    Ok(servers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn answers_are_parsed_and_deduplicated() {
+        let responder = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let target = responder.local_addr().unwrap();
+        let answering = std::thread::spawn(move || {
+            let mut buffer = [0u8; 64];
+            let (length, from) = responder.recv_from(&mut buffer).unwrap();
+            let answer = br#"{"Address":"http://127.0.0.1:8096","Id":"2f6c","Name":"living room","EndpointAddress":null}"#;
+            responder.send_to(answer, from).unwrap();
+            responder.send_to(b"not json", from).unwrap();
+            responder.send_to(answer, from).unwrap();
+            buffer[..length].to_vec()
+        });
+
+        let servers = discover(target, Duration::from_millis(500)).unwrap();
+        assert_eq!(answering.join().unwrap(), MESSAGE);
+        assert_eq!(servers, vec![Server {
+            name: String::from("living room"),
+            address: String::from("http://127.0.0.1:8096"),
+            id: String::from("2f6c"),
+        }]);
+    }
+}
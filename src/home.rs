@@ -0,0 +1,59 @@
+//! HOME
+//!
+//! The view jellyfin-tui opens with: short lists of recently added albums, recently and most
+//! played songs and a few random albums, to pick something without scrolling through every
+//! artist. Enter plays an entry, `a` adds it to the queue.
+//!
+//! Which view the left pane starts with is set with `start_view` in config.yaml:
+//!
+//! start_view: home # artists | playlists | favorites | genres
+use crate::client::{Client, Item};
+use crate::tui::LeftView;
+use serde_yaml::Value;
+
+/// Entries per section
+const SECTION_LENGTH: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Section {
+    RecentlyAdded,
+    RecentlyPlayed,
+    MostPlayed,
+    RandomAlbums,
+}
+
+impl Section {
+    /// In the order they are shown
+    pub const ALL: [Section; 4] = [
+        Section::RecentlyAdded,
+        Section::RecentlyPlayed,
+        Section::MostPlayed,
+        Section::RandomAlbums,
+    ];
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Section::RecentlyAdded => "Recently added",
+            Section::RecentlyPlayed => "Recently played",
+            Section::MostPlayed => "Most played",
+            Section::RandomAlbums => "Random albums",
+        }
+    }
+
+    pub async fn fetch(self, client: &Client) -> Result<Vec<Item>, reqwest::Error> {
+        match self {
+            Section::RecentlyAdded => client.latest(SECTION_LENGTH).await,
+            Section::RecentlyPlayed => client.top_items("Audio", "DatePlayed", Some("IsPlayed"), SECTION_LENGTH).await,
+            Section::MostPlayed => client.top_items("Audio", "PlayCount", Some("IsPlayed"), SECTION_LENGTH).await,
+            Section::RandomAlbums => client.top_items("MusicAlbum", "Random", None, SECTION_LENGTH).await,
+        }
+    }
+}
+
+/// The left pane view to start with, home unless the config says otherwise
+pub fn start_view(config: &Value) -> Result<LeftView, String> {
+    match config.get("start_view") {
+        Some(Value::Null) | None => Ok(LeftView::Home),
+        Some(view) => serde_yaml::from_value(view.clone()).map_err(|e| e.to_string()),
+    }
+}
@@ -0,0 +1,554 @@
+//! KEYBINDINGS
+//!
+//! Keys are never matched directly by the TUI. A key (or a sequence of keys, like gg) resolves to
+//! an Action through the KeyMap and the App only knows how to perform actions.
+//!
+//! The defaults are vim-like. They can be changed in the `keys` section of config.yaml, binding an
+//! action replaces all of its default keys:
+//!
+//! keys:
+//!   play_pause: ["<Space>", "c"]
+//!   seek_forward: l
+//!   top: "<Home>"
+use crate::tui::Repeat;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde_yaml::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Quit,
+    PlayPause,
+    SeekForward,
+    SeekBackward,
+    Next,
+    Previous,
+    NextSection,
+    PreviousSection,
+    Down,
+    Up,
+    Top,
+    Bottom,
+    Select,
+    Enqueue,
+    ClearQueue,
+    Shuffle,
+    CycleRepeat,
+    ToggleMute,
+    Help,
+    CommandMode,
+    CycleLayout,
+    ToggleArtists,
+    ToggleQueue,
+    ToggleLyrics,
+    GrowPane,
+    ShrinkPane,
+    ShowHome,
+    ShowArtists,
+    ShowSessions,
+    ShowPlaylists,
+    ShowFavorites,
+    ShowGenres,
+    ShowLibraries,
+    ShowProfiles,
+    Settings,
+    ToggleFavorite,
+    PlayGenre,
+    Radio,
+    SortMenu,
+    Delete,
+    MoveUp,
+    MoveDown,
+
+    // the ones below carry arguments and can only be reached from the command line or remotely
+    /// relative seek in seconds
+    Seek(f64),
+    /// absolute seek in seconds
+    SeekTo(f64),
+    SetVolume(i64),
+    ChangeVolume(i64),
+    SavePlaylist(String),
+    Search(String),
+    EnqueueIds(Vec<String>),
+    /// replace the queue with these tracks
+    PlayIds(Vec<String>),
+    /// jump to a position in the queue
+    PlayIndex(usize),
+    SetPaused(bool),
+    SetShuffle(bool),
+    SetRepeat(Repeat),
+    SetMuted(bool),
+    /// add the selected track to the playlist with this name
+    AddToPlaylist(String),
+    /// order the track list by a column, see columns.rs. The flag reverses the order
+    SortTracks(String, bool),
+    /// replace the queue with an Instant Mix of this item, refilled as it plays
+    StartRadio(String),
+    /// log in to another profile of config.yaml, see profile.rs
+    SwitchProfile(String),
+}
+
+impl Action {
+    /// Every action, in the order they are listed in the help overlay
+    pub const ALL: [Action; 42] = [
+        Action::PlayPause,
+        Action::SeekForward,
+        Action::SeekBackward,
+        Action::Next,
+        Action::Previous,
+        Action::NextSection,
+        Action::PreviousSection,
+        Action::Down,
+        Action::Up,
+        Action::Top,
+        Action::Bottom,
+        Action::Select,
+        Action::Enqueue,
+        Action::ClearQueue,
+        Action::Shuffle,
+        Action::CycleRepeat,
+        Action::ToggleMute,
+        Action::CycleLayout,
+        Action::ToggleArtists,
+        Action::ToggleQueue,
+        Action::ToggleLyrics,
+        Action::GrowPane,
+        Action::ShrinkPane,
+        Action::ShowHome,
+        Action::ShowArtists,
+        Action::ShowSessions,
+        Action::ShowPlaylists,
+        Action::ShowFavorites,
+        Action::ShowGenres,
+        Action::ShowLibraries,
+        Action::ShowProfiles,
+        Action::Settings,
+        Action::ToggleFavorite,
+        Action::PlayGenre,
+        Action::Radio,
+        Action::SortMenu,
+        Action::Delete,
+        Action::MoveUp,
+        Action::MoveDown,
+        Action::CommandMode,
+        Action::Help,
+        Action::Quit,
+    ];
+
+    /// Name used in the config file
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::PlayPause => "play_pause",
+            Action::SeekForward => "seek_forward",
+            Action::SeekBackward => "seek_backward",
+            Action::Next => "next",
+            Action::Previous => "previous",
+            Action::NextSection => "next_section",
+            Action::PreviousSection => "previous_section",
+            Action::Down => "down",
+            Action::Up => "up",
+            Action::Top => "top",
+            Action::Bottom => "bottom",
+            Action::Select => "select",
+            Action::Enqueue => "enqueue",
+            Action::ClearQueue => "clear_queue",
+            Action::Shuffle => "shuffle",
+            Action::CycleRepeat => "repeat",
+            Action::ToggleMute => "mute",
+            Action::Help => "help",
+            Action::CommandMode => "command_mode",
+            Action::CycleLayout => "cycle_layout",
+            Action::ToggleArtists => "toggle_artists",
+            Action::ToggleQueue => "toggle_queue",
+            Action::ToggleLyrics => "toggle_lyrics",
+            Action::GrowPane => "grow_pane",
+            Action::ShrinkPane => "shrink_pane",
+            Action::ShowHome => "show_home",
+            Action::ShowArtists => "show_artists",
+            Action::ShowSessions => "show_sessions",
+            Action::ShowPlaylists => "show_playlists",
+            Action::ShowFavorites => "show_favorites",
+            Action::ShowGenres => "show_genres",
+            Action::ShowLibraries => "show_libraries",
+            Action::ShowProfiles => "show_profiles",
+            Action::Settings => "settings",
+            Action::PlayGenre => "play_genre",
+            Action::Radio => "radio",
+            Action::ToggleFavorite => "toggle_favorite",
+            Action::SortMenu => "sort_menu",
+            Action::Delete => "delete",
+            Action::MoveUp => "move_up",
+            Action::MoveDown => "move_down",
+            Action::Seek(_) => "seek",
+            Action::SeekTo(_) => "seek_to",
+            Action::SetVolume(_) => "set_volume",
+            Action::ChangeVolume(_) => "change_volume",
+            Action::SavePlaylist(_) => "save_playlist",
+            Action::Search(_) => "search",
+            Action::EnqueueIds(_) => "enqueue_ids",
+            Action::PlayIds(_) => "play_ids",
+            Action::PlayIndex(_) => "play_index",
+            Action::SetPaused(_) => "set_paused",
+            Action::SetShuffle(_) => "set_shuffle",
+            Action::SetRepeat(_) => "set_repeat",
+            Action::SetMuted(_) => "set_muted",
+            Action::AddToPlaylist(_) => "add_to_playlist",
+            Action::SortTracks(..) => "sort_tracks",
+            Action::StartRadio(_) => "start_radio",
+            Action::SwitchProfile(_) => "switch_profile",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.iter().find(|a| a.name() == name).cloned()
+    }
+
+    /// Human readable description for the help overlay and the hints
+    pub fn description(&self) -> &'static str {
+        match self {
+            Action::Quit => "Quit",
+            Action::PlayPause => "Play/Pause",
+            Action::SeekForward => "Seek+5s",
+            Action::SeekBackward => "Seek-5s",
+            Action::Next => "Next track",
+            Action::Previous => "Previous track",
+            Action::NextSection => "Next Section",
+            Action::PreviousSection => "Focus Queue",
+            Action::Down => "Navigate down",
+            Action::Up => "Navigate up",
+            Action::Top => "Go to top",
+            Action::Bottom => "Go to bottom",
+            Action::Select => "Open artist / play track",
+            Action::Enqueue => "Add to queue",
+            Action::ClearQueue => "Clear the queue",
+            Action::Shuffle => "Toggle shuffle",
+            Action::CycleRepeat => "Cycle repeat (off/queue/track)",
+            Action::ToggleMute => "Mute/Unmute",
+            Action::Help => "Show key bindings",
+            Action::CommandMode => "Enter a command",
+            Action::CycleLayout => "Cycle layout (auto/stacked/columns/wide)",
+            Action::ToggleArtists => "Show/hide artists",
+            Action::ToggleQueue => "Show/hide queue",
+            Action::ToggleLyrics => "Show/hide lyrics",
+            Action::GrowPane => "Widen focused pane",
+            Action::ShrinkPane => "Narrow focused pane",
+            Action::ShowHome => "Show home",
+            Action::ShowArtists => "List artists",
+            Action::ShowSessions => "List sessions to control",
+            Action::ShowPlaylists => "List playlists",
+            Action::ShowFavorites => "List favorites",
+            Action::ShowGenres => "List genres",
+            Action::ShowLibraries => "Choose libraries",
+            Action::ShowProfiles => "List server profiles",
+            Action::Settings => "Edit settings",
+            Action::PlayGenre => "Play genre shuffled",
+            Action::Radio => "Start a radio from the selection",
+            Action::ToggleFavorite => "Favorite / unfavorite",
+            Action::SortMenu => "Sort the focused list",
+            Action::Delete => "Remove from playlist / delete playlist",
+            Action::MoveUp => "Move playlist entry up",
+            Action::MoveDown => "Move playlist entry down",
+            Action::Seek(_) | Action::SeekTo(_) => "Seek",
+            Action::SetVolume(_) | Action::ChangeVolume(_) => "Change volume",
+            Action::SavePlaylist(_) => "Save queue as playlist",
+            Action::Search(_) => "Search tracks",
+            Action::EnqueueIds(_) => "Add tracks to queue",
+            Action::PlayIds(_) => "Play tracks",
+            Action::PlayIndex(_) => "Play from the queue",
+            Action::SetPaused(_) => "Play/Pause",
+            Action::SetShuffle(_) => "Toggle shuffle",
+            Action::SetRepeat(_) => "Cycle repeat (off/queue/track)",
+            Action::SetMuted(_) => "Mute/Unmute",
+            Action::AddToPlaylist(_) => "Add track to a playlist",
+            Action::SortTracks(..) => "Sort tracks",
+            Action::StartRadio(_) => "Start a radio",
+            Action::SwitchProfile(_) => "Switch server profile",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::PlayPause => &["<Space>"],
+            Action::SeekForward => &["<Right>", "s"],
+            Action::SeekBackward => &["<Left>", "r"],
+            Action::Next => &["n"],
+            Action::Previous => &["p"],
+            Action::NextSection => &["<Tab>"],
+            Action::PreviousSection => &["<S-Tab>"],
+            Action::Down => &["<Down>", "j"],
+            Action::Up => &["<Up>", "k"],
+            Action::Top => &["gg", "<Home>"],
+            Action::Bottom => &["G", "<End>"],
+            Action::Select => &["<Enter>"],
+            Action::Enqueue => &["e", "a"],
+            Action::ClearQueue => &[],
+            Action::Shuffle => &[],
+            Action::CycleRepeat => &["R"],
+            Action::ToggleMute => &["m"],
+            Action::Help => &["?"],
+            Action::CommandMode => &[":"],
+            Action::CycleLayout => &["zl"],
+            Action::ToggleArtists => &["za"],
+            Action::ToggleQueue => &["zq"],
+            Action::ToggleLyrics => &["zy"],
+            Action::GrowPane => &[">"],
+            Action::ShrinkPane => &["<lt>"],
+            Action::ShowHome => &["gh"],
+            Action::ShowArtists => &["ga"],
+            Action::ShowSessions => &["gs"],
+            Action::ShowPlaylists => &["gp"],
+            Action::ShowFavorites => &["gf"],
+            Action::ShowGenres => &["gr"],
+            Action::ShowLibraries => &["gl"],
+            Action::ShowProfiles => &["gP"],
+            Action::Settings => &["gS"],
+            Action::PlayGenre => &["S"],
+            Action::Radio => &["i"],
+            Action::ToggleFavorite => &["f"],
+            Action::SortMenu => &["o"],
+            Action::Delete => &["d"],
+            Action::MoveUp => &["K"],
+            Action::MoveDown => &["J"],
+            _ => &[],
+        }
+    }
+}
+
+/// A single key press. Shift is folded into the character for printable keys so that
+/// `G` matches no matter how the terminal reports it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Key {
+    fn new(code: KeyCode, modifiers: KeyModifiers) -> Self {
+        let modifiers = match code {
+            KeyCode::Char(_) | KeyCode::BackTab => modifiers - KeyModifiers::SHIFT,
+            _ => modifiers,
+        };
+        Key { code, modifiers }
+    }
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        Key::new(event.code, event.modifiers)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.code {
+            KeyCode::Char(' ') => String::from("Space"),
+            KeyCode::Char(c) if self.modifiers.is_empty() => return write!(f, "{}", c),
+            KeyCode::Char(c) => c.to_string(),
+            KeyCode::Enter => String::from("Enter"),
+            KeyCode::Esc => String::from("Esc"),
+            KeyCode::Tab => String::from("Tab"),
+            KeyCode::BackTab => String::from("S-Tab"),
+            KeyCode::Backspace => String::from("BS"),
+            KeyCode::Left => String::from("Left"),
+            KeyCode::Right => String::from("Right"),
+            KeyCode::Up => String::from("Up"),
+            KeyCode::Down => String::from("Down"),
+            KeyCode::Home => String::from("Home"),
+            KeyCode::End => String::from("End"),
+            KeyCode::PageUp => String::from("PageUp"),
+            KeyCode::PageDown => String::from("PageDown"),
+            KeyCode::Delete => String::from("Del"),
+            KeyCode::Insert => String::from("Insert"),
+            KeyCode::F(n) => format!("F{}", n),
+            _ => String::from("?"),
+        };
+        let mut prefix = String::new();
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            prefix.push_str("C-");
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            prefix.push_str("A-");
+        }
+        write!(f, "<{}{}>", prefix, name)
+    }
+}
+
+/// Parses a key sequence such as `q`, `gg`, `<Space>`, `<C-d>` or `<S-Tab>`
+pub fn parse_keys(spec: &str) -> Result<Vec<Key>, String> {
+    let mut keys = vec![];
+    let mut chars = spec.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            keys.push(Key::new(KeyCode::Char(c), KeyModifiers::NONE));
+            continue;
+        }
+        let mut name = String::new();
+        let mut closed = false;
+        for c in chars.by_ref() {
+            if c == '>' {
+                closed = true;
+                break;
+            }
+            name.push(c);
+        }
+        if !closed || name.is_empty() {
+            return Err(format!("unterminated key '<{}' in '{}'", name, spec));
+        }
+        keys.push(parse_named_key(&name).ok_or(format!("unknown key '<{}>'", name))?);
+    }
+    if keys.is_empty() {
+        return Err(String::from("empty key binding"));
+    }
+    Ok(keys)
+}
+
+fn parse_named_key(name: &str) -> Option<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    loop {
+        let lower = rest.to_lowercase();
+        if lower.starts_with("c-") && rest.len() > 2 {
+            modifiers |= KeyModifiers::CONTROL;
+        } else if (lower.starts_with("a-") || lower.starts_with("m-")) && rest.len() > 2 {
+            modifiers |= KeyModifiers::ALT;
+        } else if lower.starts_with("s-") && rest.len() > 2 {
+            modifiers |= KeyModifiers::SHIFT;
+        } else {
+            break;
+        }
+        rest = &rest[2..];
+    }
+
+    let code = match rest.to_lowercase().as_str() {
+        "space" => KeyCode::Char(' '),
+        "enter" | "cr" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "tab" if modifiers.contains(KeyModifiers::SHIFT) => KeyCode::BackTab,
+        "tab" => KeyCode::Tab,
+        "backtab" => KeyCode::BackTab,
+        "bs" | "backspace" => KeyCode::Backspace,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "del" | "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "lt" => KeyCode::Char('<'),
+        "gt" => KeyCode::Char('>'),
+        f if f.starts_with('f') && f.len() > 1 => KeyCode::F(f[1..].parse().ok()?),
+        _ => {
+            let mut chars = rest.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => return None,
+            }
+        }
+    };
+    Some(Key::new(code, modifiers))
+}
+
+fn format_keys(keys: &[Key]) -> String {
+    keys.iter().map(|k| k.to_string()).collect::<String>()
+}
+
+/// Outcome of feeding the pending keys to the keymap
+pub enum Resolution {
+    Action(Action),
+    /// the keys are the start of a longer binding, wait for more
+    Pending,
+    Unbound,
+}
+
+pub struct KeyMap {
+    bindings: Vec<(Vec<Key>, Action)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let mut bindings = vec![];
+        for action in Action::ALL {
+            for spec in action.default_keys() {
+                bindings.push((parse_keys(spec).unwrap(), action.clone()));
+            }
+        }
+        KeyMap { bindings }
+    }
+}
+
+impl KeyMap {
+    /// Defaults with the `keys` section of config.yaml applied on top
+    pub fn from_config(config: &Value) -> Result<Self, String> {
+        let mut keymap = KeyMap::default();
+        let keys = match config.get("keys") {
+            Some(Value::Mapping(keys)) => keys,
+            Some(Value::Null) | None => return Ok(keymap),
+            Some(_) => return Err(String::from("'keys' must be a mapping of action: key")),
+        };
+
+        for (name, specs) in keys {
+            let name = name.as_str().ok_or("action names must be strings")?;
+            let action =
+                Action::from_name(name).ok_or(format!("unknown action '{}'", name))?;
+            let specs = match specs {
+                Value::String(spec) => vec![spec.as_str()],
+                Value::Sequence(specs) => specs
+                    .iter()
+                    .map(|s| s.as_str().ok_or(format!("keys for '{}' must be strings", name)))
+                    .collect::<Result<Vec<&str>, String>>()?,
+                _ => return Err(format!("keys for '{}' must be a string or a list", name)),
+            };
+
+            keymap.bindings.retain(|(_, a)| *a != action);
+            for spec in specs {
+                keymap.bindings.push((parse_keys(spec)?, action.clone()));
+            }
+        }
+        Ok(keymap)
+    }
+
+    pub fn resolve(&self, pending: &[Key]) -> Resolution {
+        let mut prefix = false;
+        for (keys, action) in &self.bindings {
+            if keys.as_slice() == pending {
+                return Resolution::Action(action.clone());
+            }
+            if keys.starts_with(pending) {
+                prefix = true;
+            }
+        }
+        match prefix {
+            true => Resolution::Pending,
+            false => Resolution::Unbound,
+        }
+    }
+
+    /// All keys bound to an action, formatted like in the config file
+    pub fn keys_for(&self, action: &Action) -> Vec<String> {
+        self.bindings
+            .iter()
+            .filter(|(_, a)| a == action)
+            .map(|(keys, _)| format_keys(keys))
+            .collect()
+    }
+
+    /// Short label of the first key bound to an action, for the hints line
+    pub fn hint(&self, action: &Action) -> String {
+        match self.bindings.iter().find(|(_, a)| a == action) {
+            Some((keys, _)) => {
+                let keys = format_keys(keys);
+                if keys.starts_with('<') {
+                    keys
+                } else {
+                    format!("<{}>", keys)
+                }
+            }
+            None => String::from("<unbound>"),
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! LOG FILE
+//!
+//! `--log-file <path>` appends the messages shown in the status line and the errors printed
+//! while starting to a file, since the TUI covers them up:
+//!
+//! 1760000000 [OK] Authenticated!
+//! 1760000042 Failed to fetch tracks: error sending request
+//!
+//! Lines start with the time in seconds since the epoch. Without the flag nothing is written.
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static FILE: OnceLock<Mutex<File>> = OnceLock::new();
+
+/// Opens the log file for appending, creating it if needed
+pub fn init(path: &Path) -> Result<(), String> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path).map_err(|e| e.to_string())?;
+    let _ = FILE.set(Mutex::new(file));
+    Ok(())
+}
+
+pub fn write(message: &str) {
+    let file = match FILE.get() {
+        Some(file) => file,
+        None => return,
+    };
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    if let Ok(mut file) = file.lock() {
+        let _ = writeln!(file, "{} {}", time, message);
+    }
+}
@@ -1,16 +1,34 @@
+mod cli;
 mod client;
+mod columns;
+mod command;
+mod config;
+mod discovery;
+mod home;
+mod keymap;
+mod log;
+mod mpris;
+mod panes;
+mod profile;
+mod queue;
+mod remote;
+mod settings;
+mod sort;
+mod theme;
 mod tui;
+mod websocket;
 
 use tokio;
 
+use clap::Parser;
 use std::io::stdout;
-use std::env;
 // use serde_yaml::Value;
 // use std::{collections::HashMap};
 
 use libmpv::{*};
 
 use crossterm::{
+    event::{DisableMouseCapture, EnableMouseCapture},
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
     execute, ExecutableCommand,
 };
@@ -18,12 +36,27 @@ use ratatui::prelude::{CrosstermBackend, Terminal};
 
 #[tokio::main]
 async fn main() {
+    let cli = cli::Cli::parse();
+    if let Some(ref path) = cli.config {
+        config::set_config_file(path.clone());
+    }
+    if let Some(ref path) = cli.log_file {
+        if let Err(e) = log::init(path) {
+            eprintln!("[!!] Could not open the log file: {}", e);
+        }
+    }
+    if let Some(command) = cli.command {
+        std::process::exit(cli::run(command, cli.profile.as_deref()).await);
+    }
+    let daemon_mode = cli.daemon;
+
     let version = env!("CARGO_PKG_VERSION");
 
-    println!(
-        "{}",
-        format!(
-          "
+    if !cli.no_banner {
+        println!(
+            "{}",
+            format!(
+              "
   ⠀⠀⠀⠀⡴⠂⢩⡉⠉⠉⡖⢄⠀
   ⠀⠀⠀⢸⠪⠄⠀⠀⠀⠀⠐⠂⢧⠀⠀⠀\x1b[94mjellyfin-tui\x1b[0m by dhonus
   ⠀⠀⠀⠙⢳⣢⢬⣁⠀⠛⠀⠂⡞
@@ -35,45 +68,137 @@ async fn main() {
   ⠀⠀⠀⠀⠈⠣⠀⢸⠀⠀⢠⠇⠀⠀⠀⠀This is free software (GPLv3).
   ⠀⠀⠀⠀⠀⠀⢠⠃⠀⠔⠁⠀⠀
   ",
-            version, MPV_CLIENT_API_MAJOR, MPV_CLIENT_API_MINOR, MPV_CLIENT_API_VERSION
-        )
-    );
+                version, MPV_CLIENT_API_MAJOR, MPV_CLIENT_API_MINOR, MPV_CLIENT_API_VERSION
+            )
+        );
+    }
 
-    let client = client::Client::new().await;
-    if client.access_token.is_empty() {
-        println!("Failed to authenticate. Exiting...");
-        return;
+    let theme = match theme::Theme::load() {
+        Ok(theme) => theme,
+        Err(e) => {
+            println!("[!!] Failed to load theme, using the default: {}", e);
+            theme::Theme::default()
+        }
+    };
+
+    // typed in on the first run, only used to log in
+    let mut password = None;
+    match config::config_file() {
+        Some(file) if file.exists() => {
+            println!("[OK] Found config file at: {}", file.display());
+            if let Err(e) = config::restrict_permissions() {
+                println!("[!!] Could not make the config file private: {}", e);
+            }
+        }
+        // the daemon may have no terminal to draw the setup form in
+        _ if daemon_mode => password = Some(profile::create_config()),
+        _ => match settings::run_setup(&theme).await {
+            Some(typed) => {
+                password = Some(typed);
+                println!("[OK] Created config file at: {}", config::config_file().unwrap_or_default().display());
+            }
+            None => {
+                println!("Exited!");
+                return;
+            }
+        },
     }
 
-    println!("[OK] Authenticated!");
+    // reads the file itself, the environment overrides are not to be written back
+    match config::load() {
+        Ok(file) => match profile::migrate_passwords(&file).await {
+            Ok(0) => {}
+            Ok(count) => println!("[OK] Moved {} password(s) out of the config file, only login tokens are kept", count),
+            Err(e) => println!("[!!] Could not move the passwords out of the config file: {}", e),
+        },
+        Err(e) => println!("[!!] Could not read config file: {}", e),
+    }
 
-    let artists = match client.artists().await {
-        Ok(artists) => artists,
+    let (config, issues) = config::Config::read();
+    for issue in issues {
+        log::write(&format!("[!!] In the config file, {}", issue));
+        match issue.error {
+            true => println!("[!!] In the config file, {} (using the default)", issue),
+            false => println!("[!!] In the config file, {}", issue),
+        }
+    }
+
+    let profile = match config.profile(cli.profile.as_deref()) {
+        Ok(profile) => profile,
         Err(e) => {
-            println!("[!!] Failed to get artists: {:?}", e);
+            log::write(&format!("[!!] {}", e));
+            println!("[!!] {}", e);
             return;
         }
     };
 
+    let client = match profile::connect(&profile, password).await {
+        Ok(client) => client,
+        Err(e) => {
+            log::write(&format!("[!!] Failed to authenticate: {}", e));
+            println!("[!!] Failed to authenticate: {}", e);
+            return;
+        }
+    };
+
+    log::write("[OK] Authenticated!");
+    println!("[OK] Authenticated!");
+    println!("[OK] Streaming from jellyfin at: {}", client.base_url);
+
+    let start_view = config.start_view;
+
+    if daemon_mode {
+        let mut app = tui::App::default();
+        app.init(client, theme, config).await;
+        if let Err(e) = app.serve() {
+            println!("[!!] Could not start the daemon: {}", e);
+            return;
+        }
+        app.restore_queue().await;
+        println!("[OK] Running as a daemon on {}", remote::socket_path().display());
+        println!("     Start jellyfin-tui to attach, `jellyfin-tui remote quit` to stop");
+        while !app.exit {
+            app.update().await;
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+        app.save_queue_on_exit().await;
+        println!("Exited!");
+        return;
+    }
+
+    // a running daemon does the playing, this TUI just controls it
+    let daemon = remote::Daemon::connect().ok();
+
     enable_raw_mode().unwrap();
-    execute!(stdout(), EnterAlternateScreen).unwrap();
+    execute!(stdout(), EnterAlternateScreen, EnableMouseCapture).unwrap();
 
     let mut terminal = Terminal::new(CrosstermBackend::new(stdout())).unwrap();
     terminal.clear().unwrap();
 
     let mut app = tui::App::default();
-    app.init(artists).await;
-    
+    app.init(client, theme, config).await;
+    app.start_in(start_view).await;
+    match daemon {
+        Some(daemon) => app.attach(daemon),
+        None => {
+            if let Err(e) = app.serve() {
+                app.set_message(format!("Remote control disabled: {}", e));
+            }
+            app.restore_queue().await;
+        }
+    }
+
     terminal.clear().unwrap();
 
     loop {
         app.run(&mut terminal).await;
         if app.exit {
             disable_raw_mode().unwrap();
-            execute!(stdout(), LeaveAlternateScreen).unwrap();
+            execute!(stdout(), LeaveAlternateScreen, DisableMouseCapture).unwrap();
             break;
         }
     }
+    app.save_queue_on_exit().await;
     println!("Exited!");
 }
 
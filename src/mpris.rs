@@ -0,0 +1,370 @@
+//! MPRIS
+//!
+//! Exposes the player on the D-Bus session bus as org.mpris.MediaPlayer2.jellyfin_tui, so media
+//! keys, playerctl and desktop widgets can control it.
+//!
+//! mpris-server's Player is not Send, so it lives on its own thread with a single threaded
+//! runtime. The app sends it a Status snapshot whenever something changes, and method calls
+//! coming in over D-Bus are turned into Actions and sent back to App::perform.
+use crate::keymap::Action;
+use crate::tui::Repeat;
+use mpris_server::{LoopStatus, Metadata, PlaybackStatus, Player, Time, TrackId};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Instant;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+const BUS_NAME_SUFFIX: &str = "jellyfin_tui";
+
+/// What the D-Bus side needs to know about the player
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Status {
+    pub song_id: String, // empty when nothing is playing
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub art_url: String,
+    pub length: f64,   // seconds
+    pub position: f64, // seconds
+    pub paused: bool,
+    pub volume: i64, // 0-100
+    pub shuffle: bool,
+    pub repeat: Repeat,
+}
+
+/// Starts the MPRIS server thread. The returned sender takes status updates, actions requested
+/// over D-Bus arrive on `actions`. If there is no session bus the thread just ends
+pub fn spawn(actions: Sender<Action>) -> UnboundedSender<Status> {
+    let (sender, receiver) = unbounded_channel();
+    thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(runtime) => runtime,
+            Err(_) => return,
+        };
+        let local = tokio::task::LocalSet::new();
+        local.block_on(&runtime, serve(actions, receiver));
+    });
+    sender
+}
+
+async fn serve(actions: Sender<Action>, mut receiver: UnboundedReceiver<Status>) {
+    let player = match Player::builder(BUS_NAME_SUFFIX)
+        .identity("jellyfin-tui")
+        .can_quit(true)
+        .can_play(true)
+        .can_pause(true)
+        .can_seek(true)
+        .can_go_next(true)
+        .can_go_previous(true)
+        .can_control(true)
+        .build()
+        .await
+    {
+        Ok(player) => player,
+        Err(_) => return,
+    };
+
+    let send = |action: Action| {
+        let actions = actions.clone();
+        move |_: &Player| {
+            let _ = actions.send(action.clone());
+        }
+    };
+    player.connect_play_pause(send(Action::PlayPause));
+    player.connect_play(send(Action::SetPaused(false)));
+    player.connect_pause(send(Action::SetPaused(true)));
+    player.connect_stop(send(Action::SetPaused(true)));
+    player.connect_next(send(Action::Next));
+    player.connect_previous(send(Action::Previous));
+    player.connect_quit(send(Action::Quit));
+
+    let a = actions.clone();
+    player.connect_seek(move |_, offset| {
+        let _ = a.send(Action::Seek(offset.as_micros() as f64 / 1_000_000.0));
+    });
+    let a = actions.clone();
+    player.connect_set_position(move |player, track_id, position| {
+        // the spec says to ignore requests for a track that is no longer current
+        if player.metadata().trackid().as_ref() == Some(track_id) {
+            let _ = a.send(Action::SeekTo(position.as_micros() as f64 / 1_000_000.0));
+        }
+    });
+    let a = actions.clone();
+    player.connect_set_volume(move |_, volume| {
+        let _ = a.send(Action::SetVolume((volume * 100.0).round() as i64));
+    });
+    let a = actions.clone();
+    player.connect_set_shuffle(move |_, shuffle| {
+        let _ = a.send(Action::SetShuffle(shuffle));
+    });
+    let a = actions;
+    player.connect_set_loop_status(move |_, loop_status| {
+        let _ = a.send(Action::SetRepeat(repeat(loop_status)));
+    });
+
+    tokio::task::spawn_local(player.run());
+
+    let mut last = Status::default();
+    let mut last_update = Instant::now();
+    while let Some(status) = receiver.recv().await {
+        let _ = update(&player, &last, &status, last_update.elapsed().as_secs_f64()).await;
+        last = status;
+        last_update = Instant::now();
+    }
+}
+
+/// What has to be told to D-Bus going from one status to the next
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+struct Changes {
+    metadata: bool,
+    volume: bool,
+    shuffle: bool,
+    repeat: bool,
+    /// the position jumped instead of moving on with the time that passed
+    seeked: bool,
+}
+
+fn changes(last: &Status, status: &Status, elapsed: f64) -> Changes {
+    let track_changed = status.song_id != last.song_id;
+    let expected = match last.paused {
+        true => last.position,
+        false => last.position + elapsed,
+    };
+    Changes {
+        metadata: track_changed || status.title != last.title || status.length != last.length,
+        volume: status.volume != last.volume,
+        shuffle: status.shuffle != last.shuffle,
+        repeat: status.repeat != last.repeat,
+        seeked: !track_changed && (status.position - expected).abs() > 1.5,
+    }
+}
+
+fn playback_status(status: &Status) -> PlaybackStatus {
+    match (status.song_id.is_empty(), status.paused) {
+        (true, _) => PlaybackStatus::Stopped,
+        (false, true) => PlaybackStatus::Paused,
+        (false, false) => PlaybackStatus::Playing,
+    }
+}
+
+fn loop_status(repeat: Repeat) -> LoopStatus {
+    match repeat {
+        Repeat::None => LoopStatus::None,
+        Repeat::Queue => LoopStatus::Playlist,
+        Repeat::Track => LoopStatus::Track,
+    }
+}
+
+fn repeat(loop_status: LoopStatus) -> Repeat {
+    match loop_status {
+        LoopStatus::None => Repeat::None,
+        LoopStatus::Playlist => Repeat::Queue,
+        LoopStatus::Track => Repeat::Track,
+    }
+}
+
+/// Pushes whatever changed since the last status to D-Bus
+async fn update(player: &Player, last: &Status, status: &Status, elapsed: f64) -> mpris_server::zbus::Result<()> {
+    let changes = changes(last, status, elapsed);
+    if changes.metadata {
+        player.set_metadata(metadata(status)).await?;
+    }
+    let playback_status = playback_status(status);
+    if playback_status != player.playback_status() {
+        player.set_playback_status(playback_status).await?;
+    }
+    if changes.volume {
+        player.set_volume(status.volume as f64 / 100.0).await?;
+    }
+    if changes.shuffle {
+        player.set_shuffle(status.shuffle).await?;
+    }
+    if changes.repeat {
+        player.set_loop_status(loop_status(status.repeat)).await?;
+    }
+
+    // Position is polled by clients, only jumps are signalled
+    let position = Time::from_micros((status.position * 1_000_000.0) as i64);
+    player.set_position(position);
+    if changes.seeked {
+        player.seeked(position).await?;
+    }
+    Ok(())
+}
+
+fn metadata(status: &Status) -> Metadata {
+    if status.song_id.is_empty() {
+        return Metadata::new();
+    }
+    let mut builder = Metadata::builder()
+        .title(status.title.as_str())
+        .artist([status.artist.as_str()])
+        .album(status.album.as_str())
+        .length(Time::from_micros((status.length * 1_000_000.0) as i64));
+    // jellyfin ids are hex, so they are always valid object path elements
+    if let Ok(track_id) = TrackId::try_from(format!("/org/jellyfin/track/{}", status.song_id)) {
+        builder = builder.trackid(track_id);
+    }
+    if !status.art_url.is_empty() {
+        builder = builder.art_url(status.art_url.as_str());
+    }
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing(position: f64) -> Status {
+        Status {
+            song_id: String::from("a1"),
+            title: String::from("Come Together"),
+            length: 259.0,
+            position,
+            volume: 80,
+            ..Status::default()
+        }
+    }
+
+    #[test]
+    fn nothing_changes_while_playing_on() {
+        assert_eq!(changes(&playing(10.0), &playing(10.5), 0.5), Changes::default());
+    }
+
+    #[test]
+    fn each_field_is_diffed() {
+        let last = playing(10.0);
+        let status = Status { volume: 50, shuffle: true, repeat: Repeat::Track, ..playing(10.0) };
+        let expected = Changes { volume: true, shuffle: true, repeat: true, ..Changes::default() };
+        assert_eq!(changes(&last, &status, 0.0), expected);
+
+        let status = Status { title: String::from("Something"), ..playing(10.0) };
+        assert!(changes(&last, &status, 0.0).metadata);
+    }
+
+    #[test]
+    fn jumps_are_seeks() {
+        assert!(changes(&playing(10.0), &playing(60.0), 0.5).seeked);
+        assert!(changes(&playing(60.0), &playing(10.0), 0.5).seeked);
+        // a paused player stays where it was however long it has been
+        let paused = Status { paused: true, ..playing(10.0) };
+        assert!(!changes(&paused, &paused, 30.0).seeked);
+        assert!(changes(&paused, &Status { paused: true, ..playing(20.0) }, 30.0).seeked);
+    }
+
+    #[test]
+    fn a_new_track_is_not_a_seek() {
+        let next = Status { song_id: String::from("b2"), ..playing(0.0) };
+        let changes = changes(&playing(200.0), &next, 0.5);
+        assert!(changes.metadata);
+        assert!(!changes.seeked);
+    }
+
+    #[test]
+    fn playback_status_follows_the_song_and_pause() {
+        assert_eq!(playback_status(&Status::default()), PlaybackStatus::Stopped);
+        assert_eq!(playback_status(&playing(0.0)), PlaybackStatus::Playing);
+        assert_eq!(playback_status(&Status { paused: true, ..playing(0.0) }), PlaybackStatus::Paused);
+    }
+
+    #[test]
+    fn repeat_maps_to_loop_status_and_back() {
+        for repeat_mode in [Repeat::None, Repeat::Queue, Repeat::Track] {
+            assert_eq!(repeat(loop_status(repeat_mode)), repeat_mode);
+        }
+        assert_eq!(loop_status(Repeat::Queue), LoopStatus::Playlist);
+        assert_eq!(loop_status(Repeat::Track), LoopStatus::Track);
+    }
+
+    /// A dbus-daemon of our own, stopped when the test ends
+    struct Bus(std::process::Child);
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    #[tokio::test]
+    async fn player_on_a_private_session_bus() {
+        use mpris_server::zbus::proxy::{Builder, CacheProperties};
+        use mpris_server::zbus::zvariant::{ObjectPath, OwnedValue};
+        use mpris_server::zbus::{connection, Proxy};
+        use std::collections::HashMap;
+        use std::io::{BufRead, BufReader};
+        use std::process::{Command, Stdio};
+        use std::time::Duration;
+
+        let daemon = Command::new("dbus-daemon")
+            .args(["--session", "--print-address", "--nofork"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut bus = match daemon {
+            Ok(daemon) => Bus(daemon),
+            Err(_) => {
+                eprintln!("no dbus-daemon, skipping");
+                return;
+            }
+        };
+        let mut address = String::new();
+        BufReader::new(bus.0.stdout.take().unwrap()).read_line(&mut address).unwrap();
+        let address = address.trim();
+        std::env::set_var("DBUS_SESSION_BUS_ADDRESS", address);
+
+        let (actions, received) = std::sync::mpsc::channel();
+        let statuses = spawn(actions);
+
+        let connection = connection::Builder::address(address).unwrap().build().await.unwrap();
+        let player: Proxy = Builder::new(&connection)
+            .destination(format!("org.mpris.MediaPlayer2.{}", BUS_NAME_SUFFIX))
+            .unwrap()
+            .path("/org/mpris/MediaPlayer2")
+            .unwrap()
+            .interface("org.mpris.MediaPlayer2.Player")
+            .unwrap()
+            .cache_properties(CacheProperties::No)
+            .build()
+            .await
+            .unwrap();
+        // polls until the property has the value, the player updates on its own thread
+        let wait_for = |value: &'static str| {
+            let player = player.clone();
+            async move {
+                for _ in 0..100 {
+                    if player.get_property::<String>("PlaybackStatus").await.ok().as_deref() == Some(value) {
+                        return;
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                panic!("PlaybackStatus never became {}", value);
+            }
+        };
+        wait_for("Stopped").await;
+
+        let status = Status { art_url: String::from("file:///tmp/cover.jpg"), ..playing(10.0) };
+        statuses.send(status).unwrap();
+        wait_for("Playing").await;
+        let mut metadata: HashMap<String, OwnedValue> = player.get_property("Metadata").await.unwrap();
+        let mut take = |key: &str| metadata.remove(key).unwrap();
+        assert_eq!(String::try_from(take("xesam:title")).unwrap(), "Come Together");
+        assert_eq!(i64::try_from(take("mpris:length")).unwrap(), 259_000_000);
+        assert_eq!(String::try_from(take("mpris:artUrl")).unwrap(), "file:///tmp/cover.jpg");
+
+        let next_action = || received.recv_timeout(Duration::from_secs(2)).ok();
+        player.call_method("PlayPause", &()).await.unwrap();
+        assert_eq!(next_action(), Some(Action::PlayPause));
+        player.call_method("Seek", &(5_000_000i64,)).await.unwrap();
+        assert_eq!(next_action(), Some(Action::Seek(5.0)));
+        // a track that is no longer playing is ignored
+        let stale = ObjectPath::try_from("/org/jellyfin/track/b2").unwrap();
+        player.call_method("SetPosition", &(stale, 60_000_000i64)).await.unwrap();
+        let current = ObjectPath::try_from("/org/jellyfin/track/a1").unwrap();
+        player.call_method("SetPosition", &(current, 60_000_000i64)).await.unwrap();
+        assert_eq!(next_action(), Some(Action::SeekTo(60.0)));
+
+        statuses.send(Status { paused: true, ..playing(60.0) }).unwrap();
+        wait_for("Paused").await;
+    }
+}
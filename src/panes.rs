@@ -0,0 +1,259 @@
+//! LAYOUT
+//!
+//! Decides where the panes go. There are three presets:
+//!
+//! stacked  one pane at a time (the focused one) with the player below, for narrow terminals
+//! columns  artists | tracks + player | lyrics + queue, the classic look
+//! wide     artists | tracks + player | queue | lyrics, for very wide terminals
+//!
+//! In `auto` mode the preset follows the terminal width. The mode, pane sizes and which panes are
+//! shown are stored in the `layout` section of config.yaml whenever they change:
+//!
+//! layout:
+//!   mode: auto
+//!   artists_width: 18
+//!   queue_width: 24
+//!   lyrics_width: 22
+//!   show_artists: true
+//!   show_queue: true
+//!   show_lyrics: true
+use crate::config;
+use crate::tui::ActiveSection;
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+/// Below this many columns auto mode stacks the panes
+const NARROW: u16 = 100;
+/// From this many columns on auto mode gives the lyrics their own column
+const WIDE: u16 = 200;
+
+const MIN_WIDTH: u16 = 10;
+const MAX_WIDTH: u16 = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LayoutMode {
+    Auto,
+    Stacked,
+    Columns,
+    Wide,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PaneLayout {
+    pub mode: LayoutMode,
+    pub artists_width: u16, // percent of the terminal width
+    pub queue_width: u16,
+    pub lyrics_width: u16,
+    pub show_artists: bool,
+    pub show_queue: bool,
+    pub show_lyrics: bool,
+}
+
+impl Default for PaneLayout {
+    fn default() -> Self {
+        PaneLayout {
+            mode: LayoutMode::Auto,
+            artists_width: 18,
+            queue_width: 24,
+            lyrics_width: 22,
+            show_artists: true,
+            show_queue: true,
+            show_lyrics: true,
+        }
+    }
+}
+
+/// The result of laying out one frame. Hidden panes get None
+#[derive(Debug, Default)]
+pub struct Areas {
+    pub artists: Option<Rect>,
+    pub tracks: Option<Rect>,
+    pub queue: Option<Rect>,
+    pub lyrics: Option<Rect>,
+    pub player: Rect,
+    /// there is room for the cover art next to the song info
+    pub cover: bool,
+}
+
+impl PaneLayout {
+    pub fn from_config(config: &Value) -> Result<Self, String> {
+        match config.get("layout") {
+            Some(Value::Null) | None => Ok(PaneLayout::default()),
+            Some(layout) => serde_yaml::from_value(layout.clone()).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Writes the layout back to config.yaml
+    pub fn save(&self) -> Result<(), String> {
+        let value = serde_yaml::to_value(self).map_err(|e| e.to_string())?;
+        config::save_section("layout", value)
+    }
+
+    /// The preset in effect for a terminal of the given width
+    pub fn effective_mode(&self, width: u16) -> LayoutMode {
+        match self.mode {
+            LayoutMode::Auto if width < NARROW => LayoutMode::Stacked,
+            LayoutMode::Auto if width >= WIDE => LayoutMode::Wide,
+            LayoutMode::Auto => LayoutMode::Columns,
+            mode => mode,
+        }
+    }
+
+    pub fn cycle_mode(&mut self) {
+        self.mode = match self.mode {
+            LayoutMode::Auto => LayoutMode::Stacked,
+            LayoutMode::Stacked => LayoutMode::Columns,
+            LayoutMode::Columns => LayoutMode::Wide,
+            LayoutMode::Wide => LayoutMode::Auto,
+        };
+    }
+
+    /// Whether a pane can be focused. The tracks pane is always there
+    pub fn is_visible(&self, section: ActiveSection, width: u16) -> bool {
+        match self.effective_mode(width) {
+            LayoutMode::Stacked => true,
+            _ => match section {
+                ActiveSection::Artists => self.show_artists,
+                ActiveSection::Tracks => true,
+                ActiveSection::Queue => self.show_queue,
+            },
+        }
+    }
+
+    /// Grows (or shrinks with a negative step) the focused pane
+    pub fn resize(&mut self, section: ActiveSection, step: i16) {
+        let apply = |width: u16, step: i16| {
+            (width as i16 + step).clamp(MIN_WIDTH as i16, MAX_WIDTH as i16) as u16
+        };
+        match section {
+            ActiveSection::Artists => self.artists_width = apply(self.artists_width, step),
+            ActiveSection::Queue => self.queue_width = apply(self.queue_width, step),
+            // the tracks pane takes what is left, so make the others smaller
+            ActiveSection::Tracks => {
+                self.artists_width = apply(self.artists_width, -step / 2 - step % 2);
+                self.queue_width = apply(self.queue_width, -step / 2);
+            }
+        }
+    }
+
+    pub fn split(&self, area: Rect, active: ActiveSection, has_lyrics: bool) -> Areas {
+        match self.effective_mode(area.width) {
+            LayoutMode::Stacked => self.split_stacked(area, active),
+            LayoutMode::Wide => self.split_wide(area),
+            _ => self.split_columns(area, has_lyrics),
+        }
+    }
+
+    fn split_stacked(&self, area: Rect, active: ActiveSection) -> Areas {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Min(0), Constraint::Length(7)])
+            .split(area);
+
+        let mut areas = Areas {
+            player: rows[1],
+            cover: false,
+            ..Default::default()
+        };
+        match active {
+            ActiveSection::Artists => areas.artists = Some(rows[0]),
+            ActiveSection::Tracks => areas.tracks = Some(rows[0]),
+            ActiveSection::Queue => areas.queue = Some(rows[0]),
+        }
+        areas
+    }
+
+    fn split_columns(&self, area: Rect, has_lyrics: bool) -> Areas {
+        let show_right = self.show_queue || self.show_lyrics;
+        let (columns, artists, center, right) = self.columns(area, show_right, None);
+        let center = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Percentage(86), Constraint::Min(8)])
+            .split(columns[center]);
+
+        let mut areas = Areas {
+            artists: artists.map(|i| columns[i]),
+            tracks: Some(center[0]),
+            player: center[1],
+            cover: center[1].width >= 60,
+            ..Default::default()
+        };
+
+        if let Some(right) = right {
+            match (self.show_lyrics, self.show_queue) {
+                (true, true) => {
+                    // lyrics get more room when there are any
+                    let (top, bottom) = match has_lyrics {
+                        true => (68, 32),
+                        false => (10, 90),
+                    };
+                    let split = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints(vec![Constraint::Percentage(top), Constraint::Percentage(bottom)])
+                        .split(columns[right]);
+                    areas.lyrics = Some(split[0]);
+                    areas.queue = Some(split[1]);
+                }
+                (true, false) => areas.lyrics = Some(columns[right]),
+                (false, _) => areas.queue = Some(columns[right]),
+            }
+        }
+        areas
+    }
+
+    fn split_wide(&self, area: Rect) -> Areas {
+        let lyrics = match self.show_lyrics {
+            true => Some(self.lyrics_width),
+            false => None,
+        };
+        let (columns, artists, center, queue) = self.columns(area, self.show_queue, lyrics);
+        let center_split = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Percentage(86), Constraint::Min(8)])
+            .split(columns[center]);
+
+        Areas {
+            artists: artists.map(|i| columns[i]),
+            tracks: Some(center_split[0]),
+            queue: queue.map(|i| columns[i]),
+            lyrics: lyrics.map(|_| columns[columns.len() - 1]),
+            player: center_split[1],
+            cover: center_split[1].width >= 60,
+        }
+    }
+
+    /// Splits the screen horizontally into the optional artists column, the tracks column,
+    /// the optional queue column and the optional lyrics column. Returns the indexes of each
+    fn columns(
+        &self,
+        area: Rect,
+        show_right: bool,
+        lyrics_width: Option<u16>,
+    ) -> (std::rc::Rc<[Rect]>, Option<usize>, usize, Option<usize>) {
+        let mut constraints = vec![];
+        let mut artists = None;
+        if self.show_artists {
+            artists = Some(constraints.len());
+            constraints.push(Constraint::Percentage(self.artists_width));
+        }
+        let center = constraints.len();
+        constraints.push(Constraint::Fill(1));
+        let mut right = None;
+        if show_right {
+            right = Some(constraints.len());
+            constraints.push(Constraint::Percentage(self.queue_width));
+        }
+        if let Some(width) = lyrics_width {
+            constraints.push(Constraint::Percentage(width));
+        }
+
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(constraints)
+            .split(area);
+        (columns, artists, center, right)
+    }
+}
@@ -0,0 +1,369 @@
+//! SERVER PROFILES
+//!
+//! config.yaml can hold more than one server, each as a named profile:
+//!
+//! profiles:
+//!   home:
+//!     server: http://192.168.1.10:8096
+//!     username: me
+//!   office:
+//!     server: https://jellyfin.example.com
+//!     username: me
+//!     libraries: [Music]
+//! default_profile: home
+//!
+//! The server and username at the top level, as written on the first run, are the profile
+//! called `default`. `--profile <name>` picks one when starting, `gP` switches in the app. Each
+//! profile has its own login token, saved queue and cover art cache. The default profile keeps
+//! using the directories from before there were profiles.
+//!
+//! Passwords are not stored. The password is asked for when logging in and only the token the
+//! server gives back is kept, in login.json readable by the user alone. A `password` still in
+//! the config is used to log in once and then removed, see migrate_passwords.
+use crate::config;
+use crate::discovery;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use crate::client::Client;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Name of the profile made of the top level server and username
+pub const DEFAULT: &str = "default";
+
+/// How long a profile gets to log in when its password is moved out of the config
+const MIGRATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    pub server: String,
+    pub username: String,
+    /// only set by configs from before passwords were moved out, see migrate_passwords
+    pub password: String,
+    /// libraries to browse by name or id, all music libraries when empty
+    pub libraries: Vec<String>,
+}
+
+/// How a profile is written in config.yaml
+#[derive(Debug, Deserialize)]
+struct Entry {
+    server: String,
+    username: String,
+    #[serde(default)]
+    password: String,
+    #[serde(default)]
+    libraries: Vec<String>,
+}
+
+/// A login the server gave us, kept so that starting doesn't log in again every time
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Login {
+    pub server: String,
+    pub username: String,
+    pub user_id: String,
+    pub access_token: String,
+    /// the device the token was given to, logins from before there was one used "None"
+    #[serde(default = "old_device_id")]
+    pub device_id: String,
+}
+
+fn old_device_id() -> String {
+    String::from("None")
+}
+
+/// Every profile in the config, the top level one first
+pub fn list(config: &Value) -> Result<Vec<Profile>, String> {
+    let mut profiles = vec![];
+    if config.get("server").is_some() {
+        let entry: Entry = serde_yaml::from_value(config.clone()).map_err(|e| e.to_string())?;
+        profiles.push(entry.into_profile(DEFAULT));
+    }
+    if let Some(Value::Mapping(entries)) = config.get("profiles") {
+        for (name, entry) in entries {
+            let name = name.as_str().ok_or("profile names must be strings")?;
+            if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+                return Err(format!("invalid profile name '{}'", name));
+            }
+            let entry: Entry =
+                serde_yaml::from_value(entry.clone()).map_err(|e| format!("profile '{}': {}", name, e))?;
+            profiles.push(entry.into_profile(name));
+        }
+    }
+    Ok(profiles)
+}
+
+/// The profile to use: the one asked for, else `default_profile`, else the first one
+pub fn select(config: &Value, wanted: Option<&str>) -> Result<Profile, String> {
+    let default = config.get("default_profile").and_then(Value::as_str);
+    pick(list(config)?, wanted.or(default))
+}
+
+/// The profile with the given name, or the first one
+pub fn pick(profiles: Vec<Profile>, wanted: Option<&str>) -> Result<Profile, String> {
+    match wanted {
+        Some(name) => profiles
+            .into_iter()
+            .find(|profile| profile.name == name)
+            .ok_or(format!("there is no profile called '{}'", name)),
+        None => profiles.into_iter().next().ok_or(String::from("no server configured")),
+    }
+}
+
+impl Entry {
+    fn into_profile(self, name: &str) -> Profile {
+        Profile {
+            name: name.to_string(),
+            server: self.server,
+            username: self.username,
+            password: self.password,
+            libraries: self.libraries,
+        }
+    }
+}
+
+impl Profile {
+    /// ~/.local/state/jellyfin-tui, or ~/.local/state/jellyfin-tui/profiles/<name>
+    pub fn state_dir(&self) -> Option<PathBuf> {
+        let dir = config::state_dir()?;
+        match self.name.as_str() {
+            DEFAULT => Some(dir),
+            name => Some(dir.join("profiles").join(name)),
+        }
+    }
+
+    /// ~/.cache/jellyfin-tui, or ~/.cache/jellyfin-tui/profiles/<name>
+    pub fn cache_dir(&self) -> Option<PathBuf> {
+        let dir = dirs::cache_dir()?.join("jellyfin-tui");
+        match self.name.as_str() {
+            DEFAULT => Some(dir),
+            name => Some(dir.join("profiles").join(name)),
+        }
+    }
+
+    /// The saved login, unless the profile now points at another server or user
+    pub fn load_login(&self) -> Option<Login> {
+        let json = std::fs::read_to_string(self.state_dir()?.join("login.json")).ok()?;
+        let login: Login = serde_json::from_str(&json).ok()?;
+        match login.server == self.server && login.username == self.username {
+            true => Some(login),
+            false => None,
+        }
+    }
+
+    pub fn save_login(&self, login: &Login) -> Result<(), String> {
+        let dir = self.state_dir().ok_or("could not find the state directory")?;
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        let json = serde_json::to_string(login).map_err(|e| e.to_string())?;
+        let path = dir.join("login.json");
+        let tmp = path.with_extension("json.tmp");
+        config::write_private(&tmp, json.as_bytes()).map_err(|e| e.to_string())?;
+        std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+    }
+
+    /// Deletes the saved login, so the next start asks for the password again
+    pub fn forget_login(&self) -> Result<(), String> {
+        let path = self.state_dir().ok_or("could not find the state directory")?.join("login.json");
+        match std::fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the libraries to browse back to this profile's part of config.yaml. Null means
+    /// all of them
+    pub fn save_libraries(&self, libraries: Value) -> Result<(), String> {
+        if self.name == DEFAULT {
+            return config::save_section("libraries", libraries);
+        }
+        let mut profiles = match config::load()?.get("profiles") {
+            Some(Value::Mapping(profiles)) => profiles.clone(),
+            _ => return Err(format!("profile '{}' is not in the config file", self.name)),
+        };
+        match profiles.get_mut(&Value::String(self.name.clone())) {
+            Some(Value::Mapping(entry)) => {
+                entry.insert(Value::String(String::from("libraries")), libraries);
+            }
+            _ => return Err(format!("profile '{}' is not in the config file", self.name)),
+        }
+        config::save_section("profiles", Value::Mapping(profiles))
+    }
+}
+
+/// Asks for the server, username and password on the first run and writes the server and
+/// username to a new config.yaml as the default profile. Returns the password, to log in with
+pub fn create_config() -> String {
+    let config_file = match config::config_file() {
+        Some(file) => file,
+        None => {
+            println!("[!!] Could not find config directory");
+            std::process::exit(1);
+        }
+    };
+
+    let mut server = String::new();
+    let mut username = String::new();
+    let mut password = String::new();
+
+    println!("\n[!!] The configuration file does not exist. Please fill in the following details:");
+    println!("--- Jellyfin TUI Configuration ---");
+    println!("The expected format is:");
+    println!("- server: http://localhost:8096");
+    println!("- username: admin\n");
+
+    println!("Looking for servers on the local network...");
+    let servers = discovery::discover(discovery::target(), Duration::from_secs(2)).unwrap_or_default();
+    match servers.is_empty() {
+        true => println!("[!!] No servers answered, please enter the address yourself\n"),
+        false => {
+            for (i, found) in servers.iter().enumerate() {
+                println!("  {}) {} ({})", i + 1, found.name, found.address);
+            }
+            println!();
+        }
+    }
+
+    let mut ok = false;
+    while !ok {
+        while server.trim().is_empty() || !server.contains("http") {
+            match servers.is_empty() {
+                true => println!("host: "),
+                false => println!("host, or the number of a server above: "),
+            }
+            io::stdin().read_line(&mut server).unwrap();
+            let picked = server.trim().parse::<usize>().ok().and_then(|n| servers.get(n.wrapping_sub(1)));
+            if let Some(picked) = picked {
+                server = picked.address.clone();
+            } else if server.trim().is_empty() {
+                println!("[!!] Host cannot be empty");
+                server = "".to_string();
+            } else if !server.contains("http") {
+                println!("[!!] Host must be a valid URL including http or https");
+                server = "".to_string();
+            }
+        }
+        println!("username: ");
+        io::stdin().read_line(&mut username).expect("Failed to read username");
+        password = read_password("password: ").unwrap_or_default();
+
+        println!("\nHost: '{}' Username: '{}'", server.trim(), username.trim());
+        println!("[!!] Is this correct? (Y/n)");
+        let mut confirm = String::new();
+        io::stdin().read_line(&mut confirm).expect("Failed to read confirmation");
+        // y is default
+        if confirm.contains("n") || confirm.contains("N") {
+            server = "".to_string();
+            username = "".to_string();
+            password = "".to_string();
+        } else {
+            ok = true;
+        }
+    }
+
+    // the password is only used to log in, see migrate_passwords
+    let config = serde_yaml::to_value(serde_json::json!({
+        "server": server.trim(),
+        "username": username.trim(),
+    })).unwrap_or(Value::Null);
+
+    match config::save(&config) {
+        Ok(_) => println!("\n[OK] Created default config file at: {}", config_file.display()),
+        Err(e) => {
+            println!("[!!] Could not write the config file: {}", e);
+            std::process::exit(1);
+        }
+    }
+    password
+}
+
+/// Reads a line from the terminal without showing it. None if it was cancelled or there is
+/// no terminal to ask on
+pub fn read_password(prompt: &str) -> Option<String> {
+    if !io::stdin().is_terminal() {
+        return None;
+    }
+    print!("{}", prompt);
+    let _ = io::stdout().flush();
+    enable_raw_mode().ok()?;
+    let mut password = String::new();
+    let entered = loop {
+        let key = match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => key,
+            Ok(_) => continue,
+            Err(_) => break false,
+        };
+        match key.code {
+            KeyCode::Enter => break true,
+            KeyCode::Esc => break false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break false,
+            KeyCode::Backspace => {
+                password.pop();
+            }
+            KeyCode::Char(c) => password.push(c),
+            _ => {}
+        }
+    };
+    let _ = disable_raw_mode();
+    println!();
+    entered.then_some(password)
+}
+
+/// Logs in with the saved token, or else with a password: the one given, the one still in
+/// the config, or one typed on the terminal
+pub async fn connect(profile: &Profile, password: Option<String>) -> Result<Client, String> {
+    if let Some(client) = Client::resume(profile).await {
+        return Ok(client);
+    }
+    let password = password
+        .or_else(|| Some(profile.password.clone()).filter(|password| !password.is_empty()))
+        .or_else(|| read_password(&format!("Password for {} on {}: ", profile.username, profile.server)))
+        .ok_or("no password given")?;
+    Client::log_in(profile, &password).await
+}
+
+/// Logs in every profile that still has its password in config.yaml, then removes the
+/// passwords that worked so only the tokens are kept. A profile that can't log in right now
+/// keeps its password until the next start. The profiles log in side by side and each gets
+/// MIGRATION_TIMEOUT, so an unreachable server doesn't hold up starting. Returns how many were
+/// moved out
+pub async fn migrate_passwords(config: &Value) -> Result<usize, String> {
+    let attempts: Vec<_> = list(config)?
+        .into_iter()
+        .filter(|profile| !profile.password.is_empty())
+        .map(|profile| tokio::spawn(async move {
+            let logging_in = async {
+                Client::resume(&profile).await.is_some()
+                    || Client::log_in(&profile, &profile.password).await.is_ok()
+            };
+            let logged_in = tokio::time::timeout(MIGRATION_TIMEOUT, logging_in).await.unwrap_or(false);
+            (profile, logged_in)
+        }))
+        .collect();
+
+    let mut migrated = config.clone();
+    let mut count = 0;
+    for attempt in attempts {
+        let (profile, logged_in) = match attempt.await {
+            Ok(attempt) => attempt,
+            Err(_) => continue,
+        };
+        if !logged_in {
+            continue;
+        }
+        let entry = match profile.name.as_str() {
+            DEFAULT => Some(&mut migrated),
+            name => migrated.get_mut("profiles").and_then(|profiles| profiles.get_mut(name)),
+        };
+        if let Some(Value::Mapping(entry)) = entry {
+            entry.remove(&Value::String(String::from("password")));
+            count += 1;
+        }
+    }
+    if count > 0 {
+        config::save(&migrated)?;
+    }
+    Ok(count)
+}
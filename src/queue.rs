@@ -0,0 +1,52 @@
+//! SAVED QUEUE
+//!
+//! The queue, where we are in it and the player modes are written to queue.json in the
+//! profile's state directory every 30 seconds and on exit, then restored, paused, on the next
+//! start.
+//!
+//! On exit the current song is reported stopped and the server's LastPlayedDate for it is kept
+//! as `last_played`. If the server has a different date on the next start, another device
+//! played the song in between and the position it reported wins over ours.
+use crate::profile::Profile;
+use crate::tui::{Repeat, Song};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedQueue {
+    pub songs: Vec<Song>,
+    pub unshuffled: Vec<Song>, // order before shuffling, empty when shuffle is off
+    pub index: usize,
+    pub position: f64, // seconds
+    pub shuffle: bool,
+    pub repeat: Repeat,
+    pub volume: i64,
+    pub muted: bool,
+    // empty for the periodic saves, those only matter after a crash
+    pub last_played: String,
+}
+
+/// ~/.local/state/jellyfin-tui/queue.json, see profile.rs for the other profiles
+fn path(profile: &Profile) -> Option<PathBuf> {
+    profile.state_dir().map(|dir| dir.join("queue.json"))
+}
+
+/// The queue saved by the last run, if there is one and it can be read
+pub fn load(profile: &Profile) -> Option<SavedQueue> {
+    let json = std::fs::read_to_string(path(profile)?).ok()?;
+    serde_json::from_str(&json).ok()
+}
+
+pub fn save(profile: &Profile, queue: &SavedQueue) -> Result<(), String> {
+    let path = path(profile).ok_or("could not find the state directory")?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let json = serde_json::to_string(queue).map_err(|e| e.to_string())?;
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, json).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, &path).map_err(|e| e.to_string())
+}
@@ -0,0 +1,345 @@
+//! REMOTE CONTROL
+//!
+//! A Unix socket in $XDG_RUNTIME_DIR (or $TMPDIR/jellyfin-tui-<uid>) that lets scripts and window manager key bindings drive a
+//! running jellyfin-tui, like cmus-remote. Every line sent is one command and gets one line of
+//! JSON back. Commands are the same as on the `:` command line, plus `status`:
+//!
+//! $ echo "seek +30" | socat - UNIX-CONNECT:$XDG_RUNTIME_DIR/jellyfin-tui.sock
+//! {"ok":true}
+//! $ echo '{"command": "add", "args": ["<item id>", "<item id>"]}' | socat - UNIX-CONNECT:...
+//! {"ok":true}
+//! $ echo status | socat - UNIX-CONNECT:...
+//! {"ok":true,"status":{"song":{"id":"...","name":"...",...},"position":12.5,...}}
+//!
+//! `queue` answers with the songs in the queue.
+//!
+//! `jellyfin-tui remote <command>` is a small client for this. A TUI attached to a daemon
+//! (`jellyfin-tui --daemon`) talks to it over the same socket through Daemon.
+//!
+//! Only the user running jellyfin-tui can reach the socket: its directory is private to them,
+//! and a socket or directory owned by someone else is never listened on or connected to.
+use crate::command;
+use crate::keymap::Action;
+use crate::tui::{Repeat, Song};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::net;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::watch;
+
+/// What `status` answers with
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct Status {
+    pub song: Option<Song>,
+    pub position: f64, // seconds
+    pub duration: f64,
+    pub paused: bool,
+    pub volume: i64,
+    pub muted: bool,
+    pub shuffle: bool,
+    pub repeat: Repeat,
+    pub queue_position: usize,
+    pub queue_length: usize,
+    /// changes whenever the queue does, so attached TUIs know when to fetch it again
+    pub queue_version: u64,
+    #[serde(skip)] // too long for every status, see the `queue` command
+    pub queue: Vec<Song>,
+}
+
+/// A command in JSON form, `args` can be a string or a list of strings
+#[derive(Debug, Deserialize)]
+struct Request {
+    command: String,
+    #[serde(default)]
+    args: Value,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Response {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    queue: Option<Value>,
+}
+
+/// $XDG_RUNTIME_DIR/jellyfin-tui.sock, or $TMPDIR/jellyfin-tui-<uid>/jellyfin-tui.sock where
+/// there is no runtime dir
+pub fn socket_path() -> PathBuf {
+    socket_dir().join("jellyfin-tui.sock")
+}
+
+fn socket_dir() -> PathBuf {
+    match dirs::runtime_dir() {
+        Some(dir) => dir,
+        // the temp directory is shared, so never put the socket straight into it
+        None => std::env::temp_dir().join(format!("jellyfin-tui-{}", uid())),
+    }
+}
+
+fn uid() -> u32 {
+    // SAFETY: getuid has no preconditions and can't fail
+    unsafe { libc::getuid() }
+}
+
+/// Fails unless the file is ours and nobody else can get at it
+fn check_private(path: &Path, mode_mask: u32) -> Result<(), String> {
+    // not following symlinks, someone else's link to our directory is still theirs
+    let metadata = std::fs::symlink_metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    if metadata.uid() != uid() {
+        return Err(format!("{} belongs to another user", path.display()));
+    }
+    if metadata.mode() & mode_mask != 0 {
+        return Err(format!("{} can be used by other users", path.display()));
+    }
+    Ok(())
+}
+
+/// Creates the socket directory readable by the user alone, or checks that it already is
+fn private_socket_dir() -> Result<PathBuf, String> {
+    let dir = socket_dir();
+    if !dir.exists() {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&dir)
+            .map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    if dirs::runtime_dir().is_none() {
+        check_private(&dir, 0o077)?;
+    }
+    Ok(dir)
+}
+
+/// The listening socket. The file is removed again when this is dropped
+pub struct Socket {
+    path: PathBuf,
+}
+
+impl Drop for Socket {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Starts accepting connections. Actions go to `actions`, `status` is answered from `status`
+pub fn listen(actions: Sender<Action>, status: watch::Receiver<Status>) -> Result<Socket, String> {
+    let path = private_socket_dir()?.join("jellyfin-tui.sock");
+    if path.exists() {
+        check_private(&path, 0)?;
+        if net::UnixStream::connect(&path).is_ok() {
+            return Err(format!("another jellyfin-tui is listening on {}", path.display()));
+        }
+        // left behind by a crash
+        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    }
+    // created without access for others, so there is no moment anyone else can connect
+    // SAFETY: umask only swaps the process' file mode mask
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(&path).map_err(|e| e.to_string());
+    unsafe { libc::umask(umask) };
+    let listener = listener?;
+    let _ = std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600));
+
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle(stream, actions.clone(), status.clone()));
+        }
+    });
+    Ok(Socket { path })
+}
+
+async fn handle(stream: UnixStream, actions: Sender<Action>, status: watch::Receiver<Status>) {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match execute(&line, &actions, &status) {
+            Ok(response) => response,
+            Err(e) => Response {
+                ok: false,
+                error: Some(e),
+                ..Default::default()
+            },
+        };
+        let mut json = serde_json::to_string(&response).unwrap_or_default();
+        json.push('\n');
+        if writer.write_all(json.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Runs one command. The response carries the status or the queue if that was asked for
+fn execute(line: &str, actions: &Sender<Action>, status: &watch::Receiver<Status>) -> Result<Response, String> {
+    let line = match line.trim_start().starts_with('{') {
+        true => {
+            let request: Request = serde_json::from_str(line).map_err(|e| e.to_string())?;
+            let args = match request.args {
+                Value::Null => String::new(),
+                Value::String(args) => args,
+                Value::Array(args) => args
+                    .iter()
+                    .map(|arg| match arg {
+                        Value::String(arg) => arg.clone(),
+                        arg => arg.to_string(),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" "),
+                args => args.to_string(),
+            };
+            format!("{} {}", request.command, args)
+        }
+        false => line.to_string(),
+    };
+
+    let mut response = Response {
+        ok: true,
+        ..Default::default()
+    };
+    match line.trim() {
+        "status" => {
+            response.status = Some(serde_json::to_value(&*status.borrow()).map_err(|e| e.to_string())?);
+        }
+        "queue" => {
+            response.queue = Some(serde_json::to_value(&status.borrow().queue).map_err(|e| e.to_string())?);
+        }
+        line => {
+            let action = command::parse(line)?;
+            actions.send(action).map_err(|_| String::from("jellyfin-tui is shutting down"))?;
+        }
+    }
+    Ok(response)
+}
+
+/// A connection to a running jellyfin-tui (usually a daemon)
+pub struct Daemon {
+    stream: net::UnixStream,
+    reader: BufReader<net::UnixStream>,
+}
+
+impl Daemon {
+    pub fn connect() -> Result<Self, String> {
+        let path = socket_path();
+        // a socket someone else put there could pretend to be our daemon
+        if dirs::runtime_dir().is_none() {
+            check_private(&socket_dir(), 0o077)?;
+        }
+        check_private(&path, 0o077)?;
+        let stream = net::UnixStream::connect(&path)
+            .map_err(|e| format!("could not connect to {}: {}", path.display(), e))?;
+        let _ = stream.set_read_timeout(Some(Duration::from_secs(5)));
+        let reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+        Ok(Daemon { stream, reader })
+    }
+
+    fn request(&mut self, line: &str) -> Result<Response, String> {
+        self.stream
+            .write_all(format!("{}\n", line.replace('\n', " ")).as_bytes())
+            .map_err(|e| e.to_string())?;
+        let mut answer = String::new();
+        match self.reader.read_line(&mut answer) {
+            Ok(0) => return Err(String::from("connection closed")),
+            Ok(_) => {}
+            Err(e) => return Err(e.to_string()),
+        }
+        let response: Response = serde_json::from_str(&answer).map_err(|e| format!("invalid response: {}", e))?;
+        match response.ok {
+            true => Ok(response),
+            false => Err(response.error.unwrap_or_default()),
+        }
+    }
+
+    /// Sends a command line, like the ones typed after `:`
+    pub fn command(&mut self, line: &str) -> Result<(), String> {
+        self.request(line).map(|_| ())
+    }
+
+    pub fn status(&mut self) -> Result<Status, String> {
+        let status = self.request("status")?.status.unwrap_or_default();
+        serde_json::from_value(status).map_err(|e| e.to_string())
+    }
+
+    pub fn queue(&mut self) -> Result<Vec<Song>, String> {
+        let queue = self.request("queue")?.queue.unwrap_or_default();
+        serde_json::from_value(queue).map_err(|e| e.to_string())
+    }
+
+    /// Polls the status a few times a second on its own connection, fetching the queue along
+    /// with it when it changed. Stops after sending an error
+    pub fn watch(sender: Sender<Result<Status, String>>) {
+        thread::spawn(move || {
+            let mut daemon = match Daemon::connect() {
+                Ok(daemon) => daemon,
+                Err(e) => {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+            };
+            let mut queue = vec![];
+            let mut queue_version = None;
+            loop {
+                let result = daemon.status().and_then(|mut status| {
+                    if queue_version != Some(status.queue_version) {
+                        queue = daemon.queue()?;
+                        queue_version = Some(status.queue_version);
+                    }
+                    status.queue = queue.clone();
+                    Ok(status)
+                });
+                let failed = result.is_err();
+                if sender.send(result).is_err() || failed {
+                    return;
+                }
+                thread::sleep(Duration::from_millis(200));
+            }
+        });
+    }
+}
+
+/// `jellyfin-tui remote <command>`: sends one command to the running instance and prints the
+/// answer. Returns the process exit code
+pub fn run_client(args: &[String]) -> i32 {
+    if args.is_empty() {
+        println!("Usage: jellyfin-tui remote <command> [args]");
+        println!("Commands:");
+        println!("  status");
+        for (_, usage) in command::COMMANDS.iter() {
+            println!("  {}", usage.trim_start_matches(':'));
+        }
+        return 2;
+    }
+
+    let mut daemon = match Daemon::connect() {
+        Ok(daemon) => daemon,
+        Err(e) => {
+            eprintln!("[!!] Is jellyfin-tui running? {}", e);
+            return 1;
+        }
+    };
+    let response = match daemon.request(&args.join(" ")) {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("[!!] {}", e);
+            return 1;
+        }
+    };
+
+    let result = match (response.status, response.queue) {
+        (Some(result), _) | (_, Some(result)) => result,
+        _ => return 0,
+    };
+    println!("{}", serde_json::to_string_pretty(&result).unwrap_or_default());
+    0
+}
@@ -0,0 +1,393 @@
+//! SETTINGS
+//!
+//! A full screen form for config.yaml. On the first run it asks for the server, username and
+//! password before anything else; later `gS` or `:settings` opens it with every option:
+//!
+//! server       http://192.168.1.10:8096
+//! username     me
+//! libraries    [Music, Soundtracks]
+//! keys         {play_pause: ["<Space>", p]}
+//!
+//! Text options are edited as they are, the others as one line of YAML. Saving checks every
+//! option the way the app reads it and only then replaces config.yaml, in one piece. The
+//! password is never written, it is only used to log in, see profile.rs.
+use crate::client;
+use crate::config::{self, Config};
+use crate::discovery::{self, Server};
+use crate::theme::Theme;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph, Wrap};
+use serde_yaml::{Mapping, Value};
+use std::io::stdout;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Text,
+    Secret,
+    Yaml,
+}
+
+/// key, kind and help of an option
+type Field = (&'static str, Kind, &'static str);
+
+/// (key, kind, help) of the first run form
+const SETUP_OPTIONS: [Field; 3] = [
+    ("server", Kind::Text, "Address of the Jellyfin server, e.g. http://192.168.1.10:8096"),
+    ("username", Kind::Text, "Your Jellyfin user"),
+    ("password", Kind::Secret, "Your password, used to log in and not saved. Ctrl+R shows it"),
+];
+
+/// (key, kind, help) of every option in the settings, in the order of the form
+const OPTIONS: [Field; 14] = [
+    ("server", Kind::Text, "Address of the Jellyfin server, e.g. http://192.168.1.10:8096"),
+    ("username", Kind::Text, "Your Jellyfin user. Changing the server or user asks for the password on the next start"),
+    ("libraries", Kind::Yaml, "Libraries to browse by name or id, e.g. [Music]. Empty browses every music library"),
+    ("start_view", Kind::Text, "The left pane on start: home, artists, playlists, favorites or genres"),
+    ("default_profile", Kind::Text, "Profile used without --profile. Empty uses the top level server"),
+    ("layout", Kind::Yaml, "Pane layout, e.g. {mode: wide, artists_width: 25}, see the README"),
+    ("columns", Kind::Yaml, "Extra track list columns, e.g. [favorite, play_count]"),
+    ("sort", Kind::Yaml, "Order of artists and tracks, e.g. {artists: name, tracks: album}"),
+    ("theme", Kind::Text, "Built-in theme when theme.yaml doesn't pick one: dark, light or high-contrast"),
+    ("streaming", Kind::Yaml, "e.g. {max_bitrate: 320} to transcode above 320 kbit/s. Empty streams the original"),
+    ("cache", Kind::Yaml, "e.g. {cover_art: false} to not download cover art"),
+    ("scrobbling", Kind::Yaml, "e.g. {enabled: false} to not tell the server what is played"),
+    ("keys", Kind::Yaml, "Key bindings, e.g. {play_pause: [\"<Space>\", p]}"),
+    ("profiles", Kind::Yaml, "More servers, e.g. {office: {server: ..., username: ...}}"),
+];
+
+/// What a key press in the form asks for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Continue,
+    Save,
+    Test,
+    Cancel,
+}
+
+pub struct Settings {
+    first_run: bool,
+    options: &'static [Field],
+    values: Vec<String>, // one per option
+    selected: usize,
+    reveal: bool, // show the password
+    servers: Vec<Server>, // found on the local network, ctrl+n goes through them
+    status: Option<(String, bool)>, // result of the last test or save, and whether it failed
+}
+
+impl Settings {
+    /// Every option, filled in from the config
+    pub fn new(config: &Value) -> Self {
+        let values = OPTIONS
+            .iter()
+            .map(|(key, kind, _)| match (config.get(key), kind) {
+                (None | Some(Value::Null), _) => String::new(),
+                (Some(Value::String(text)), Kind::Text | Kind::Secret) => text.clone(),
+                (Some(value), _) => inline(value),
+            })
+            .collect();
+        Settings {
+            first_run: false,
+            options: &OPTIONS,
+            values,
+            selected: 0,
+            reveal: false,
+            servers: vec![],
+            status: None,
+        }
+    }
+
+    /// The first run form, with the first server found on the network filled in
+    pub fn setup() -> Self {
+        let servers = discovery::discover(discovery::target(), Duration::from_secs(2)).unwrap_or_default();
+        let mut values = vec![String::new(); SETUP_OPTIONS.len()];
+        if let Some(server) = servers.first() {
+            values[0] = server.address.clone();
+        }
+        Settings {
+            first_run: true,
+            options: &SETUP_OPTIONS,
+            values,
+            selected: 0,
+            reveal: false,
+            servers,
+            status: None,
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Outcome {
+        if key.kind != KeyEventKind::Press {
+            return Outcome::Continue;
+        }
+        let selected = self.selected;
+        match (key.code, key.modifiers.contains(KeyModifiers::CONTROL)) {
+            (KeyCode::Esc, _) => return Outcome::Cancel,
+            (KeyCode::Enter, _) => return Outcome::Save,
+            (KeyCode::Char('t'), true) => return Outcome::Test,
+            (KeyCode::Char('r'), true) => self.reveal = !self.reveal,
+            (KeyCode::Char('u'), true) => self.values[selected].clear(),
+            (KeyCode::Char('n'), true) if !self.servers.is_empty() => {
+                let next = match self.servers.iter().position(|server| server.address == self.values[0]) {
+                    Some(index) => (index + 1) % self.servers.len(),
+                    None => 0,
+                };
+                self.values[0] = self.servers[next].address.clone();
+                self.selected = 0;
+            }
+            (KeyCode::Down | KeyCode::Tab, _) => self.selected = (self.selected + 1) % self.values.len(),
+            (KeyCode::Up | KeyCode::BackTab, _) => {
+                self.selected = (self.selected + self.values.len() - 1) % self.values.len();
+            }
+            (KeyCode::Backspace, _) => {
+                self.values[selected].pop();
+            }
+            (KeyCode::Char(c), false) => self.values[selected].push(c),
+            _ => {}
+        }
+        Outcome::Continue
+    }
+
+    /// The config with the form's values, checked the way the app reads it. Options not in
+    /// the form are kept
+    pub fn config(&self) -> Result<Value, String> {
+        let mut config = match config::load()? {
+            Value::Mapping(mapping) => mapping,
+            _ => Mapping::new(),
+        };
+        for ((key, kind, _), text) in self.options.iter().zip(&self.values) {
+            let key = Value::String(key.to_string());
+            let value = match kind {
+                Kind::Secret => continue,
+                _ if text.trim().is_empty() => {
+                    config.remove(&key);
+                    continue;
+                }
+                Kind::Text => Value::String(text.trim().to_string()),
+                Kind::Yaml => serde_yaml::from_str(text).map_err(|e| format!("{}: {}", key.as_str().unwrap_or(""), e))?,
+            };
+            config.insert(key, value);
+        }
+        let config = Value::Mapping(config);
+
+        let server = config.get("server").and_then(Value::as_str).unwrap_or("");
+        if self.first_run && (server.is_empty() || config.get("username").is_none()) {
+            return Err(String::from("server and username are needed"));
+        }
+        if !server.is_empty() && !server.starts_with("http://") && !server.starts_with("https://") {
+            return Err(String::from("server: the address must start with http:// or https://"));
+        }
+        Config::validate(&config)?;
+        Ok(config)
+    }
+
+    /// What was typed as the password, if the form asks for one
+    pub fn password(&self) -> Option<String> {
+        self.options
+            .iter()
+            .position(|(_, kind, _)| *kind == Kind::Secret)
+            .map(|index| self.values[index].clone())
+    }
+
+    /// Checks and writes the config, returning what was written
+    pub fn save(&mut self) -> Result<Value, String> {
+        let result = self.config().and_then(|config| config::save(&config).map(|_| config));
+        match result {
+            Ok(_) => self.status = Some((String::from("Saved"), false)),
+            Err(ref e) => self.status = Some((e.clone(), true)),
+        }
+        result
+    }
+
+    /// Asks the server in the form for its name and version
+    pub async fn test(&mut self) -> bool {
+        let server = self.values[0].trim();
+        if server.is_empty() {
+            self.status = Some((String::from("Fill in the server first"), true));
+            return false;
+        }
+        match client::server_info(server).await {
+            Ok(info) => {
+                let message = format!("Connected to {} (Jellyfin {})", info.server_name, info.version);
+                self.status = Some((message, false));
+                true
+            }
+            Err(e) => {
+                self.status = Some((format!("Could not reach {}: {}", server, e), true));
+                false
+            }
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame, theme: &Theme) {
+        let area = frame.size();
+        frame.render_widget(Clear, area);
+
+        let mut hints = vec![("↑↓", "option"), ("^T", "test connection")];
+        if self.password().is_some() {
+            hints.push(("^R", "show password"));
+        }
+        hints.push(("Enter", "save"));
+        hints.push(("Esc", if self.first_run { "quit" } else { "close" }));
+        let mut spans = vec![];
+        for (key, text) in hints {
+            spans.push(Span::styled(format!(" {} ", text), theme.hint_text));
+            spans.push(Span::styled(key, theme.hint_key));
+        }
+        spans.push(Span::raw(" "));
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_set(theme.border_type)
+            .border_style(theme.border_active)
+            .title(if self.first_run { "Welcome to jellyfin-tui" } else { "Settings" })
+            .title_bottom(Line::from(spans).centered());
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([
+                Constraint::Length(self.values.len() as u16),
+                Constraint::Length(1),
+                Constraint::Min(0),
+            ])
+            .split(inner);
+
+        let label_width = 17;
+        for (i, ((key, kind, _), value)) in self.options.iter().zip(&self.values).enumerate() {
+            let row = Rect { y: rows[0].y + i as u16, height: 1, ..rows[0] };
+            let shown = match kind {
+                Kind::Secret if !self.reveal => "•".repeat(value.chars().count()),
+                _ => value.clone(),
+            };
+            // the end of a long value, where typing happens
+            let width = row.width.saturating_sub(label_width + 1) as usize;
+            let skip = shown.chars().count().saturating_sub(width);
+            let shown = shown.chars().skip(skip).collect::<String>();
+            let style = if i == self.selected { theme.selected } else { Style::default() };
+            let line = Line::from(vec![
+                Span::styled(format!("{:<width$}", key, width = label_width as usize), style),
+                Span::raw(shown.clone()),
+            ]);
+            frame.render_widget(Paragraph::new(line), row);
+            if i == self.selected {
+                frame.set_cursor(row.x + label_width + shown.chars().count() as u16, row.y);
+            }
+        }
+
+        let mut lines = vec![Line::styled(self.options[self.selected].2, theme.duration), Line::raw("")];
+        if !self.servers.is_empty() {
+            lines.push(Line::raw("Servers found on the network (^N picks the next):"));
+            for server in &self.servers {
+                lines.push(Line::raw(format!("  {} ({})", server.name, server.address)));
+            }
+            lines.push(Line::raw(""));
+        }
+        if let Some((ref message, failed)) = self.status {
+            let style = if failed { theme.hint_key } else { theme.playing };
+            lines.push(Line::styled(message.clone(), style));
+        }
+        frame.render_widget(Paragraph::new(lines).wrap(Wrap { trim: false }), rows[2]);
+    }
+}
+
+/// Runs the first run form on its own before the app starts. Once saved it returns the
+/// password typed in, None means the user quit
+pub async fn run_setup(theme: &Theme) -> Option<String> {
+    let mut settings = Settings::setup();
+    enable_raw_mode().ok()?;
+    execute!(stdout(), EnterAlternateScreen).ok()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout())).ok()?;
+
+    let saved = loop {
+        let _ = terminal.draw(|frame| settings.render(frame, theme));
+        let key = match event::read() {
+            Ok(Event::Key(key)) => key,
+            _ => continue,
+        };
+        match settings.handle_key(key) {
+            Outcome::Continue => {}
+            Outcome::Cancel => break false,
+            Outcome::Test => {
+                settings.test().await;
+            }
+            // the server is tried first, so a typo is caught before anything is written
+            Outcome::Save => {
+                if settings.test().await && settings.save().is_ok() {
+                    break true;
+                }
+            }
+        }
+    };
+
+    let _ = disable_raw_mode();
+    let _ = execute!(stdout(), LeaveAlternateScreen);
+    saved.then(|| settings.password().unwrap_or_default())
+}
+
+/// A value as one line of YAML: [a, b] and {key: value}
+fn inline(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("~"),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => {
+            let plain = !s.is_empty()
+                && !s.contains([',', '[', ']', '{', '}', ':', '#', '"', '\'', '<', '&', '*', '!', '|', '>', '%', '@', '`'])
+                && s.trim() == s
+                && serde_yaml::from_str::<Value>(s).ok() == Some(Value::String(s.clone()));
+            match plain {
+                true => s.clone(),
+                false => serde_json::to_string(s).unwrap_or_default(),
+            }
+        }
+        Value::Sequence(items) => format!("[{}]", items.iter().map(inline).collect::<Vec<String>>().join(", ")),
+        Value::Mapping(mapping) => format!(
+            "{{{}}}",
+            mapping
+                .iter()
+                .map(|(key, value)| format!("{}: {}", inline(key), inline(value)))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_read_back_as_the_same_strings() {
+        let texts = ["Music", "yes", "no", "true", "1.0", "320", "~", "null", "a: b", "#tag", "", " padded", "[Music]", "it's"];
+        for text in texts {
+            let written = inline(&Value::String(text.to_string()));
+            assert_eq!(serde_yaml::from_str::<String>(&written).ok().as_deref(), Some(text), "{} as {}", text, written);
+        }
+        // also inside a flow sequence or mapping, where commas and colons end the value
+        let list = Value::Sequence(texts.iter().map(|text| Value::String(text.to_string())).collect());
+        assert_eq!(serde_yaml::from_str::<Value>(&inline(&list)).unwrap(), list);
+    }
+
+    #[test]
+    fn yaml_options_keep_numbers_and_bools_unquoted() {
+        let config: Value = serde_yaml::from_str(
+            "server: http://localhost:8096\nstreaming:\n  max_bitrate: 320\ncache:\n  cover_art: false\nlibraries: [Music, '1.0']\n",
+        ).unwrap();
+        let settings = Settings::new(&config);
+        let shown = |key: &str| {
+            let index = OPTIONS.iter().position(|(option, _, _)| *option == key).unwrap();
+            settings.values[index].clone()
+        };
+        assert_eq!(shown("server"), "http://localhost:8096");
+        assert_eq!(shown("streaming"), "{max_bitrate: 320}");
+        assert_eq!(shown("cache"), "{cover_art: false}");
+        assert_eq!(shown("libraries"), "[Music, \"1.0\"]");
+        for key in ["streaming", "cache", "libraries"] {
+            assert_eq!(serde_yaml::from_str::<Value>(&shown(key)).ok().as_ref(), config.get(key));
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! SORTING
+//!
+//! How the artist and track lists are ordered. Picked from the sort menu (`o`) and sent to the
+//! server as SortBy/SortOrder, lists that are not fetched in one query (playlists, search
+//! results, ...) are sorted locally instead. Kept in the `sort` section of config.yaml:
+//!
+//! sort:
+//!   artists: name
+//!   tracks: album # name | date_added | year | play_count | random | album
+//!   album_artists: false
+//!
+//! With `album_artists` only album artists are listed and an artist's tracks are the ones on
+//! their own albums. Otherwise every track artist is listed, with every track they appear on.
+use crate::config;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortBy {
+    Name,
+    DateAdded,
+    Year,
+    PlayCount,
+    Random,
+    /// album, then disc, then track number
+    Album,
+}
+
+impl SortBy {
+    /// In the order of the sort menu
+    pub const ALL: [SortBy; 6] = [
+        SortBy::Album,
+        SortBy::Name,
+        SortBy::DateAdded,
+        SortBy::Year,
+        SortBy::PlayCount,
+        SortBy::Random,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SortBy::Name => "Name",
+            SortBy::DateAdded => "Date added",
+            SortBy::Year => "Year",
+            SortBy::PlayCount => "Play count",
+            SortBy::Random => "Random",
+            SortBy::Album => "Album, disc, track",
+        }
+    }
+
+    /// SortBy and SortOrder for /Artists. Artists have no album, so that means by name
+    pub fn artist_query(self) -> (&'static str, &'static str) {
+        match self {
+            SortBy::Name | SortBy::Album => ("SortName", "Ascending"),
+            SortBy::DateAdded => ("DateCreated,SortName", "Descending"),
+            SortBy::Year => ("ProductionYear,SortName", "Ascending"),
+            SortBy::PlayCount => ("PlayCount,SortName", "Descending"),
+            SortBy::Random => ("Random", "Ascending"),
+        }
+    }
+
+    /// SortBy and SortOrder for tracks
+    pub fn track_query(self) -> (&'static str, &'static str) {
+        match self {
+            SortBy::Name => ("SortName", "Ascending"),
+            SortBy::DateAdded => ("DateCreated,SortName", "Descending"),
+            SortBy::Year => ("ProductionYear,Album,ParentIndexNumber,IndexNumber", "Ascending"),
+            SortBy::PlayCount => ("PlayCount,SortName", "Descending"),
+            SortBy::Random => ("Random", "Ascending"),
+            SortBy::Album => ("Album,ParentIndexNumber,IndexNumber", "Ascending"),
+        }
+    }
+
+    /// The columns::sort key doing the same locally. None for random, that's a shuffle
+    pub fn local_key(self) -> Option<&'static str> {
+        match self {
+            SortBy::Name => Some("title"),
+            SortBy::DateAdded => Some("date_added"),
+            SortBy::Year => Some("year"),
+            SortBy::PlayCount => Some("play_count"),
+            SortBy::Random => None,
+            SortBy::Album => Some("album"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sorting {
+    pub artists: SortBy,
+    pub tracks: SortBy,
+    pub album_artists: bool,
+}
+
+impl Default for Sorting {
+    fn default() -> Self {
+        Sorting {
+            artists: SortBy::Name,
+            tracks: SortBy::Album,
+            album_artists: false,
+        }
+    }
+}
+
+impl Sorting {
+    pub fn from_config(config: &Value) -> Result<Self, String> {
+        match config.get("sort") {
+            Some(Value::Null) | None => Ok(Sorting::default()),
+            Some(sort) => serde_yaml::from_value(sort.clone()).map_err(|e| e.to_string()),
+        }
+    }
+
+    /// Writes the sorting back to config.yaml
+    pub fn save(&self) -> Result<(), String> {
+        let value = serde_yaml::to_value(self).map_err(|e| e.to_string())?;
+        config::save_section("sort", value)
+    }
+}
@@ -0,0 +1,338 @@
+//! THEMES
+//!
+//! Every colour the TUI draws with comes from a Theme. The built-in themes are dark (the default),
+//! light and high-contrast. A theme file at ~/.config/jellyfin-tui/theme.yaml picks one of them
+//! as its base and overrides individual styles, e.g.
+//!
+//! base: light
+//! border_type: rounded
+//! border_active: { fg: magenta }
+//! selected: { fg: black, bg: "#ffaf00", modifiers: [bold] }
+//!
+//! The file is watched and re-applied while the app is running. Without a base, the theme
+//! named by `theme:` in config.yaml is used, and dark when neither says.
+use crate::config::{app_config_dir, Config};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::symbols::border;
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub border_type: border::Set,
+    pub controls_border_type: border::Set,
+    pub border: Style,
+    pub border_active: Style,
+    pub selected: Style,
+    pub selected_inactive: Style,
+    pub playing: Style,
+    pub duration: Style,
+    pub gauge: Style,
+    pub lyrics: Style,
+    pub lyrics_highlight: Style,
+    pub status_bar: Style,
+    pub hint_key: Style,
+    pub hint_text: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    /// The original jellyfin-tui look
+    pub fn dark() -> Self {
+        Theme {
+            border_type: border::PLAIN,
+            controls_border_type: border::THICK,
+            border: Style::default().fg(Color::White),
+            border_active: Style::default().fg(Color::Blue),
+            selected: Style::default()
+                .add_modifier(Modifier::BOLD)
+                .add_modifier(Modifier::REVERSED),
+            selected_inactive: Style::default()
+                .bg(Color::DarkGray)
+                .fg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            playing: Style::default().fg(Color::Blue),
+            duration: Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+            gauge: Style::default()
+                .fg(Color::White)
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD),
+            lyrics: Style::default().fg(Color::Gray),
+            lyrics_highlight: Style::default().fg(Color::White).add_modifier(Modifier::BOLD),
+            status_bar: Style::default(),
+            hint_key: Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            hint_text: Style::default().fg(Color::White),
+        }
+    }
+
+    /// For terminals with a light background
+    pub fn light() -> Self {
+        Theme {
+            border_type: border::PLAIN,
+            controls_border_type: border::THICK,
+            border: Style::default().fg(Color::DarkGray),
+            border_active: Style::default().fg(Color::Blue),
+            selected: Style::default()
+                .fg(Color::White)
+                .bg(Color::Blue)
+                .add_modifier(Modifier::BOLD),
+            selected_inactive: Style::default()
+                .fg(Color::Black)
+                .bg(Color::Gray)
+                .add_modifier(Modifier::BOLD),
+            playing: Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            duration: Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
+            gauge: Style::default()
+                .fg(Color::Blue)
+                .bg(Color::Gray)
+                .add_modifier(Modifier::BOLD),
+            lyrics: Style::default().fg(Color::DarkGray),
+            lyrics_highlight: Style::default().fg(Color::Black).add_modifier(Modifier::BOLD),
+            status_bar: Style::default().fg(Color::Black),
+            hint_key: Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            hint_text: Style::default().fg(Color::Black),
+        }
+    }
+
+    /// No greys, no subtle hints
+    pub fn high_contrast() -> Self {
+        Theme {
+            border_type: border::DOUBLE,
+            controls_border_type: border::THICK,
+            border: Style::default().fg(Color::White),
+            border_active: Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            selected: Style::default()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            selected_inactive: Style::default()
+                .fg(Color::Black)
+                .bg(Color::White)
+                .add_modifier(Modifier::BOLD),
+            playing: Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+            duration: Style::default().fg(Color::White),
+            gauge: Style::default()
+                .fg(Color::Yellow)
+                .bg(Color::Black)
+                .add_modifier(Modifier::BOLD),
+            lyrics: Style::default().fg(Color::White),
+            lyrics_highlight: Style::default()
+                .fg(Color::Black)
+                .bg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+            status_bar: Style::default().fg(Color::White).add_modifier(Modifier::BOLD),
+            hint_key: Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            hint_text: Style::default().fg(Color::White),
+        }
+    }
+
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name.to_lowercase().replace(['_', ' '], "-").as_str() {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            "high-contrast" | "highcontrast" => Some(Theme::high_contrast()),
+            _ => None,
+        }
+    }
+
+    /// Reads the theme file. A missing file is not an error, you just get the dark theme
+    ///
+    pub fn load() -> Result<Self, String> {
+        let path = match theme_file() {
+            Some(path) => path,
+            None => return Ok(configured()),
+        };
+        if !path.exists() {
+            return Ok(configured());
+        }
+        let f = std::fs::File::open(&path).map_err(|e| e.to_string())?;
+        let file: ThemeFile = serde_yaml::from_reader(f).map_err(|e| e.to_string())?;
+        file.into_theme()
+    }
+}
+
+/// The built-in theme named by `theme` in config.yaml, dark when it isn't set
+fn configured() -> Theme {
+    let (config, _) = Config::read();
+    config.theme.as_deref().and_then(Theme::builtin).unwrap_or_default()
+}
+
+/// Location of the theme file, next to config.yaml
+pub fn theme_file() -> Option<PathBuf> {
+    app_config_dir().map(|dir| dir.join("theme.yaml"))
+}
+
+/// On-disk representation. Every field is optional and falls back to the base theme
+#[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ThemeFile {
+    #[serde(default)]
+    base: Option<String>,
+    #[serde(default)]
+    border_type: Option<String>,
+    #[serde(default)]
+    controls_border_type: Option<String>,
+    #[serde(default)]
+    border: Option<StyleSpec>,
+    #[serde(default)]
+    border_active: Option<StyleSpec>,
+    #[serde(default)]
+    selected: Option<StyleSpec>,
+    #[serde(default)]
+    selected_inactive: Option<StyleSpec>,
+    #[serde(default)]
+    playing: Option<StyleSpec>,
+    #[serde(default)]
+    duration: Option<StyleSpec>,
+    #[serde(default)]
+    gauge: Option<StyleSpec>,
+    #[serde(default)]
+    lyrics: Option<StyleSpec>,
+    #[serde(default)]
+    lyrics_highlight: Option<StyleSpec>,
+    #[serde(default)]
+    status_bar: Option<StyleSpec>,
+    #[serde(default)]
+    hint_key: Option<StyleSpec>,
+    #[serde(default)]
+    hint_text: Option<StyleSpec>,
+}
+
+/// { fg: blue, bg: "#202020", modifiers: [bold, italic] }
+#[derive(Debug, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct StyleSpec {
+    #[serde(default)]
+    fg: Option<String>,
+    #[serde(default)]
+    bg: Option<String>,
+    #[serde(default)]
+    modifiers: Vec<String>,
+}
+
+impl StyleSpec {
+    fn to_style(&self) -> Result<Style, String> {
+        let mut style = Style::default();
+        if let Some(fg) = &self.fg {
+            style = style.fg(parse_color(fg)?);
+        }
+        if let Some(bg) = &self.bg {
+            style = style.bg(parse_color(bg)?);
+        }
+        for m in &self.modifiers {
+            style = style.add_modifier(parse_modifier(m)?);
+        }
+        Ok(style)
+    }
+}
+
+impl ThemeFile {
+    fn into_theme(self) -> Result<Theme, String> {
+        let mut theme = match self.base {
+            Some(ref base) => {
+                Theme::builtin(base).ok_or(format!("unknown base theme '{}'", base))?
+            }
+            None => configured(),
+        };
+        if let Some(b) = self.border_type {
+            theme.border_type = parse_border(&b)?;
+        }
+        if let Some(b) = self.controls_border_type {
+            theme.controls_border_type = parse_border(&b)?;
+        }
+        let overrides = [
+            (self.border, &mut theme.border),
+            (self.border_active, &mut theme.border_active),
+            (self.selected, &mut theme.selected),
+            (self.selected_inactive, &mut theme.selected_inactive),
+            (self.playing, &mut theme.playing),
+            (self.duration, &mut theme.duration),
+            (self.gauge, &mut theme.gauge),
+            (self.lyrics, &mut theme.lyrics),
+            (self.lyrics_highlight, &mut theme.lyrics_highlight),
+            (self.status_bar, &mut theme.status_bar),
+            (self.hint_key, &mut theme.hint_key),
+            (self.hint_text, &mut theme.hint_text),
+        ];
+        for (spec, style) in overrides {
+            if let Some(spec) = spec {
+                *style = spec.to_style()?;
+            }
+        }
+        Ok(theme)
+    }
+}
+
+fn parse_color(s: &str) -> Result<Color, String> {
+    Color::from_str(s).map_err(|_| format!("invalid colour '{}'", s))
+}
+
+fn parse_modifier(s: &str) -> Result<Modifier, String> {
+    match s.to_lowercase().as_str() {
+        "bold" => Ok(Modifier::BOLD),
+        "dim" => Ok(Modifier::DIM),
+        "italic" => Ok(Modifier::ITALIC),
+        "underlined" | "underline" => Ok(Modifier::UNDERLINED),
+        "slow_blink" | "blink" => Ok(Modifier::SLOW_BLINK),
+        "rapid_blink" => Ok(Modifier::RAPID_BLINK),
+        "reversed" | "reverse" => Ok(Modifier::REVERSED),
+        "hidden" => Ok(Modifier::HIDDEN),
+        "crossed_out" | "strikethrough" => Ok(Modifier::CROSSED_OUT),
+        _ => Err(format!("invalid modifier '{}'", s)),
+    }
+}
+
+fn parse_border(s: &str) -> Result<border::Set, String> {
+    match s.to_lowercase().as_str() {
+        "plain" => Ok(border::PLAIN),
+        "rounded" => Ok(border::ROUNDED),
+        "double" => Ok(border::DOUBLE),
+        "thick" => Ok(border::THICK),
+        _ => Err(format!("invalid border type '{}'", s)),
+    }
+}
+
+/// Polls the theme file's modification time so edits show up without a restart
+pub struct ThemeWatcher {
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl ThemeWatcher {
+    pub fn new() -> Self {
+        ThemeWatcher {
+            modified: Self::mtime(),
+            last_check: Instant::now(),
+        }
+    }
+
+    fn mtime() -> Option<SystemTime> {
+        let path = theme_file()?;
+        std::fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Returns a freshly loaded theme if the file changed since the last call.
+    /// Checks at most once a second, a broken file keeps the current theme
+    pub fn poll(&mut self) -> Option<Theme> {
+        if self.last_check.elapsed() < Duration::from_secs(1) {
+            return None;
+        }
+        self.last_check = Instant::now();
+
+        let modified = Self::mtime();
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+        Theme::load().ok()
+    }
+}
@@ -1,10 +1,24 @@
-use crate::client::{self, Artist, Client, DiscographySong, ProgressReport, report_progress};
+use crate::client::{self, Artist, Client, DiscographySong, Genre, Item, Library, Lyric, Playlist, ProgressReport, Session, report_progress};
+use crate::columns::{self, Column};
+use crate::command::{self, CommandLine};
+use crate::config::{self, Cache, Config, Scrobbling, Streaming};
+use crate::home;
+use crate::keymap::{Action, Key, KeyMap, Resolution};
+use crate::log;
+use crate::mpris;
+use crate::remote;
+use crate::settings::{Outcome, Settings};
+use crate::sort::{SortBy, Sorting};
+use crate::websocket;
+use crate::panes::PaneLayout;
+use crate::profile::{self, Profile};
+use crate::queue::{self, SavedQueue};
+use crate::theme::{Theme, ThemeWatcher};
 use layout::Flex;
 use libmpv::{*};
 
 use std::io::{self, Stdout};
 
-use ratatui::symbols::border;
 use ratatui::widgets::block::Title;
 use ratatui::widgets::Borders;
 use ratatui::widgets::{block::Position, Block, Paragraph};
@@ -13,20 +27,35 @@ use ratatui::{prelude::*, widgets::*};
 use ratatui::{Terminal, terminal::Frame};
 use ratatui_image::{picker::Picker, StatefulImage, protocol::StatefulProtocol, Resize};
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 /// A type alias for the terminal type used in this application
 pub type Tui = Terminal<CrosstermBackend<Stdout>>;
 
+/// How many tracks playing a genre shuffled queues up
+const GENRE_QUEUE_LENGTH: u64 = 200;
+/// Tracks fetched per Instant Mix, and how close to the end of the queue the next batch is fetched
+const RADIO_BATCH: u64 = 50;
+const RADIO_REFILL_AT: usize = 5;
+/// Tracks from the selected one on that are handed to another session, which gets them all in
+/// the URL of one request
+const SESSION_PLAY_LENGTH: usize = 100;
+
 use std::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, Mutex};
 
+use std::collections::HashSet;
 use std::thread;
 
-use crossterm::event::{self, Event, KeyEvent};
+use rand::seq::SliceRandom;
+
+use crossterm::event::{self, Event, KeyEvent, KeyEventKind, MouseButton, MouseEvent, MouseEventKind};
 use crossterm::event::KeyCode;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ActiveSection {
     Artists,
     Tracks,
@@ -38,41 +67,180 @@ impl Default for ActiveSection {
     }
 }
 
+/// What the left pane lists
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LeftView {
+    #[default]
+    Home,
+    Artists,
+    Sessions,
+    Playlists,
+    Favorites,
+    Genres,
+    Libraries,
+    Profiles,
+}
+
+/// What happens when the end of a song or of the queue is reached
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Repeat {
+    #[default]
+    None,
+    Queue,
+    Track,
+}
+
+impl Repeat {
+    pub fn next(self) -> Self {
+        match self {
+            Repeat::None => Repeat::Queue,
+            Repeat::Queue => Repeat::Track,
+            Repeat::Track => Repeat::None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Repeat::None => "off",
+            Repeat::Queue => "queue",
+            Repeat::Track => "track",
+        }
+    }
+}
+
+/// Where the panes were drawn in the last frame, for mouse hit-testing
+#[derive(Default)]
+struct PaneAreas {
+    artists: Rect,
+    tracks: Rect,
+    queue: Rect,
+    progress: Rect,
+}
+
 pub struct MpvPlaybackState {
     pub percentage: f64,
     pub duration: f64,
     pub current_index: i64,
+    pub paused: bool,
 }
 
 /// Internal song representation. Used in the queue and passed to MPV
-#[derive(Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Song {
     pub id: String,
+    #[serde(skip)] // contains the access token
     pub url: String,
     pub name: String,
     pub artist: String,
     pub album: String,
     pub parent_id: String,
     pub production_year: u64,
+    #[serde(default)]
+    pub genres: Vec<String>,
+}
+
+impl Song {
+    pub fn from_track(client: &Client, track: &DiscographySong) -> Self {
+        Song {
+            id: track.id.clone(),
+            url: client.song_url_sync(track.id.clone()),
+            name: track.name.clone(),
+            artist: track.album_artist.clone(),
+            album: track.album.clone(),
+            parent_id: track.parent_id.clone(),
+            production_year: track.production_year,
+            genres: track.genres.clone(),
+        }
+    }
 }
 
 pub struct App {
     pub exit: bool,
 
     artists: Vec<Artist>, // all artists
+    left_view: LeftView,
+    sessions: Vec<Session>, // other sessions we can control
+    sessions_fetched: Instant,
+    sessions_refresh: Instant, // when to fetch the sessions again
+    // fetched in the background, tagged with the server they came from
+    sessions_sender: Sender<(String, std::result::Result<Vec<Session>, String>)>,
+    sessions_receiver: Receiver<(String, std::result::Result<Vec<Session>, String>)>,
+    sessions_pending: bool,
+    // playback actions go to this session instead of the local player
+    target_session: Option<String>,
+    playlists: Vec<Playlist>,
+    open_playlist: Option<Playlist>, // the playlist shown in the tracks pane
+    playlist_in_order: bool, // the open playlist is shown in its saved order, not sorted here
+    pending_delete: Option<(String, Instant)>, // playlists are deleted on the second press
+    favorites: Vec<Item>,
+    home: Vec<(home::Section, Item)>,
+    genres: Vec<Genre>,
+    libraries: Vec<Library>, // the user's music libraries, the client knows which are browsed
+    profiles: Vec<Profile>, // the servers in config.yaml, see profile.rs
+    open_genre: Option<Genre>, // the genre whose artists and albums the left pane lists
+    genre_items: Vec<Item>,
     tracks: Vec<DiscographySong>, // current artist's tracks
-    lyrics: (String, Vec<String>),
+    lyrics: (String, Vec<Lyric>),
     metadata: Option<client::MediaStream>,
     playlist: Vec<Song>, // (URL, Title, Artist, Album)
     active_song_id: String,
     cover_art: Option<Box<dyn StatefulProtocol>>,
     picker: Option<Picker>,
     paused: bool,
+    theme: Theme,
+    theme_watcher: ThemeWatcher,
+    keymap: KeyMap,
+    columns: Vec<Column>, // extra columns of the track list
+    sorting: Sorting,
+    streaming: Streaming,
+    cache: Cache,
+    scrobbling: Scrobbling,
+    sort_menu: Option<(ActiveSection, ListState)>, // the list being sorted and the menu selection
+    open_artist: Option<String>, // the artist whose tracks are shown, they are re-fetched when sorting
+    pending_keys: Vec<Key>, // keys typed so far of a multi-key binding
+    pending_since: Instant,
+    show_help: bool,
+    command_line: CommandLine,
+    settings: Option<Settings>, // the settings form, drawn over everything while open
+    status_message: Option<(String, Instant)>, // feedback from commands, shown for a few seconds
+    volume: i64,
+    muted: bool,
+    shuffle: bool,
+    repeat: Repeat,
+    unshuffled: Vec<Song>, // queue order before shuffling, restored when shuffle is turned off
+    queue_saved: Instant,
+    radio: bool, // the queue is an Instant Mix that grows as it plays
+    radio_refill: Instant, // when to try refilling again after a failed attempt
+    panes: PaneLayout,
+    screen_width: u16,
+    areas: PaneAreas,
+    mpris: Option<UnboundedSender<mpris::Status>>,
+    mpris_status: mpris::Status, // last status sent, so only changes go over the channel
+    remote_status: watch::Sender<remote::Status>, // answers `status` on the control socket
+    socket: Option<remote::Socket>,
+    websocket: Option<tokio::task::JoinHandle<()>>, // restarted when switching profiles
+    // when attached to a daemon, playback happens there and this is only a view of it
+    daemon: Option<remote::Daemon>,
+    daemon_receiver: Option<Receiver<std::result::Result<remote::Status, String>>>,
+    // actions requested from outside the TUI, e.g. over MPRIS
+    action_sender: Sender<Action>,
+    action_receiver: Receiver<Action>,
+    last_click: Option<(Instant, ActiveSection, usize)>, // for detecting double clicks
     active_section: ActiveSection, // current active section (Artists, Tracks, Queue)
     last_section: ActiveSection, // last active section
     
     // ratatui list indexes
     selected_artist: ListState,
+    selected_session: ListState,
+    selected_playlist: ListState,
+    selected_favorite: ListState,
+    selected_home: ListState,
+    selected_genre: ListState,
+    selected_library: ListState,
+    selected_profile: ListState,
+    selected_genre_item: ListState, // row 0 is "All tracks", the items follow
     selected_track: ListState,
     selected_queue_item: ListState,
     
@@ -104,10 +272,31 @@ impl Default for App {
         picker.guess_protocol();
 
         let (sender, receiver) = channel();
+        let (action_sender, action_receiver) = channel();
+        let (sessions_sender, sessions_receiver) = channel();
 
         App {
             exit: false,
             artists: vec![],
+            left_view: LeftView::Home,
+            sessions: vec![],
+            sessions_fetched: Instant::now(),
+            sessions_refresh: Instant::now(),
+            sessions_sender,
+            sessions_receiver,
+            sessions_pending: false,
+            target_session: None,
+            playlists: vec![],
+            open_playlist: None,
+            playlist_in_order: false,
+            pending_delete: None,
+            favorites: vec![],
+            home: vec![],
+            genres: vec![],
+            libraries: vec![],
+            profiles: vec![],
+            open_genre: None,
+            genre_items: vec![],
             tracks: vec![],
             lyrics: (String::from(""), vec![]),
             metadata: None,
@@ -116,20 +305,66 @@ impl Default for App {
             cover_art: None,
             picker: Some(picker),
             paused: true,
+            theme: Theme::default(),
+            theme_watcher: ThemeWatcher::new(),
+            keymap: KeyMap::default(),
+            columns: vec![Column::Favorite],
+            sorting: Sorting::default(),
+            streaming: Streaming::default(),
+            cache: Cache::default(),
+            scrobbling: Scrobbling::default(),
+            sort_menu: None,
+            open_artist: None,
+            pending_keys: vec![],
+            pending_since: Instant::now(),
+            show_help: false,
+            command_line: CommandLine::new(),
+            settings: None,
+            status_message: None,
+            volume: 100,
+            muted: false,
+            shuffle: false,
+            repeat: Repeat::None,
+            unshuffled: vec![],
+            queue_saved: Instant::now(),
+            radio: false,
+            radio_refill: Instant::now(),
+            panes: PaneLayout::default(),
+            screen_width: 0,
+            areas: PaneAreas::default(),
+            mpris: None,
+            mpris_status: mpris::Status::default(),
+            remote_status: watch::channel(remote::Status::default()).0,
+            socket: None,
+            websocket: None,
+            daemon: None,
+            daemon_receiver: None,
+            action_sender,
+            action_receiver,
+            last_click: None,
             active_section: ActiveSection::Artists,
             last_section: ActiveSection::Artists,
             selected_artist: ListState::default(),
+            selected_session: ListState::default(),
+            selected_playlist: ListState::default(),
+            selected_favorite: ListState::default(),
+            selected_home: ListState::default(),
+            selected_genre: ListState::default(),
+            selected_library: ListState::default(),
+            selected_profile: ListState::default(),
+            selected_genre_item: ListState::default(),
             selected_track: ListState::default(),
             selected_queue_item: ListState::default(),
             client: None,
             mpv_thread: None,
-            mpv_state: Arc::new(Mutex::new(MpvState::new())),
+            mpv_state: Arc::new(Mutex::new(MpvState::new(100))),
             sender,
             receiver,
             current_playback_state: MpvPlaybackState {
                 percentage: 0.0,
                 duration: 0.0,
                 current_index: 0,
+                paused: false,
             },
             old_percentage: 0.0,
             scrobble_this: (String::from(""), 0),
@@ -143,10 +378,10 @@ struct MpvState {
 }
 
 impl MpvState {
-    fn new() -> Self {
+    fn new(volume: i64) -> Self {
         let mpv = Mpv::new().unwrap();
         mpv.set_property("vo", "null").unwrap();
-        mpv.set_property("volume", 100).unwrap();
+        mpv.set_property("volume", volume).unwrap();
         mpv.set_property("prefetch-playlist", "yes").unwrap(); // gapless playback
 
         let ev_ctx = mpv.create_event_context();
@@ -160,55 +395,106 @@ impl MpvState {
             should_stop: false,
         }
     }
+
+    fn set_repeat(&self, repeat: Repeat) {
+        let (file, playlist) = match repeat {
+            Repeat::None => ("no", "no"),
+            Repeat::Queue => ("no", "inf"),
+            Repeat::Track => ("inf", "no"),
+        };
+        let _ = self.mpv.set_property("loop-file", file);
+        let _ = self.mpv.set_property("loop-playlist", playlist);
+    }
 }
 
 impl App {
-    pub async fn init(&mut self, artists: Vec<Artist>) {
-        let client = client::Client::new().await;
-        if client.access_token.is_empty() {
-            println!("Failed to authenticate. Exiting...");
-            return;
-        }
+    pub async fn init(&mut self, mut client: Client, theme: Theme, config: Config) {
+        client.max_bitrate = config.streaming.max_bitrate;
+        self.artists = client.artists(&config.sort).await.unwrap_or_default();
         self.client = Some(client);
-        self.artists = artists;
+        self.theme = theme;
+        self.keymap = config.keys;
+        self.panes = config.layout;
+        self.columns = config.columns;
+        self.sorting = config.sort;
+        self.streaming = config.streaming;
+        self.cache = config.cache;
+        self.scrobbling = config.scrobbling;
         self.active_section = ActiveSection::Artists;
         self.selected_artist.select(Some(0));
 
-        // let player = Player::builder("com.tui.jellyfin")
-        //     .can_play(true)
-        //     .can_pause(true)
-        //     .build()
-        //     .await;
-
-        // match player {
-        //     Ok(player) => {
-        //         println!("MPRIS server started");
-        //         player.connect_play_pause(|_player| {
-        //             println!("PlayPause");
-        //         });
-        //         player.set_metadata(
-        //             Metadata::builder()
-        //                 .title("Title")
-        //                 .artist(["Artist"])
-        //                 .album("Album")
-        //                 .build(),
-        //         ).await;
-
-        //         player.run().await;
-        //     }
-        //     Err(e) => {
-        //         println!("Failed to start MPRIS server: {:?}", e);
-        //     }
-        // }
+    }
+
+    /// Shows the view the left pane starts with, see home.rs
+    pub async fn start_in(&mut self, view: LeftView) {
+        self.left_view = view;
+        match view {
+            LeftView::Home => self.refresh_home().await,
+            LeftView::Sessions => self.refresh_sessions(),
+            LeftView::Playlists => self.refresh_playlists().await,
+            LeftView::Favorites => self.refresh_favorites().await,
+            LeftView::Genres => self.refresh_genres().await,
+            LeftView::Libraries => self.refresh_libraries().await,
+            LeftView::Profiles => self.refresh_profiles(),
+            LeftView::Artists => {}
+        }
+    }
+
+    /// Makes this instance the player: starts MPRIS, the server WebSocket and the control socket
+    pub fn serve(&mut self) -> std::result::Result<(), String> {
+        self.mpris = Some(mpris::spawn(self.action_sender.clone()));
+        if let Some(ref client) = self.client {
+            let websocket = websocket::spawn(client, self.action_sender.clone());
+            self.websocket = Some(websocket);
+        }
+        let socket = remote::listen(self.action_sender.clone(), self.remote_status.subscribe())?;
+        self.socket = Some(socket);
+        Ok(())
+    }
+
+    /// Makes this instance a remote for a daemon. Playback actions are forwarded to it and the
+    /// player state shown is the daemon's
+    pub fn attach(&mut self, daemon: remote::Daemon) {
+        let (sender, receiver) = channel();
+        remote::Daemon::watch(sender);
+        self.daemon = Some(daemon);
+        self.daemon_receiver = Some(receiver);
+        self.set_message(format!("Attached to {}", remote::socket_path().display()));
     }
 
     pub async fn run(&mut self, terminal: &mut Tui) {
+        self.update().await;
+
+        // let the rats take over
+        terminal
+            .draw(|frame| {
+                self.render_frame(frame);
+            })
+            .unwrap();
+
+        self.handle_events().await.unwrap();
+
+        // ratatui is an immediate mode tui which is cute, but it will be heavy on the cpu
+        // later maybe make a thread that sends refresh signals
+        // ok for now, but will cause some user input jank
+        let fps = 60;
+        thread::sleep(Duration::from_millis(1000 / fps));
+    }
+
+    /// Everything but drawing and input: follows the player, reports playback to the server and
+    /// handles remote actions. This is all a daemon does
+    pub async fn update(&mut self) {
+        if self.daemon_receiver.is_some() {
+            self.follow_daemon();
+        }
+
         // get playback state from the mpv thread
         match self.receiver.try_recv() {
             Ok(state) => {
                 self.current_playback_state.percentage = state.percentage;
                 self.current_playback_state.current_index = state.current_index;
                 self.current_playback_state.duration = state.duration;
+                self.paused = state.paused;
 
                 // Queue position
                 self.selected_queue_item
@@ -224,6 +510,7 @@ impl App {
                         album: String::from(""),
                         parent_id: String::from(""),
                         production_year: 0,
+                        genres: vec![],
                     },
                 };
                 let song_id = song.id.clone();
@@ -236,19 +523,23 @@ impl App {
 
                     let client = self.client.as_ref().unwrap();
                     
-                    let runit = report_progress(
-                        client.base_url.clone(), client.access_token.clone(), ProgressReport {
-                        volume_level: 100,
-                        is_paused: self.paused,
-                        // take into account duratio, percentage and *10000
-                        position_ticks: (self.current_playback_state.duration * self.current_playback_state.percentage * 100000.0) as u64,
-                        media_source_id: self.active_song_id.clone(),
-                        playback_start_time_ticks: 0,
-                        can_seek: false, // TODO
-                        item_id: self.active_song_id.clone(),
-                        event_name: "timeupdate".to_string(),
-                    });
-                    tokio::spawn(runit);
+                    // the daemon reports its own playback
+                    if self.daemon.is_none() && self.scrobbling.enabled {
+                        let runit = report_progress(
+                            client.base_url.clone(), client.access_token.clone(), client.device_id.clone(), ProgressReport {
+                            volume_level: self.volume as u64,
+                            is_muted: self.muted,
+                            is_paused: self.paused,
+                            // take into account duratio, percentage and *10000
+                            position_ticks: (self.current_playback_state.duration * self.current_playback_state.percentage * 100000.0) as u64,
+                            media_source_id: self.active_song_id.clone(),
+                            playback_start_time_ticks: 0,
+                            can_seek: false, // TODO
+                            item_id: self.active_song_id.clone(),
+                            event_name: "timeupdate".to_string(),
+                        });
+                        tokio::spawn(runit);
+                    }
 
                 } else if self.old_percentage > self.current_playback_state.percentage {
                     self.old_percentage = self.current_playback_state.percentage;
@@ -266,15 +557,18 @@ impl App {
                                     None
                                 }
                             };
-                            let cover_image = match client.download_cover_art(song.parent_id).await {
-                                Ok(cover_image) => {
-                                    if cover_image != "" {
-                                        Some(cover_image)
-                                    } else {
-                                        None
+                            let cover_image = match self.cache.cover_art {
+                                false => None,
+                                true => match client.download_cover_art(song.parent_id).await {
+                                    Ok(cover_image) => {
+                                        if !cover_image.is_empty() {
+                                            Some(cover_image)
+                                        } else {
+                                            None
+                                        }
                                     }
-                                }
-                                _ => None,
+                                    _ => None,
+                                },
                             };
                             // force log the song, then panic
                             match lyrics {
@@ -295,8 +589,7 @@ impl App {
                             }
                             match cover_image {
                                 Some(cover_image) => {
-                                    let p = format!("./covers/{}", cover_image);
-                                    let _ = match image::io::Reader::open(p) {
+                                    let _ = match image::io::Reader::open(cover_image) {
                                         Ok(reader) => {
                                             match reader.decode() {
                                                 Ok(img) => {
@@ -325,15 +618,25 @@ impl App {
                                 }
                             }
 
-                            if self.scrobble_this.0 != "" {
-                                let _ = client.stopped(
-                                    self.scrobble_this.0.clone(),
-                                    self.scrobble_this.1,
-                                ).await;
-                                self.scrobble_this = (String::from(""), 0);
-                            }
+                            if self.daemon.is_none() && self.scrobbling.enabled {
+                                if !self.scrobble_this.0.is_empty() {
+                                    let _ = client.stopped(
+                                        self.scrobble_this.0.clone(),
+                                        self.scrobble_this.1,
+                                    ).await;
+                                    // play count and last played changed, keep the columns current
+                                    if let Ok(updated) = client.items(std::slice::from_ref(&self.scrobble_this.0)).await {
+                                        for updated in updated.items {
+                                            if let Some(track) = self.tracks.iter_mut().find(|t| t.id == updated.id) {
+                                                track.user_data = updated.user_data;
+                                            }
+                                        }
+                                    }
+                                    self.scrobble_this = (String::from(""), 0);
+                                }
 
-                            let _ = client.playing(self.active_song_id.clone()).await;
+                                let _ = client.playing(self.active_song_id.clone()).await;
+                            }
                         }
                         None => {}
                     }
@@ -342,20 +645,115 @@ impl App {
             Err(_) => {}
         }
 
-        // let the rats take over
-        terminal
-            .draw(|frame| {
-                self.render_frame(frame);
-            })
-            .unwrap();
+        // media keys and the like. They are meant for this player, not a session it controls
+        while let Ok(action) = self.action_receiver.try_recv() {
+            self.perform(action).await;
+        }
+        self.update_mpris();
+        self.update_remote_status();
 
-        self.handle_events().await.unwrap();
+        if self.radio && self.daemon.is_none() {
+            self.refill_radio().await;
+        }
 
-        // ratatui is an immediate mode tui which is cute, but it will be heavy on the cpu
-        // later maybe make a thread that sends refresh signals
-        // ok for now, but will cause some user input jank
-        let fps = 60;
-        thread::sleep(Duration::from_millis(1000 / fps));
+        // the daemon keeps its own queue
+        if self.daemon.is_none() && self.queue_saved.elapsed() > queue::SAVE_INTERVAL {
+            self.save_queue(String::new());
+        }
+
+        self.receive_sessions();
+        if (self.left_view == LeftView::Sessions || self.target_session.is_some())
+            && Instant::now() >= self.sessions_refresh
+        {
+            self.refresh_sessions();
+        }
+
+        // pick up edits to theme.yaml
+        if let Some(theme) = self.theme_watcher.poll() {
+            self.theme = theme;
+        }
+    }
+
+    /// Takes over the daemon's player state. It's fed through the same channel the mpv thread
+    /// uses, so song changes are picked up the same way
+    fn follow_daemon(&mut self) {
+        let receiver = match self.daemon_receiver {
+            Some(ref receiver) => receiver,
+            None => return,
+        };
+        let mut latest = None;
+        while let Ok(result) = receiver.try_recv() {
+            latest = Some(result);
+        }
+        match latest {
+            Some(Ok(status)) => {
+                self.playlist = status.queue;
+                self.volume = status.volume;
+                self.muted = status.muted;
+                self.shuffle = status.shuffle;
+                self.repeat = status.repeat;
+                let _ = self.sender.send(MpvPlaybackState {
+                    percentage: match status.duration {
+                        0.0 => 0.0,
+                        duration => status.position / duration * 100.0,
+                    },
+                    duration: status.duration,
+                    current_index: status.queue_position as i64,
+                    paused: status.paused,
+                });
+            }
+            Some(Err(e)) => {
+                self.set_message(format!("Lost the connection to the daemon: {}", e));
+                self.daemon = None;
+                self.daemon_receiver = None;
+            }
+            None => {}
+        }
+    }
+
+    /// The command line that does the same as an action on the daemon, for everything that
+    /// touches playback. None for actions that only concern this TUI
+    fn daemon_command(&self, action: &Action) -> Option<String> {
+        let command = match action {
+            Action::PlayPause => String::from("toggle"),
+            Action::SetPaused(true) => String::from("pause"),
+            Action::SetPaused(false) => String::from("play"),
+            Action::SeekForward => String::from("seek +5"),
+            Action::SeekBackward => String::from("seek -5"),
+            Action::Seek(seconds) => format!("seek {:+}", seconds),
+            Action::SeekTo(seconds) => format!("seek {}", seconds),
+            Action::SetVolume(volume) => format!("vol {}", volume),
+            Action::ChangeVolume(delta) => format!("vol {:+}", delta),
+            Action::Next => String::from("next"),
+            Action::Previous => String::from("prev"),
+            Action::Shuffle => String::from("shuffle"),
+            Action::SetShuffle(shuffle) if *shuffle == self.shuffle => return None,
+            Action::SetShuffle(_) => String::from("shuffle"),
+            Action::CycleRepeat => String::from("repeat"),
+            Action::ToggleMute => String::from("mute"),
+            Action::SetMuted(muted) if *muted == self.muted => return None,
+            Action::SetMuted(_) => String::from("mute"),
+            Action::SetRepeat(repeat) => format!("repeat {}", repeat.name()),
+            Action::ClearQueue => String::from("clear"),
+            Action::StartRadio(id) => format!("radio {}", id),
+            Action::EnqueueIds(ids) => format!("add {}", ids.join(" ")),
+            Action::PlayIds(ids) => format!("play {}", ids.join(" ")),
+            Action::PlayIndex(index) => format!("jump {}", index + 1),
+            Action::Enqueue if self.active_section == ActiveSection::Tracks => {
+                let selected = self.selected_track.selected().unwrap_or(0);
+                format!("add {}", self.tracks.get(selected)?.id)
+            }
+            Action::Select if self.active_section == ActiveSection::Tracks => {
+                let selected = self.selected_track.selected().unwrap_or(0);
+                let ids = self.tracks.iter().skip(selected).map(|track| track.id.as_str());
+                format!("play {}", ids.collect::<Vec<&str>>().join(" "))
+            }
+            Action::Select if self.active_section == ActiveSection::Queue => {
+                format!("jump {}", self.selected_queue_item.selected().unwrap_or(0) + 1)
+            }
+            _ => return None,
+        };
+        Some(command)
     }
 
     fn toggle_section(&mut self, forwards: bool) {
@@ -390,60 +788,72 @@ impl App {
     /// This is the main render function for rataui. It's called every frame.
     /// TODO: optimize this
     pub fn render_frame(&mut self, frame: &mut Frame) {
-        let outer_layout = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints(vec![
-                Constraint::Percentage(18),
-                Constraint::Percentage(58),
-                Constraint::Percentage(24),
-            ])
+        // the last line belongs to the command line
+        let screen = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![Constraint::Min(0), Constraint::Length(1)])
             .split(frame.size());
 
-        let left = outer_layout[0];
+        self.screen_width = screen[0].width;
+        let areas = self.panes.split(screen[0], self.active_section, !self.lyrics.1.is_empty());
+        self.areas = PaneAreas::default();
+
+        if let Some(area) = areas.artists {
+            match self.left_view {
+                LeftView::Home => self.render_home(frame, area),
+                LeftView::Artists => self.render_artists(frame, area),
+                LeftView::Sessions => self.render_sessions(frame, area),
+                LeftView::Playlists => self.render_playlists(frame, area),
+                LeftView::Favorites => self.render_favorites(frame, area),
+                LeftView::Genres => self.render_genres(frame, area),
+                LeftView::Libraries => self.render_libraries(frame, area),
+                LeftView::Profiles => self.render_profiles(frame, area),
+            }
+        }
+        if let Some(area) = areas.tracks {
+            self.render_tracks(frame, area);
+        }
+        self.render_player(frame, areas.player, areas.cover);
+        if let Some(area) = areas.lyrics {
+            self.render_lyrics(frame, area);
+        }
+        if let Some(area) = areas.queue {
+            self.render_queue(frame, area);
+        }
 
-        // create a wrapper, to get the width. After that create the inner 'left' and split it
-        let center = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints(vec![Constraint::Percentage(86), Constraint::Min(8)])
-            .split(outer_layout[1]);
+        self.render_command_line(frame, screen[1]);
 
-        let right = match self.lyrics.1.len() {
-            0 => Layout::default()
-                .direction(Direction::Vertical)
-                .constraints(vec![Constraint::Percentage(10), Constraint::Percentage(90)])
-                .split(outer_layout[2]),
-            _ => Layout::default()
-                .direction(Direction::Vertical)
-                .constraints(vec![Constraint::Percentage(68), Constraint::Percentage(32)])
-                .split(outer_layout[2]),
-        };
+        if self.show_help {
+            self.render_help(frame);
+        }
+        if self.sort_menu.is_some() {
+            self.render_sort_menu(frame);
+        }
+        if let Some(ref settings) = self.settings {
+            settings.render(frame, &self.theme);
+        }
+    }
 
+    fn render_artists(&mut self, frame: &mut Frame, area: Rect) {
         let artist_block = match self.active_section {
-            ActiveSection::Artists => Block::new()
-                .borders(Borders::ALL)
-                .border_style(style::Color::Blue),
-            _ => Block::new()
-                .borders(Borders::ALL)
-                .border_style(style::Color::White),
+            ActiveSection::Artists => self.pane_block(true),
+            _ => self.pane_block(false),
         };
 
         let artist_highlight_style = match self.active_section {
-            ActiveSection::Artists => Style::default()
-                .add_modifier(Modifier::BOLD)
-                .add_modifier(Modifier::REVERSED),
-            _ => Style::default()
-                .add_modifier(Modifier::BOLD)
-                .bg(Color::DarkGray)
-                .fg(Color::Black)
-                .add_modifier(Modifier::BOLD),
+            ActiveSection::Artists => self.theme.selected,
+            _ => self.theme.selected_inactive,
         };
 
-        // render all artists as a list here in left[0]
+        // render all artists as a list here in area[0]
         let items = self
             .artists
             .iter()
-            .map(|artist| artist.name.as_str())
-            .collect::<Vec<&str>>();
+            .map(|artist| match artist.user_data.is_favorite {
+                true => format!("{} ♥", artist.name),
+                false => artist.name.clone(),
+            })
+            .collect::<Vec<String>>();
 
         let list = List::new(items)
             .block(artist_block.title("Artist / Album"))
@@ -453,31 +863,266 @@ impl App {
             )
             .repeat_highlight_symbol(true);
 
-        frame.render_stateful_widget(list, left, &mut self.selected_artist);
+        frame.render_stateful_widget(list, area, &mut self.selected_artist);
+        self.areas.artists = area;
+    }
+
+    /// Other sessions in the left pane, with what they are playing
+    fn render_sessions(&mut self, frame: &mut Frame, area: Rect) {
+        let block = match self.active_section {
+            ActiveSection::Artists => self.pane_block(true),
+            _ => self.pane_block(false),
+        };
+        let highlight_style = match self.active_section {
+            ActiveSection::Artists => self.theme.selected,
+            _ => self.theme.selected_inactive,
+        };
+
+        let items = self
+            .sessions
+            .iter()
+            .map(|session| {
+                let playing = match session.now_playing_item {
+                    Some(ref item) => format!(
+                        "{} {} - {}",
+                        if session.play_state.is_paused { "⏸" } else { "▶" },
+                        item.name,
+                        item.album_artist
+                    ),
+                    None => String::from("Nothing playing"),
+                };
+                let item = ListItem::new(format!("{} ({}) | {}", session.device_name, session.client, playing));
+                match self.target_session.as_ref() == Some(&session.id) {
+                    true => item.style(self.theme.playing),
+                    false => item,
+                }
+            })
+            .collect::<Vec<ListItem>>();
+
+        let list = List::new(items)
+            .block(block.title("Sessions"))
+            .highlight_symbol(">>")
+            .highlight_style(highlight_style)
+            .repeat_highlight_symbol(true);
+
+        frame.render_stateful_widget(list, area, &mut self.selected_session);
+        self.areas.artists = area;
+    }
+
+    fn render_playlists(&mut self, frame: &mut Frame, area: Rect) {
+        let block = match self.active_section {
+            ActiveSection::Artists => self.pane_block(true),
+            _ => self.pane_block(false),
+        };
+        let highlight_style = match self.active_section {
+            ActiveSection::Artists => self.theme.selected,
+            _ => self.theme.selected_inactive,
+        };
+
+        let items = self
+            .playlists
+            .iter()
+            .map(|playlist| format!("{} ({})", playlist.name, playlist.child_count))
+            .collect::<Vec<String>>();
+
+        let list = List::new(items)
+            .block(block.title("Playlists"))
+            .highlight_symbol(">>")
+            .highlight_style(highlight_style)
+            .repeat_highlight_symbol(true);
+
+        frame.render_stateful_widget(list, area, &mut self.selected_playlist);
+        self.areas.artists = area;
+    }
+
+    /// Favorite artists, albums and songs
+    fn render_favorites(&mut self, frame: &mut Frame, area: Rect) {
+        let block = match self.active_section {
+            ActiveSection::Artists => self.pane_block(true),
+            _ => self.pane_block(false),
+        };
+        let highlight_style = match self.active_section {
+            ActiveSection::Artists => self.theme.selected,
+            _ => self.theme.selected_inactive,
+        };
+
+        let items = self
+            .favorites
+            .iter()
+            .map(|favorite| match favorite.type_.as_str() {
+                "MusicArtist" => format!("Artist · {}", favorite.name),
+                "MusicAlbum" => format!("Album · {} - {}", favorite.name, favorite.album_artist),
+                _ => format!("Song · {} - {}", favorite.name, favorite.album_artist),
+            })
+            .collect::<Vec<String>>();
+
+        let list = List::new(items)
+            .block(block.title("Favorites"))
+            .highlight_symbol(">>")
+            .highlight_style(highlight_style)
+            .repeat_highlight_symbol(true);
+
+        frame.render_stateful_widget(list, area, &mut self.selected_favorite);
+        self.areas.artists = area;
+    }
+
+    /// The home sections, each entry under its section's title
+    fn render_home(&mut self, frame: &mut Frame, area: Rect) {
+        let block = match self.active_section {
+            ActiveSection::Artists => self.pane_block(true),
+            _ => self.pane_block(false),
+        };
+        let highlight_style = match self.active_section {
+            ActiveSection::Artists => self.theme.selected,
+            _ => self.theme.selected_inactive,
+        };
+
+        let items = self
+            .home
+            .iter()
+            .zip(self.home_headers())
+            .map(|((_, item), header)| {
+                let line = match item.type_.as_str() {
+                    "MusicAlbum" => format!("Album · {} - {}", item.name, item.album_artist),
+                    _ => format!("Song · {} - {}", item.name, item.album_artist),
+                };
+                match header {
+                    Some(title) => {
+                        let mut text = Text::from(Line::styled(title, self.theme.duration));
+                        text.push_line(Line::from(line));
+                        ListItem::new(text)
+                    }
+                    None => ListItem::new(line),
+                }
+            })
+            .collect::<Vec<ListItem>>();
+
+        let list = List::new(items)
+            .block(block.title("Home"))
+            .highlight_symbol(">>")
+            .highlight_style(highlight_style)
+            .repeat_highlight_symbol(true);
+
+        frame.render_stateful_widget(list, area, &mut self.selected_home);
+        self.areas.artists = area;
+    }
+
+    /// The music libraries, ticked if they are browsed
+    fn render_libraries(&mut self, frame: &mut Frame, area: Rect) {
+        let block = match self.active_section {
+            ActiveSection::Artists => self.pane_block(true),
+            _ => self.pane_block(false),
+        };
+        let highlight_style = match self.active_section {
+            ActiveSection::Artists => self.theme.selected,
+            _ => self.theme.selected_inactive,
+        };
+
+        let browsed = self.client.as_ref().map(|client| client.libraries()).unwrap_or_default();
+        let items = self
+            .libraries
+            .iter()
+            .map(|library| match browsed.contains(&library.id) {
+                true => format!("[x] {}", library.name),
+                false => format!("[ ] {}", library.name),
+            })
+            .collect::<Vec<String>>();
+
+        let list = List::new(items)
+            .block(block.title("Libraries"))
+            .highlight_symbol(">>")
+            .highlight_style(highlight_style)
+            .repeat_highlight_symbol(true);
+
+        frame.render_stateful_widget(list, area, &mut self.selected_library);
+        self.areas.artists = area;
+    }
+
+    /// The profiles in the config, the one in use marked
+    fn render_profiles(&mut self, frame: &mut Frame, area: Rect) {
+        let block = match self.active_section {
+            ActiveSection::Artists => self.pane_block(true),
+            _ => self.pane_block(false),
+        };
+        let highlight_style = match self.active_section {
+            ActiveSection::Artists => self.theme.selected,
+            _ => self.theme.selected_inactive,
+        };
+
+        let current = self.client.as_ref().map(|client| client.profile.name.clone()).unwrap_or_default();
+        let items = self
+            .profiles
+            .iter()
+            .map(|profile| match profile.name == current {
+                true => format!("* {} ({})", profile.name, profile.server),
+                false => format!("  {} ({})", profile.name, profile.server),
+            })
+            .collect::<Vec<String>>();
+
+        let list = List::new(items)
+            .block(block.title("Profiles"))
+            .highlight_symbol(">>")
+            .highlight_style(highlight_style)
+            .repeat_highlight_symbol(true);
+
+        frame.render_stateful_widget(list, area, &mut self.selected_profile);
+        self.areas.artists = area;
+    }
+
+    /// All genres, or the artists and albums of the open one
+    fn render_genres(&mut self, frame: &mut Frame, area: Rect) {
+        let block = match self.active_section {
+            ActiveSection::Artists => self.pane_block(true),
+            _ => self.pane_block(false),
+        };
+        let highlight_style = match self.active_section {
+            ActiveSection::Artists => self.theme.selected,
+            _ => self.theme.selected_inactive,
+        };
+
+        let (title, items, state) = match self.open_genre {
+            Some(ref genre) => {
+                let mut items = vec![String::from("All tracks")];
+                items.extend(self.genre_items.iter().map(|item| match item.type_.as_str() {
+                    "MusicArtist" => format!("Artist · {}", item.name),
+                    _ => format!("Album · {} - {}", item.name, item.album_artist),
+                }));
+                (format!("Genre: {}", genre.name), items, &mut self.selected_genre_item)
+            }
+            None => {
+                let items = self.genres.iter().map(|genre| genre.name.clone()).collect::<Vec<String>>();
+                (String::from("Genres"), items, &mut self.selected_genre)
+            }
+        };
+
+        let list = List::new(items)
+            .block(block.title(title))
+            .highlight_symbol(">>")
+            .highlight_style(highlight_style)
+            .repeat_highlight_symbol(true);
+
+        frame.render_stateful_widget(list, area, state);
+        self.areas.artists = area;
+    }
 
+    fn render_tracks(&mut self, frame: &mut Frame, area: Rect) {
         let track_block = match self.active_section {
-            ActiveSection::Tracks => Block::new()
-                .borders(Borders::ALL)
-                .border_style(style::Color::Blue),
-            _ => Block::new()
-                .borders(Borders::ALL)
-                .border_style(style::Color::White),
+            ActiveSection::Tracks => self.pane_block(true),
+            _ => self.pane_block(false),
         };
         
         let track_highlight_style = match self.active_section {
-            ActiveSection::Tracks => Style::default()
-                .bg(Color::White)
-                .fg(Color::Black)
-                .add_modifier(Modifier::BOLD),
-            _ => Style::default()
-                .bg(Color::DarkGray)
-                .fg(Color::Black)
-                .add_modifier(Modifier::BOLD),
+            ActiveSection::Tracks => self.theme.selected,
+            _ => self.theme.selected_inactive,
         };
+        // inside the borders and the highlight symbol
+        let width = area.width.saturating_sub(4) as usize;
+        let headers = self.disc_headers();
         let items = self
             .tracks
             .iter()
-            .map(|track| {
+            .zip(headers)
+            .map(|(track, header)| {
                 let title = format!("{} - {}", track.album, track.name);
                 // track.run_time_ticks is in microseconds
                 let seconds = (track.run_time_ticks / 1_000_0000) % 60;
@@ -492,76 +1137,101 @@ impl App {
                 if track.has_lyrics{
                     time_span_text.push_str(" (l)");
                 }
+                for column in &self.columns {
+                    time_span_text.push_str(&column.cell(track));
+                }
+                // pad the title so the columns line up on the right
+                let title_width = width.saturating_sub(time_span_text.chars().count());
+                let title = format!("{:<title_width$}", title.chars().take(title_width).collect::<String>());
+                let mut time: Text = match header {
+                    Some(disc) => {
+                        let mut text = Text::from(Line::styled(format!("Disc {}", disc), self.theme.duration));
+                        text.push_line(Line::from(title));
+                        text
+                    }
+                    None => Text::from(title),
+                };
+                time.push_span(
+                    Span::styled(
+                        time_span_text,
+                        self.theme.duration,
+                    )
+                );
                 if track.id == self.active_song_id {
-                    let mut time: Text = Text::from(title);
-                    time.push_span(
-                        Span::styled(
-                            time_span_text,
-                            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
-                        )
-                    );
                     ListItem::new(time)
-                        .style(Style::default().fg(Color::Blue))
+                        .style(self.theme.playing)
                 } else {
-                    let mut time: Text = Text::from(title);
-                    time.push_span(
-                        Span::styled(
-                            time_span_text,
-                            Style::default().fg(Color::DarkGray).add_modifier(Modifier::ITALIC),
-                        )
-                    );
                     ListItem::new(time)
                 }
             })
             .collect::<Vec<ListItem>>();
+        let title = match self.open_playlist {
+            Some(ref playlist) => format!("Playlist: {}", playlist.name),
+            None => String::from("Track"),
+        };
         let list = List::new(items)
-            .block(track_block.title("Track"))
+            .block(track_block.title(title))
             .highlight_symbol(">>")
             .highlight_style(
                 track_highlight_style
             )
             .repeat_highlight_symbol(true);
-        frame.render_stateful_widget(list, center[0], &mut self.selected_track);
+        frame.render_stateful_widget(list, area, &mut self.selected_track);
+        self.areas.tracks = area;
 
         // render controls
         frame.render_widget(
-            &Controls {},
+            &Controls { theme: &self.theme, keymap: &self.keymap },
             Layout::default()
                 .direction(Direction::Vertical)
                 .constraints(vec![Constraint::Percentage(100)])
-                .split(center[0])[0],
+                .split(area)[0],
         );
+    }
 
+    /// Cover art, the current song, the progress bar and the stream info
+    fn render_player(&mut self, frame: &mut Frame, area: Rect, show_cover: bool) {
         // currently playing song name. We can get this easily, we have the playlist and the current index
         let current_song = match self
             .playlist
             .get(self.current_playback_state.current_index as usize)
         {
             Some(song) => {
-                let str = format!("{} - {} - {}", song.name, song.artist, song.album);
+                let mut str = format!("{} - {} - {}", song.name, song.artist, song.album);
                 if song.production_year > 0 {
-                    format!("{} ({})", str, song.production_year)
-                } else {
-                    str
+                    str = format!("{} ({})", str, song.production_year);
+                }
+                if !song.genres.is_empty() {
+                    str = format!("{} · {}", str, song.genres.join(", "));
                 }
+                str
             }
             None => String::from("No song playing"),
         };
 
         let bottom = Block::default()
             .borders(Borders::ALL)
+            .border_set(self.theme.border_type)
+            .border_style(self.theme.border)
+            .style(self.theme.status_bar)
             .padding(Padding::new(0, 0, 0, 0));
-        let inner = bottom.inner(center[1]);
-        frame.render_widget(bottom, center[1]);
+        let inner = bottom.inner(area);
+        frame.render_widget(bottom, area);
 
-        // split the bottom into two parts
+        // split the bottom into two parts, no cover when it would be squashed
+        let cover_width = match show_cover {
+            true => 15,
+            false => 0,
+        };
         let bottom_split = Layout::default()
             .flex(Flex::SpaceAround)
             .direction(Direction::Horizontal)
-            .constraints(vec![Constraint::Percentage(15), Constraint::Percentage(85)])
+            .constraints(vec![Constraint::Percentage(cover_width), Constraint::Percentage(100 - cover_width)])
             .split(inner);
 
-        if self.cover_art.is_some() {
+        if !show_cover {
+            // keep the image for when there is room again
+        } else if self.cover_art.is_some() {
             let image = StatefulImage::new(None).resize(Resize::Fit(None));
             frame.render_stateful_widget(image, self.centered_rect(bottom_split[0], 80, 100), self.cover_art.as_mut().unwrap());
         } else {
@@ -598,16 +1268,12 @@ impl App {
         frame.render_widget(
             LineGauge::default()
                 .block(Block::bordered().padding(Padding::zero()).borders(Borders::NONE))
-                .gauge_style(
-                    Style::default()
-                        .fg(Color::White)
-                        .bg(Color::DarkGray)
-                        .add_modifier(Modifier::BOLD),
-                )
+                .gauge_style(self.theme.gauge)
                 .line_set(symbols::line::ROUNDED)
                 .ratio(self.current_playback_state.percentage / 100 as f64),
             progress_bar_area[1],
         );
+        self.areas.progress = progress_bar_area[1];
 
         let metadata = match self.metadata {
             Some(ref metadata) => format!(
@@ -689,34 +1355,68 @@ impl App {
                 );
             }
         }
+    }
 
+    fn render_lyrics(&mut self, frame: &mut Frame, area: Rect) {
         match self.lyrics.1.len() {
             0 => {
                 let lyrics = "No lyrics available";
                 frame.render_widget(
-                    Paragraph::new(lyrics).block(Block::new().borders(Borders::ALL)),
-                    right[0],
+                    Paragraph::new(lyrics).block(self.pane_block(false)),
+                    area,
                 );
             }
             _ => {
-                let lyrics = self.lyrics.1.join("\n");
+                // synced lyrics: highlight the line we're at and keep it roughly centered
+                let position_ticks = (self.current_playback_state.duration
+                    * self.current_playback_state.percentage
+                    * 100000.0) as u64;
+                let current_line = self
+                    .lyrics
+                    .1
+                    .iter()
+                    .rposition(|l| l.start.is_some_and(|start| start <= position_ticks));
+
+                let lines = self
+                    .lyrics
+                    .1
+                    .iter()
+                    .enumerate()
+                    .map(|(i, l)| match current_line {
+                        Some(current) if current == i => {
+                            Line::styled(l.text.as_str(), self.theme.lyrics_highlight)
+                        }
+                        _ => Line::styled(l.text.as_str(), self.theme.lyrics),
+                    })
+                    .collect::<Vec<Line>>();
+
+                let half_height = area.height / 2;
+                let scroll = match current_line {
+                    Some(current) => (current as u16).saturating_sub(half_height),
+                    None => 0,
+                };
+
                 frame.render_widget(
-                    Paragraph::new(lyrics)
-                        .block(Block::new().title("Lyrics")
-                            .borders(Borders::ALL).padding(Padding::horizontal(1))
-                        ).wrap(Wrap { trim: false }),
-                    right[0],
+                    Paragraph::new(lines)
+                        .block(self.pane_block(false).title("Lyrics")
+                            .padding(Padding::horizontal(1))
+                        ).wrap(Wrap { trim: false })
+                        .scroll((scroll, 0)),
+                    area,
                 );
             }
         }
+    }
 
+    fn render_queue(&mut self, frame: &mut Frame, area: Rect) {
         let queue_block = match self.active_section {
-            ActiveSection::Queue => Block::new()
-                .borders(Borders::ALL)
-                .border_style(style::Color::Blue),
-            _ => Block::new()
-                .borders(Borders::ALL)
-                .border_style(style::Color::White),
+            ActiveSection::Queue => self.pane_block(true),
+            _ => self.pane_block(false),
+        };
+
+        let queue_highlight_style = match self.active_section {
+            ActiveSection::Queue => self.theme.selected,
+            _ => self.theme.selected_inactive,
         };
 
         let items = self
@@ -725,35 +1425,171 @@ impl App {
             .map(|song| song.name.as_str())
             .collect::<Vec<&str>>();
         let list = List::new(items)
-            .block(queue_block.title("Queue"))
+            .block(queue_block.title(match self.radio {
+                true => "Queue (radio)",
+                false => "Queue",
+            }))
             .highlight_symbol(">>")
             .highlight_style(
-                Style::default()
-                    .add_modifier(Modifier::BOLD)
-                    .add_modifier(Modifier::REVERSED),
+                queue_highlight_style
             )
             .repeat_highlight_symbol(true);
 
-        frame.render_stateful_widget(list, right[1], &mut self.selected_queue_item);
+        frame.render_stateful_widget(list, area, &mut self.selected_queue_item);
+        self.areas.queue = area;
     }
 
-    async fn handle_events(&mut self) -> io::Result<()> {
-        while event::poll(Duration::from_millis(0))? {
-            match event::read()? {
-                Event::Key(key_event) => {
-                    self.handle_key_event(key_event).await;
-                }
-                Event::Mouse(mouse_event) => {
-                    self.handle_mouse_event(mouse_event);
-                }
-                _ => {}
+    /// The `:` prompt while typing a command, otherwise the last message and the player modes
+    fn render_command_line(&mut self, frame: &mut Frame, area: Rect) {
+        if self.command_line.active {
+            let prompt = format!(":{}", self.command_line.input);
+            let hint = self.command_line.hint();
+            let split = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints(vec![Constraint::Min(0), Constraint::Length(hint.chars().count() as u16 + 1)])
+                .split(area);
+            frame.render_widget(Paragraph::new(prompt.as_str()).style(self.theme.status_bar), split[0]);
+            frame.render_widget(Paragraph::new(hint).style(self.theme.duration), split[1]);
+            frame.set_cursor(
+                (area.x + prompt.chars().count() as u16).min(area.right().saturating_sub(1)),
+                area.y,
+            );
+            return;
+        }
+
+        if let Some((_, since)) = self.status_message {
+            if since.elapsed() > Duration::from_secs(5) {
+                self.status_message = None;
             }
         }
-        Ok(())
+        let message = match self.status_message {
+            Some((ref message, _)) => message.as_str(),
+            None => "",
+        };
+        let target = match self.target_session() {
+            Some(session) => format!("→ {} | ", session.device_name),
+            None => String::new(),
+        };
+        let modes = format!(
+            "{}{}{}vol {}%{} ",
+            target,
+            if self.shuffle { "shuffle | " } else { "" },
+            match self.repeat {
+                Repeat::None => String::new(),
+                repeat => format!("repeat {} | ", repeat.name()),
+            },
+            self.volume,
+            if self.muted { " (muted)" } else { "" }
+        );
+        let split = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Min(0), Constraint::Length(modes.chars().count() as u16)])
+            .split(area);
+        frame.render_widget(Paragraph::new(format!(" {}", message)).style(self.theme.status_bar), split[0]);
+        frame.render_widget(Paragraph::new(modes).style(self.theme.duration), split[1]);
     }
-    pub fn centered_rect(&self, r: Rect, percent_x: u16, percent_y: u16) -> Rect {
-        let popup_layout = Layout::default()
-          .direction(Direction::Vertical)
+
+    pub fn set_message(&mut self, message: String) {
+        log::write(&message);
+        self.status_message = Some((message, Instant::now()));
+    }
+
+    /// Popup listing every action and the keys bound to it. Generated from the keymap
+    fn render_help(&self, frame: &mut Frame) {
+        let rows = Action::ALL
+            .iter()
+            .map(|action| {
+                Row::new(vec![
+                    Cell::from(self.keymap.keys_for(action).join(" ")).style(self.theme.hint_key),
+                    Cell::from(action.description()).style(self.theme.hint_text),
+                ])
+            })
+            .collect::<Vec<Row>>();
+
+        let area = self.centered_rect(frame.size(), 50, 70);
+        let table = Table::new(rows, [Constraint::Percentage(40), Constraint::Percentage(60)])
+            .block(
+                self.pane_block(true)
+                    .title("Key bindings")
+                    .title(
+                        Title::from(Line::from(vec![
+                            Span::styled(" Close ", self.theme.hint_text),
+                            Span::styled(format!("{} ", self.keymap.hint(&Action::Help)), self.theme.hint_key),
+                        ]))
+                        .alignment(Alignment::Center)
+                        .position(Position::Bottom),
+                    )
+                    .padding(Padding::horizontal(1)),
+            )
+            .column_spacing(2);
+
+        frame.render_widget(Clear, area);
+        frame.render_widget(table, area);
+    }
+
+    /// Popup with the ways the focused list can be sorted, the current one is marked
+    fn render_sort_menu(&mut self, frame: &mut Frame) {
+        let (section, current) = match self.sort_menu {
+            Some((ActiveSection::Artists, _)) => (ActiveSection::Artists, self.sorting.artists),
+            Some((section, _)) => (section, self.sorting.tracks),
+            None => return,
+        };
+        let mut items = SortBy::ALL
+            .iter()
+            .map(|sort_by| {
+                let marker = if *sort_by == current { "●" } else { " " };
+                format!("{} {}", marker, sort_by.label())
+            })
+            .collect::<Vec<String>>();
+        items.push(format!(
+            "  Artists: {}",
+            if self.sorting.album_artists { "album artists only" } else { "all track artists" }
+        ));
+
+        let title = match section {
+            ActiveSection::Artists => "Sort artists",
+            _ => "Sort tracks",
+        };
+        let list = List::new(items)
+            .block(self.pane_block(true).title(title))
+            .highlight_symbol(">>")
+            .highlight_style(self.theme.selected);
+
+        let area = self.centered_rect(frame.size(), 30, 40);
+        frame.render_widget(Clear, area);
+        if let Some((_, ref mut state)) = self.sort_menu {
+            frame.render_stateful_widget(list, area, state);
+        }
+    }
+
+    /// Bordered block for one of the panes, styled by the theme
+    fn pane_block(&self, active: bool) -> Block<'static> {
+        Block::new()
+            .borders(Borders::ALL)
+            .border_set(self.theme.border_type)
+            .border_style(match active {
+                true => self.theme.border_active,
+                false => self.theme.border,
+            })
+    }
+
+    async fn handle_events(&mut self) -> io::Result<()> {
+        while event::poll(Duration::from_millis(0))? {
+            match event::read()? {
+                Event::Key(key_event) => {
+                    self.handle_key_event(key_event).await;
+                }
+                Event::Mouse(mouse_event) => {
+                    self.handle_mouse_event(mouse_event).await;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    pub fn centered_rect(&self, r: Rect, percent_x: u16, percent_y: u16) -> Rect {
+        let popup_layout = Layout::default()
+          .direction(Direction::Vertical)
           .constraints([
             Constraint::Percentage((100 - percent_y) / 2),
             Constraint::Percentage(percent_y),
@@ -771,65 +1607,434 @@ impl App {
           .split(popup_layout[1])[1]
       }      
     async fn handle_key_event(&mut self, key_event: KeyEvent) {
+        if key_event.kind != KeyEventKind::Press {
+            return;
+        }
+
+        if self.settings.is_some() {
+            self.handle_settings_key(key_event).await;
+            return;
+        }
+        if self.show_help && key_event.code == KeyCode::Esc {
+            self.show_help = false;
+            return;
+        }
+        if self.sort_menu.is_some() && key_event.code == KeyCode::Esc {
+            self.sort_menu = None;
+            return;
+        }
+
+        if self.command_line.active {
+            self.handle_command_key(key_event).await;
+            return;
+        }
+
+        // a half typed sequence (the first g of gg) expires after a second
+        if self.pending_since.elapsed() > Duration::from_secs(1) {
+            self.pending_keys.clear();
+        }
+        self.pending_keys.push(Key::from(key_event));
+        self.pending_since = Instant::now();
+
+        let action = match self.keymap.resolve(&self.pending_keys) {
+            Resolution::Action(action) => action,
+            Resolution::Pending => return,
+            Resolution::Unbound => {
+                // a stray key in the middle of a sequence starts a new one
+                let last = self.pending_keys[self.pending_keys.len() - 1];
+                let retry = self.pending_keys.len() > 1;
+                self.pending_keys.clear();
+                if !retry {
+                    return;
+                }
+                self.pending_keys.push(last);
+                match self.keymap.resolve(&self.pending_keys) {
+                    Resolution::Action(action) => action,
+                    Resolution::Pending => return,
+                    Resolution::Unbound => {
+                        self.pending_keys.clear();
+                        return;
+                    }
+                }
+            }
+        };
+        self.pending_keys.clear();
+
+        // the help overlay swallows everything but the keys that close it
+        if self.show_help {
+            if let Action::Help | Action::Quit = action {
+                self.show_help = false;
+            }
+            return;
+        }
+        if self.sort_menu.is_some() {
+            self.sort_menu_action(action).await;
+            return;
+        }
+
+        self.dispatch(action).await;
+    }
+
+    /// Keys typed while the `:` prompt is open
+    async fn handle_command_key(&mut self, key_event: KeyEvent) {
         match key_event.code {
-            KeyCode::Char('q') => self.exit(),
-            KeyCode::Left | KeyCode::Char('r')  => {
+            KeyCode::Esc => self.command_line.close(),
+            KeyCode::Enter => {
+                let line = self.command_line.submit();
+                if line.is_empty() {
+                    return;
+                }
+                match command::parse(&line) {
+                    Ok(action) => self.dispatch(action).await,
+                    Err(e) => self.set_message(e),
+                }
+            }
+            KeyCode::Tab => self.command_line.complete(),
+            KeyCode::Up => self.command_line.history_prev(),
+            KeyCode::Down => self.command_line.history_next(),
+            KeyCode::Backspace => self.command_line.backspace(),
+            KeyCode::Char(c) => self.command_line.insert(c),
+            _ => {}
+        }
+    }
+
+    /// Keys typed while the settings form is open
+    async fn handle_settings_key(&mut self, key_event: KeyEvent) {
+        let settings = match self.settings {
+            Some(ref mut settings) => settings,
+            None => return,
+        };
+        match settings.handle_key(key_event) {
+            Outcome::Continue => {}
+            Outcome::Cancel => self.settings = None,
+            Outcome::Test => {
+                settings.test().await;
+            }
+            Outcome::Save => {
+                if let Ok(config) = settings.save() {
+                    self.apply_config(&config).await;
+                }
+            }
+        }
+    }
+
+    /// Uses the saved options that can change while running. The server, login, libraries
+    /// and profiles apply on the next start
+    async fn apply_config(&mut self, config: &serde_yaml::Value) {
+        // the form only saves a valid config, parsed again to apply the environment overrides
+        let (config, _) = Config::parse(&serde_yaml::to_string(config).unwrap_or_default());
+        self.keymap = config.keys;
+        self.panes = config.layout;
+        self.columns = config.columns;
+        self.cache = config.cache;
+        self.scrobbling = config.scrobbling;
+        if config.streaming != self.streaming {
+            if let Some(client) = self.client.as_mut() {
+                client.max_bitrate = config.streaming.max_bitrate;
+            }
+            self.streaming = config.streaming;
+        }
+        if config.sort != self.sorting {
+            self.sorting = config.sort;
+            self.refresh_artists().await;
+        }
+        self.settings = None;
+        self.set_message(String::from("Settings saved, server options apply on the next start"));
+    }
+
+    /// Performs an action typed, clicked or entered on the `:` line. While another session is
+    /// controlled, playback actions go to it instead
+    async fn dispatch(&mut self, action: Action) {
+        if self.target_session.is_some() && self.control_session(&action).await {
+            return;
+        }
+        self.perform(action).await;
+    }
+
+    /// Performs an action on this player, or on the daemon it is attached to
+    async fn perform(&mut self, action: Action) {
+        if let Some(command) = self.daemon.as_ref().and_then(|_| self.daemon_command(&action)) {
+            if let Some(ref mut daemon) = self.daemon {
+                if let Err(e) = daemon.command(&command) {
+                    self.set_message(format!("Daemon: {}", e));
+                }
+                return;
+            }
+        }
+
+        match action {
+            Action::Quit => self.exit(),
+            Action::Help => {
+                self.show_help = true;
+            }
+            Action::CommandMode => {
+                self.status_message = None;
+                self.command_line.open();
+            }
+            Action::SeekBackward => {
                 let mpv = self.mpv_state.lock().unwrap();
                 let _ = mpv.mpv.seek_backward(5.0);
             }
-            KeyCode::Right | KeyCode::Char('s') => {
+            Action::SeekForward => {
                 let mpv = self.mpv_state.lock().unwrap();
                 let _ = mpv.mpv.seek_forward(5.0);
             }
-            KeyCode::Char('n') => {
+            Action::Seek(seconds) => {
+                let mpv = self.mpv_state.lock().unwrap();
+                if seconds >= 0.0 {
+                    let _ = mpv.mpv.seek_forward(seconds);
+                } else {
+                    let _ = mpv.mpv.seek_backward(-seconds);
+                }
+            }
+            Action::SeekTo(seconds) => {
+                let mpv = self.mpv_state.lock().unwrap();
+                let _ = mpv.mpv.seek_absolute(seconds);
+            }
+            Action::SetVolume(volume) => {
+                self.set_volume(volume);
+            }
+            Action::ChangeVolume(delta) => {
+                self.set_volume(self.volume + delta);
+            }
+            Action::Shuffle => {
+                self.toggle_shuffle();
+                self.set_message(format!("Shuffle {}", if self.shuffle { "on" } else { "off" }));
+            }
+            Action::SetShuffle(shuffle) => {
+                if shuffle != self.shuffle {
+                    self.toggle_shuffle();
+                }
+            }
+            Action::CycleRepeat => {
+                self.repeat = self.repeat.next();
+                self.mpv_state.lock().unwrap().set_repeat(self.repeat);
+                self.set_message(format!("Repeat {}", self.repeat.name()));
+            }
+            Action::SetRepeat(repeat) => {
+                self.repeat = repeat;
+                self.mpv_state.lock().unwrap().set_repeat(self.repeat);
+            }
+            Action::ToggleMute => {
+                self.set_muted(!self.muted);
+                self.set_message(String::from(if self.muted { "Muted" } else { "Unmuted" }));
+            }
+            Action::SetMuted(muted) => {
+                self.set_muted(muted);
+            }
+            Action::SetPaused(paused) => {
+                let mpv = self.mpv_state.lock().unwrap();
+                let _ = mpv.mpv.set_property("pause", paused);
+                self.paused = paused;
+            }
+            Action::ClearQueue => self.clear_queue(),
+            Action::SavePlaylist(name) => {
+                let ids = self.playlist.iter().map(|song| song.id.clone()).collect::<Vec<String>>();
+                if ids.is_empty() {
+                    self.set_message(String::from("The queue is empty"));
+                    return;
+                }
+                let result = match self.client {
+                    Some(ref client) => client.create_playlist(&name, ids).await,
+                    None => return,
+                };
+                match result {
+                    Ok(_) => {
+                        self.set_message(format!("Saved the queue as '{}'", name));
+                        self.refresh_playlists().await;
+                    }
+                    Err(e) => self.set_message(format!("Failed to save playlist: {}", e)),
+                }
+            }
+            Action::Search(term) => {
+                let results = match self.client {
+                    Some(ref client) => client.search(&term).await,
+                    None => return,
+                };
+                match results {
+                    Ok(results) => {
+                        self.set_message(format!("{} tracks matching '{}'", results.items.len(), term));
+                        self.tracks = results.items;
+                        self.open_playlist = None;
+                        self.open_artist = None;
+                        self.active_section = ActiveSection::Tracks;
+                        self.selected_track.select(Some(0));
+                    }
+                    Err(e) => self.set_message(format!("Search failed: {}", e)),
+                }
+            }
+            Action::Next => {
                 let client = self.client.as_ref().unwrap();
-                let _ = client.stopped(
-                    self.active_song_id.clone(),
-                    // position ticks
-                    (self.current_playback_state.duration * self.current_playback_state.percentage * 100000.0) as u64,
-                ).await;
+                if self.scrobbling.enabled {
+                    let _ = client.stopped(
+                        self.active_song_id.clone(),
+                        // position ticks
+                        (self.current_playback_state.duration * self.current_playback_state.percentage * 100000.0) as u64,
+                    ).await;
+                }
                 let mpv = self.mpv_state.lock().unwrap();
                 let _ = mpv.mpv.playlist_next_force();
             }
-            KeyCode::Char('p') => {
+            Action::Previous => {
                 let mpv = self.mpv_state.lock().unwrap();
                 let _ = mpv.mpv.playlist_previous_force();
             }
-            KeyCode::Char(' ') => {
+            Action::PlayPause => {
                 // get the current state of mpv
                 let mpv = self.mpv_state.lock().unwrap();
-                self.paused = mpv.mpv.get_property("pause").unwrap_or(false);
-                if self.paused {
+                let paused = mpv.mpv.get_property("pause").unwrap_or(false);
+                if paused {
                     let _ = mpv.mpv.unpause();
                 } else {
                     let _ = mpv.mpv.pause();
                 }
+                self.paused = !paused;
             }
-            KeyCode::Tab => {
+            Action::NextSection => {
                 self.toggle_section(true);
+                // skip hidden panes, tracks are always visible so this ends
+                while !self.panes.is_visible(self.active_section, self.screen_width) {
+                    self.toggle_section(true);
+                }
             }
-            KeyCode::BackTab => {
+            Action::PreviousSection => {
                 self.toggle_section(false);
+                if !self.panes.is_visible(self.active_section, self.screen_width) {
+                    self.active_section = ActiveSection::Tracks;
+                }
+            }
+            Action::CycleLayout => {
+                self.panes.cycle_mode();
+                self.set_message(format!("Layout: {:?}", self.panes.mode).to_lowercase());
+                self.save_layout();
+            }
+            Action::ToggleArtists => {
+                self.panes.show_artists = !self.panes.show_artists;
+                self.save_layout();
+            }
+            Action::ToggleQueue => {
+                self.panes.show_queue = !self.panes.show_queue;
+                self.save_layout();
+            }
+            Action::ToggleLyrics => {
+                self.panes.show_lyrics = !self.panes.show_lyrics;
+                self.save_layout();
+            }
+            Action::GrowPane => {
+                self.panes.resize(self.active_section, 2);
+                self.save_layout();
+            }
+            Action::ShrinkPane => {
+                self.panes.resize(self.active_section, -2);
+                self.save_layout();
+            }
+            Action::ShowHome => {
+                self.show_left_view(LeftView::Home);
+                self.refresh_home().await;
+            }
+            Action::ShowArtists => {
+                self.show_left_view(LeftView::Artists);
+            }
+            Action::ShowLibraries => {
+                self.show_left_view(LeftView::Libraries);
+                self.refresh_libraries().await;
+            }
+            Action::ShowProfiles => {
+                self.show_left_view(LeftView::Profiles);
+                self.refresh_profiles();
+            }
+            Action::SwitchProfile(name) => self.switch_profile(&name).await,
+            Action::Settings => match config::load() {
+                Ok(config) => self.settings = Some(Settings::new(&config)),
+                Err(e) => self.set_message(format!("Could not read config file: {}", e)),
+            },
+            Action::ShowSessions => {
+                self.show_left_view(LeftView::Sessions);
+                self.refresh_sessions();
+            }
+            Action::ShowPlaylists => {
+                self.show_left_view(LeftView::Playlists);
+                self.refresh_playlists().await;
+            }
+            Action::ShowFavorites => {
+                self.show_left_view(LeftView::Favorites);
+                self.refresh_favorites().await;
+            }
+            Action::ShowGenres => {
+                if self.left_view == LeftView::Genres && self.open_genre.is_some() {
+                    // back to all genres
+                    self.open_genre = None;
+                    self.genre_items.clear();
+                } else {
+                    self.refresh_genres().await;
+                }
+                self.show_left_view(LeftView::Genres);
+            }
+            Action::ToggleFavorite => self.toggle_favorite().await,
+            Action::PlayGenre => self.play_genre().await,
+            Action::Radio => match self.radio_seed() {
+                // through dispatch, so it reaches a daemon or a controlled session too
+                Some(id) => Box::pin(self.dispatch(Action::StartRadio(id))).await,
+                None => self.set_message(String::from("Select a track, album, artist, genre or playlist first")),
+            },
+            Action::StartRadio(id) => self.start_radio(&id).await,
+            Action::SortMenu => {
+                let section = match (self.active_section, self.left_view) {
+                    (ActiveSection::Artists, LeftView::Artists) => ActiveSection::Artists,
+                    _ => ActiveSection::Tracks,
+                };
+                let current = match section {
+                    ActiveSection::Artists => self.sorting.artists,
+                    _ => self.sorting.tracks,
+                };
+                let mut state = ListState::default();
+                state.select(SortBy::ALL.iter().position(|sort_by| *sort_by == current));
+                self.sort_menu = Some((section, state));
+            }
+            Action::Delete => match self.active_section {
+                ActiveSection::Artists if self.left_view == LeftView::Playlists => {
+                    let selected = self.selected_playlist.selected().unwrap_or(0);
+                    if let Some(playlist) = self.playlists.get(selected).cloned() {
+                        self.delete_playlist(&playlist).await;
+                    }
+                }
+                ActiveSection::Tracks => self.remove_from_playlist().await,
+                _ => {}
+            },
+            Action::MoveUp => self.move_playlist_entry(-1).await,
+            Action::MoveDown => self.move_playlist_entry(1).await,
+            Action::AddToPlaylist(name) => self.add_to_playlist(&name).await,
+            Action::SortTracks(key, reverse) => {
+                let selected = self.selected_track.selected().and_then(|i| self.tracks.get(i)).map(|t| t.id.clone());
+                match columns::sort(&mut self.tracks, &key, reverse) {
+                    Ok(()) => {
+                        self.playlist_in_order = false;
+                        // keep the same track selected
+                        let index = selected.and_then(|id| self.tracks.iter().position(|t| t.id == id));
+                        self.selected_track.select(Some(index.unwrap_or(0)));
+                    }
+                    Err(e) => self.set_message(e),
+                }
             }
-            KeyCode::Down | KeyCode::Char('j') => match self.active_section {
+            Action::Down => match self.active_section {
                 ActiveSection::Artists => {
-                    let selected = self
-                        .selected_artist
-                        .selected()
-                        .unwrap_or(self.artists.len() - 1);
-                    if selected == self.artists.len() - 1 {
-                        self.selected_artist.select(Some(selected));
+                    let last = self.left_len().saturating_sub(1);
+                    let state = self.left_state();
+                    let selected = state.selected().unwrap_or(last);
+                    if selected >= last {
+                        state.select(Some(last));
                         return;
                     }
-                    self.selected_artist.select(Some(selected + 1));
+                    state.select(Some(selected + 1));
                 }
                 ActiveSection::Tracks => {
-                    let selected = self
-                        .selected_track
-                        .selected()
-                        .unwrap_or(self.tracks.len() - 1);
-                    if selected == self.tracks.len() - 1 {
-                        self.selected_track.select(Some(selected));
+                    if self.tracks.is_empty() {
+                        return;
+                    }
+                    let last = self.tracks.len().saturating_sub(1);
+                    let selected = self.selected_track.selected().unwrap_or(last);
+                    if selected >= last {
+                        self.selected_track.select(Some(last));
                         return;
                     }
                     self.selected_track.select(Some(selected + 1));
@@ -838,14 +2043,15 @@ impl App {
                     *self.selected_queue_item.offset_mut() += 1;
                 }
             },
-            KeyCode::Up | KeyCode::Char('k') => match self.active_section {
+            Action::Up => match self.active_section {
                 ActiveSection::Artists => {
-                    let selected = self.selected_artist.selected().unwrap_or(0);
+                    let state = self.left_state();
+                    let selected = state.selected().unwrap_or(0);
                     if selected == 0 {
-                        self.selected_artist.select(Some(selected));
+                        state.select(Some(selected));
                         return;
                     }
-                    self.selected_artist.select(Some(selected - 1));
+                    state.select(Some(selected - 1));
                 }
                 ActiveSection::Tracks => {
                     let selected = self.selected_track.selected().unwrap_or(0);
@@ -863,9 +2069,9 @@ impl App {
                     *lvalue -= 1;
                 }
             },
-            KeyCode::Char('g') => match self.active_section {
+            Action::Top => match self.active_section {
                 ActiveSection::Artists => {
-                    self.selected_artist.select(Some(0));
+                    self.left_state().select(Some(0));
                 }
                 ActiveSection::Tracks => {
                     self.selected_track.select(Some(0));
@@ -874,19 +2080,76 @@ impl App {
                     self.selected_queue_item.select(Some(0));
                 }
             },
-            KeyCode::Char('G') => match self.active_section {
+            Action::Bottom => match self.active_section {
                 ActiveSection::Artists => {
-                    self.selected_artist.select(Some(self.artists.len() - 1));
+                    let last = self.left_len().saturating_sub(1);
+                    self.left_state().select(Some(last));
                 }
                 ActiveSection::Tracks => {
-                    self.selected_track.select(Some(self.tracks.len() - 1));
+                    self.selected_track.select(Some(self.tracks.len().saturating_sub(1)));
                 }
                 ActiveSection::Queue => {
-                    self.selected_queue_item.select(Some(self.playlist.len() - 1));
+                    self.selected_queue_item.select(Some(self.playlist.len().saturating_sub(1)));
                 }
             },
-            KeyCode::Enter => {
+            Action::Select => {
                 match self.active_section {
+                    ActiveSection::Artists if self.left_view == LeftView::Favorites => {
+                        let selected = self.selected_favorite.selected().unwrap_or(0);
+                        if let Some(favorite) = self.favorites.get(selected).cloned() {
+                            self.open_item(&favorite).await;
+                        }
+                    }
+                    ActiveSection::Artists if self.left_view == LeftView::Home => {
+                        self.play_home_entry(true).await;
+                    }
+                    ActiveSection::Artists if self.left_view == LeftView::Genres => {
+                        match self.open_genre {
+                            Some(_) => match self.selected_genre_item.selected().unwrap_or(0) {
+                                0 => {
+                                    self.active_section = ActiveSection::Tracks;
+                                    self.selected_track.select(Some(0));
+                                }
+                                selected => {
+                                    if let Some(item) = self.genre_items.get(selected - 1).cloned() {
+                                        self.open_item(&item).await;
+                                    }
+                                }
+                            },
+                            None => {
+                                let selected = self.selected_genre.selected().unwrap_or(0);
+                                if let Some(genre) = self.genres.get(selected).cloned() {
+                                    self.open_genre(genre).await;
+                                }
+                            }
+                        }
+                    }
+                    ActiveSection::Artists if self.left_view == LeftView::Playlists => {
+                        let selected = self.selected_playlist.selected().unwrap_or(0);
+                        if let Some(playlist) = self.playlists.get(selected).cloned() {
+                            self.open_playlist(playlist).await;
+                            self.active_section = ActiveSection::Tracks;
+                            self.selected_track.select(Some(0));
+                        }
+                    }
+                    ActiveSection::Artists if self.left_view == LeftView::Libraries => {
+                        let selected = self.selected_library.selected().unwrap_or(0);
+                        if let Some(library) = self.libraries.get(selected).cloned() {
+                            self.toggle_library(&library).await;
+                        }
+                    }
+                    ActiveSection::Artists if self.left_view == LeftView::Profiles => {
+                        let selected = self.selected_profile.selected().unwrap_or(0);
+                        if let Some(profile) = self.profiles.get(selected).cloned() {
+                            self.switch_profile(&profile.name).await;
+                        }
+                    }
+                    ActiveSection::Artists if self.left_view == LeftView::Sessions => {
+                        let selected = self.selected_session.selected().unwrap_or(0);
+                        if let Some(session) = self.sessions.get(selected).cloned() {
+                            self.toggle_target_session(&session);
+                        }
+                    }
                     ActiveSection::Artists => {
                         let selected = self.selected_artist.selected().unwrap_or(0);
                         self.discography(&self.artists[selected].id.clone()).await;
@@ -896,28 +2159,14 @@ impl App {
                         let selected = self.selected_track.selected().unwrap_or(0);
                         match self.client {
                             Some(ref client) => {
-                                let lock = self.mpv_state.clone();
-                                let mut mpv = lock.lock().unwrap();
-                                mpv.should_stop = true;
-
                                 // the playlist MPV will be getting
-                                self.playlist = self
+                                let songs = self
                                     .tracks
                                     .iter()
                                     .skip(selected)
-                                    .map(|track| {
-                                        Song {
-                                            id: track.id.clone(),
-                                            url: client.song_url_sync(track.id.clone()),
-                                            name: track.name.clone(),
-                                            artist: track.album_artist.clone(),
-                                            album: track.album.clone(),
-                                            parent_id: track.parent_id.clone(),
-                                            production_year: track.production_year,
-                                        }
-                                    })
+                                    .map(|track| Song::from_track(client, track))
                                     .collect();
-                                self.replace_playlist();
+                                self.play(songs);
                             }
                             None => {
                                 println!("No client");
@@ -925,79 +2174,1387 @@ impl App {
                         }
                     }
                     ActiveSection::Queue => {
-                        let _ = self.selected_queue_item.selected().unwrap_or(0);
-                        // println!("Selected queue item: {:?}", selected);
+                        let selected = self.selected_queue_item.selected().unwrap_or(0);
+                        self.play_index(selected);
                     }
                 }
             }
-            _ => {}
-        }
-    }
-
-    /// Fetch the discography of an artist
-    /// This will change the active section to tracks
-    async fn discography(&mut self, id: &str) {
-        match self.client {
-            Some(ref client) => {
-                let artist = client.discography(id).await;
-                match artist {
-                    Ok(artist) => {
-                        self.active_section = ActiveSection::Tracks;
-                        self.tracks = artist.items;
+            Action::Enqueue if self.active_section == ActiveSection::Artists && self.left_view == LeftView::Home => {
+                self.play_home_entry(false).await;
+            }
+            Action::Enqueue => {
+                if let ActiveSection::Tracks = self.active_section {
+                    let selected = self.selected_track.selected().unwrap_or(0);
+                    let (client, track) = match (&self.client, self.tracks.get(selected)) {
+                        (Some(client), Some(track)) => (client, track),
+                        _ => return,
+                    };
+                    let song = Song::from_track(client, track);
+                    self.enqueue(vec![song]);
+                }
+            }
+            Action::PlayIds(ids) => {
+                let client = match self.client {
+                    Some(ref client) => client,
+                    None => return,
+                };
+                match client.items(&ids).await {
+                    Ok(items) if !items.items.is_empty() => {
+                        let songs = items
+                            .items
+                            .iter()
+                            .map(|track| Song::from_track(client, track))
+                            .collect::<Vec<Song>>();
+                        self.play(songs);
                     }
-                    Err(e) => {
-                        println!("Failed to get discography: {:?}", e);
+                    Ok(_) => self.set_message(String::from("No tracks found")),
+                    Err(e) => self.set_message(format!("Failed to fetch tracks: {}", e)),
+                }
+            }
+            Action::PlayIndex(index) => {
+                self.play_index(index);
+            }
+            Action::EnqueueIds(ids) => {
+                let client = match self.client {
+                    Some(ref client) => client,
+                    None => return,
+                };
+                match client.items(&ids).await {
+                    Ok(items) if !items.items.is_empty() => {
+                        let songs = items
+                            .items
+                            .iter()
+                            .map(|track| Song::from_track(client, track))
+                            .collect::<Vec<Song>>();
+                        self.set_message(format!("Added {} tracks to the queue", songs.len()));
+                        self.enqueue(songs);
                     }
+                    Ok(_) => self.set_message(String::from("No tracks found")),
+                    Err(e) => self.set_message(format!("Failed to fetch tracks: {}", e)),
                 }
             }
-            None => {} // this would be bad
         }
     }
 
-    fn replace_playlist(&mut self) {
-        let _ = {
-            self.mpv_state = Arc::new(Mutex::new(MpvState::new())); // Shared state for controlling MPV
-            let mpv_state = self.mpv_state.clone();
-            let sender = self.sender.clone();
-            let songs = self.playlist.clone();
-            // println!("Playing playlist: {:?}", songs);
+    /// Number of entries in the left pane
+    fn left_len(&self) -> usize {
+        match self.left_view {
+            LeftView::Home => self.home.len(),
+            LeftView::Artists => self.artists.len(),
+            LeftView::Sessions => self.sessions.len(),
+            LeftView::Libraries => self.libraries.len(),
+            LeftView::Profiles => self.profiles.len(),
+            LeftView::Playlists => self.playlists.len(),
+            LeftView::Favorites => self.favorites.len(),
+            LeftView::Genres if self.open_genre.is_some() => self.genre_items.len() + 1,
+            LeftView::Genres => self.genres.len(),
+        }
+    }
 
-            self.mpv_thread = Some(thread::spawn(move || {
-                Self::t_playlist(songs, mpv_state, sender)
-            }));
-        };
+    /// Selection of the left pane
+    fn left_state(&mut self) -> &mut ListState {
+        match self.left_view {
+            LeftView::Home => &mut self.selected_home,
+            LeftView::Artists => &mut self.selected_artist,
+            LeftView::Sessions => &mut self.selected_session,
+            LeftView::Libraries => &mut self.selected_library,
+            LeftView::Profiles => &mut self.selected_profile,
+            LeftView::Playlists => &mut self.selected_playlist,
+            LeftView::Favorites => &mut self.selected_favorite,
+            LeftView::Genres if self.open_genre.is_some() => &mut self.selected_genre_item,
+            LeftView::Genres => &mut self.selected_genre,
+        }
     }
 
-    fn t_playlist(
-        songs: Vec<Song>,
-        mpv_state: Arc<Mutex<MpvState>>,
-        sender: Sender<MpvPlaybackState>,
-    ) {
-        {
-            let lock = mpv_state.clone();
-            let mpv = match lock.lock() {
-                Ok(mpv) => mpv,
-                Err(_) => {
-                    return;
-                }
-            };
+    fn show_left_view(&mut self, view: LeftView) {
+        self.left_view = view;
+        if self.panes.is_visible(ActiveSection::Artists, self.screen_width) {
+            self.active_section = ActiveSection::Artists;
+        }
+    }
 
-            match mpv.mpv.playlist_clear() {
-                Ok(_) => {}
-                Err(_) => {}
-            }
+    fn target_session(&self) -> Option<&Session> {
+        let id = self.target_session.as_ref()?;
+        self.sessions.iter().find(|session| session.id == *id)
+    }
 
-            mpv.mpv
-                .playlist_load_files(
-                    &songs
-                        .iter()
-                        .map(|song| (song.url.as_str(), FileState::AppendPlay, None))
-                        .collect::<Vec<(&str, FileState, Option<&str>)>>()
-                        .as_slice(),
-                )
-                .unwrap();
+    /// Fetches the other sessions. Drops the target if it went away
+    /// Fetches the sessions in the background, so a slow server doesn't hold up drawing. They
+    /// are picked up by receive_sessions
+    fn refresh_sessions(&mut self) {
+        if self.sessions_pending {
+            return;
+        }
+        let client = match self.client {
+            Some(ref client) => client.clone(),
+            None => return,
+        };
+        self.sessions_refresh = Instant::now() + Duration::from_secs(5);
+        self.sessions_pending = true;
+        let sender = self.sessions_sender.clone();
+        tokio::spawn(async move {
+            let result = client.sessions().await.map_err(|e| e.to_string());
+            let _ = sender.send((client.base_url, result));
+        });
+    }
 
+    fn receive_sessions(&mut self) {
+        let (server, result) = match self.sessions_receiver.try_recv() {
+            Ok(received) => received,
+            Err(_) => return,
+        };
+        self.sessions_pending = false;
+        // from before switching profiles
+        if self.client.as_ref().is_none_or(|client| client.base_url != server) {
+            return;
+        }
+        match result {
+            Ok(sessions) => {
+                self.sessions = sessions;
+                self.sessions_fetched = Instant::now();
+            }
+            Err(e) => {
+                self.set_message(format!("Failed to fetch sessions: {}", e));
+                return;
+            }
+        }
+        clamp_selection(&mut self.selected_session, self.sessions.len());
+        if self.target_session.is_some() && self.target_session().is_none() {
+            self.target_session = None;
+            self.set_message(String::from("The session being controlled is gone"));
+        }
+    }
+
+    /// Starts sending playback actions to a session, or stops if it already was the target
+    fn toggle_target_session(&mut self, session: &Session) {
+        if self.target_session.as_ref() == Some(&session.id) {
+            self.target_session = None;
+            self.set_message(format!("Stopped controlling {}", session.device_name));
+            return;
+        }
+        if !session.supports_remote_control {
+            self.set_message(format!("{} can't be remote controlled", session.device_name));
+            return;
+        }
+        self.target_session = Some(session.id.clone());
+        self.set_message(format!("Controlling {}, select it again to stop", session.device_name));
+    }
+
+    /// Performs a playback action on the target session instead of the local player. Returns
+    /// false for actions that are not about playback, those are handled as usual
+    async fn control_session(&mut self, action: &Action) -> bool {
+        let session = match self.target_session() {
+            Some(session) => session.clone(),
+            None => return false,
+        };
+        let client = match self.client {
+            Some(ref client) => client,
+            None => return false,
+        };
+
+        // where it should be by now, the position is from the last refresh
+        let position = session.play_state.position_ticks as f64 / 10_000_000.0
+            + match session.play_state.is_paused {
+                true => 0.0,
+                false => self.sessions_fetched.elapsed().as_secs_f64(),
+            };
+        let seek = |seconds: f64| Some((seconds.max(0.0) * 10_000_000.0) as u64);
+        let volume = session.play_state.volume_level.unwrap_or(100);
+        let id = session.id.as_str();
+
+        let result = match action {
+            Action::PlayPause => client.session_playstate(id, "PlayPause", None).await,
+            Action::SetPaused(true) => client.session_playstate(id, "Pause", None).await,
+            Action::SetPaused(false) => client.session_playstate(id, "Unpause", None).await,
+            Action::Next => client.session_playstate(id, "NextTrack", None).await,
+            Action::Previous => client.session_playstate(id, "PreviousTrack", None).await,
+            Action::ClearQueue => client.session_playstate(id, "Stop", None).await,
+            Action::SeekForward => client.session_playstate(id, "Seek", seek(position + 5.0)).await,
+            Action::SeekBackward => client.session_playstate(id, "Seek", seek(position - 5.0)).await,
+            Action::Seek(seconds) => client.session_playstate(id, "Seek", seek(position + seconds)).await,
+            Action::SeekTo(seconds) => client.session_playstate(id, "Seek", seek(*seconds)).await,
+            Action::SetVolume(_) | Action::ChangeVolume(_) => {
+                let volume = match action {
+                    Action::SetVolume(volume) => *volume,
+                    Action::ChangeVolume(delta) => volume + delta,
+                    _ => volume,
+                };
+                let arguments = serde_json::json!({ "Volume": volume.clamp(0, 100).to_string() });
+                client.session_command(id, "SetVolume", arguments).await
+            }
+            Action::ToggleMute => client.session_command(id, "ToggleMute", serde_json::json!({})).await,
+            Action::SetMuted(true) => client.session_command(id, "Mute", serde_json::json!({})).await,
+            Action::SetMuted(false) => client.session_command(id, "Unmute", serde_json::json!({})).await,
+            Action::PlayIds(ids) => client.session_play(id, &ids[..ids.len().min(SESSION_PLAY_LENGTH)], "PlayNow").await,
+            Action::EnqueueIds(ids) => client.session_play(id, &ids[..ids.len().min(SESSION_PLAY_LENGTH)], "PlayLast").await,
+            // the session gets one batch, refilling only happens for our own queue
+            Action::StartRadio(seed) => match client.instant_mix(seed, RADIO_BATCH).await {
+                Ok(tracks) => {
+                    let ids = tracks.items.into_iter().map(|track| track.id).collect::<Vec<String>>();
+                    client.session_play(id, &ids, "PlayNow").await
+                }
+                Err(e) => Err(e),
+            },
+            Action::Select if self.active_section == ActiveSection::Tracks => {
+                let selected = self.selected_track.selected().unwrap_or(0);
+                let ids = self
+                    .tracks
+                    .iter()
+                    .skip(selected)
+                    .take(SESSION_PLAY_LENGTH)
+                    .map(|track| track.id.clone())
+                    .collect::<Vec<String>>();
+                client.session_play(id, &ids, "PlayNow").await
+            }
+            Action::Enqueue if self.active_section == ActiveSection::Tracks => {
+                let selected = self.selected_track.selected().unwrap_or(0);
+                let ids = match self.tracks.get(selected) {
+                    Some(track) => vec![track.id.clone()],
+                    None => return true,
+                };
+                client.session_play(id, &ids, "PlayLast").await
+            }
+            _ => return false,
+        };
+
+        match result {
+            // give the session a moment to report back, then show the new state
+            Ok(()) => self.sessions_refresh = Instant::now() + Duration::from_secs(1),
+            Err(e) => self.set_message(format!("{}: {}", session.device_name, e)),
+        }
+        true
+    }
+
+    async fn refresh_playlists(&mut self) {
+        let result = match self.client {
+            Some(ref client) => client.playlists().await,
+            None => return,
+        };
+        match result {
+            Ok(playlists) => self.playlists = playlists,
+            Err(e) => {
+                self.set_message(format!("Failed to fetch playlists: {}", e));
+                return;
+            }
+        }
+        clamp_selection(&mut self.selected_playlist, self.playlists.len());
+    }
+
+    /// Shows a playlist's tracks in the tracks pane
+    async fn open_playlist(&mut self, playlist: Playlist) {
+        let result = match self.client {
+            Some(ref client) => client.playlist_items(&playlist.id).await,
+            None => return,
+        };
+        match result {
+            Ok(items) => {
+                self.tracks = items.items;
+                self.open_playlist = Some(playlist);
+                self.playlist_in_order = true;
+                self.open_artist = None;
+                let len = self.tracks.len();
+                if self.selected_track.selected().is_some_and(|selected| selected >= len) {
+                    self.selected_track.select(Some(len.saturating_sub(1)));
+                }
+            }
+            Err(e) => self.set_message(format!("Failed to fetch '{}': {}", playlist.name, e)),
+        }
+    }
+
+    /// The first press asks for confirmation, a second one within a few seconds deletes
+    async fn delete_playlist(&mut self, playlist: &Playlist) {
+        let confirmed = match self.pending_delete.take() {
+            Some((id, since)) => id == playlist.id && since.elapsed() < Duration::from_secs(3),
+            None => false,
+        };
+        if !confirmed {
+            self.pending_delete = Some((playlist.id.clone(), Instant::now()));
+            self.set_message(format!("Press {} again to delete '{}'", self.keymap.hint(&Action::Delete), playlist.name));
+            return;
+        }
+
+        let result = match self.client {
+            Some(ref client) => client.delete_playlist(&playlist.id).await,
+            None => return,
+        };
+        match result {
+            Ok(()) => {
+                self.set_message(format!("Deleted '{}'", playlist.name));
+                if self.open_playlist.as_ref().map(|open| &open.id) == Some(&playlist.id) {
+                    self.open_playlist = None;
+                    self.tracks.clear();
+                }
+                self.refresh_playlists().await;
+            }
+            Err(e) => self.set_message(format!("Failed to delete '{}': {}", playlist.name, e)),
+        }
+    }
+
+    /// Removes the selected track from the open playlist
+    async fn remove_from_playlist(&mut self) {
+        let playlist = match self.open_playlist {
+            Some(ref playlist) => playlist.clone(),
+            None => return,
+        };
+        let selected = self.selected_track.selected().unwrap_or(0);
+        let entry = match self.tracks.get(selected) {
+            Some(track) => track.playlist_item_id.clone(),
+            None => return,
+        };
+        let result = match self.client {
+            Some(ref client) => client.remove_from_playlist(&playlist.id, &[entry]).await,
+            None => return,
+        };
+        match result {
+            Ok(()) => {
+                self.open_playlist(playlist).await;
+                self.refresh_playlists().await;
+            }
+            Err(e) => self.set_message(format!("Failed to remove the track: {}", e)),
+        }
+    }
+
+    /// Moves the selected entry of the open playlist up (-1) or down (1). Only while it is shown
+    /// in its saved order, see playlist_in_order
+    async fn move_playlist_entry(&mut self, direction: i64) {
+        let playlist = match (self.active_section, &self.open_playlist) {
+            (ActiveSection::Tracks, Some(playlist)) => playlist.clone(),
+            _ => return,
+        };
+        // the server moves entries by their index in the saved order
+        if !self.playlist_in_order {
+            self.set_message(String::from("The playlist is sorted, open it again to move tracks"));
+            return;
+        }
+        let selected = self.selected_track.selected().unwrap_or(0);
+        let target = selected as i64 + direction;
+        if target < 0 || target >= self.tracks.len() as i64 {
+            return;
+        }
+        let target = target as usize;
+        let result = match self.client {
+            Some(ref client) => {
+                client.move_playlist_item(&playlist.id, &self.tracks[selected].playlist_item_id, target).await
+            }
+            None => return,
+        };
+        match result {
+            Ok(()) => {
+                self.tracks.swap(selected, target);
+                self.selected_track.select(Some(target));
+            }
+            Err(e) => self.set_message(format!("Failed to move the track: {}", e)),
+        }
+    }
+
+    /// Adds the selected track (or queue entry) to the playlist with this name
+    async fn add_to_playlist(&mut self, name: &str) {
+        let id = match self.active_section {
+            ActiveSection::Queue => {
+                let selected = self.selected_queue_item.selected().unwrap_or(0);
+                self.playlist.get(selected).map(|song| song.id.clone())
+            }
+            _ => {
+                let selected = self.selected_track.selected().unwrap_or(0);
+                self.tracks.get(selected).map(|track| track.id.clone())
+            }
+        };
+        let id = match id {
+            Some(id) => id,
+            None => {
+                self.set_message(String::from("No track selected"));
+                return;
+            }
+        };
+
+        if self.playlists.is_empty() {
+            self.refresh_playlists().await;
+        }
+        let playlist = match self.playlists.iter().find(|p| p.name.eq_ignore_ascii_case(name)) {
+            Some(playlist) => playlist.clone(),
+            None => {
+                self.set_message(format!("No playlist named '{}', :save creates one from the queue", name));
+                return;
+            }
+        };
+        let result = match self.client {
+            Some(ref client) => client.add_to_playlist(&playlist.id, &[id]).await,
+            None => return,
+        };
+        match result {
+            Ok(()) => {
+                self.set_message(format!("Added to '{}'", playlist.name));
+                self.refresh_playlists().await;
+                if self.open_playlist.as_ref().map(|open| &open.id) == Some(&playlist.id) {
+                    self.open_playlist(playlist).await;
+                }
+            }
+            Err(e) => self.set_message(format!("Failed to add to '{}': {}", playlist.name, e)),
+        }
+    }
+
+    async fn refresh_favorites(&mut self) {
+        let result = match self.client {
+            Some(ref client) => client.favorites().await,
+            None => return,
+        };
+        match result {
+            Ok(favorites) => self.favorites = favorites,
+            Err(e) => {
+                self.set_message(format!("Failed to fetch favorites: {}", e));
+                return;
+            }
+        }
+        clamp_selection(&mut self.selected_favorite, self.favorites.len());
+    }
+
+    /// An artist opens its discography, an album its tracks. A song opens all favorite songs
+    /// with that one selected
+    async fn open_item(&mut self, favorite: &Item) {
+        let client = match self.client {
+            Some(ref client) => client,
+            None => return,
+        };
+        let (result, selected) = match favorite.type_.as_str() {
+            "MusicArtist" => (client.discography(&favorite.id, &self.sorting).await, 0),
+            "MusicAlbum" => (client.album(&favorite.id).await, 0),
+            _ => {
+                let songs = self.favorites.iter().filter(|f| f.type_ == "Audio");
+                let ids = songs.map(|f| f.id.clone()).collect::<Vec<String>>();
+                let selected = ids.iter().position(|id| *id == favorite.id).unwrap_or(0);
+                (client.items(&ids).await, selected)
+            }
+        };
+        match result {
+            Ok(tracks) => {
+                self.tracks = tracks.items;
+                self.open_playlist = None;
+                self.open_artist = match favorite.type_.as_str() {
+                    "MusicArtist" => Some(favorite.id.clone()),
+                    _ => None,
+                };
+                self.active_section = ActiveSection::Tracks;
+                self.selected_track.select(Some(selected));
+            }
+            Err(e) => self.set_message(format!("Failed to fetch '{}': {}", favorite.name, e)),
+        }
+    }
+
+    /// Fetches every home section. A section that fails is left out
+    async fn refresh_home(&mut self) {
+        let client = match self.client {
+            Some(ref client) => client,
+            None => return,
+        };
+        let mut entries = vec![];
+        let mut failed = None;
+        for section in home::Section::ALL {
+            match section.fetch(client).await {
+                Ok(items) => entries.extend(items.into_iter().map(|item| (section, item))),
+                Err(e) => failed = Some(format!("Failed to fetch {}: {}", section.title().to_lowercase(), e)),
+            }
+        }
+        self.home = entries;
+        if let Some(message) = failed {
+            self.set_message(message);
+        }
+        clamp_selection(&mut self.selected_home, self.home.len());
+    }
+
+    /// The section title to show above each home entry, only the first of a section has one
+    fn home_headers(&self) -> Vec<Option<&'static str>> {
+        self.home
+            .iter()
+            .enumerate()
+            .map(|(i, (section, _))| match i.checked_sub(1).map(|i| self.home[i].0) {
+                Some(previous) if previous == *section => None,
+                _ => Some(section.title()),
+            })
+            .collect()
+    }
+
+    /// Plays the selected home entry, or adds it to the queue. An album goes in whole, a song
+    /// is played along with the songs after it in its section
+    async fn play_home_entry(&mut self, play: bool) {
+        let selected = self.selected_home.selected().unwrap_or(0);
+        let (section, item) = match self.home.get(selected) {
+            Some(entry) => entry.clone(),
+            None => return,
+        };
+        let client = match self.client {
+            Some(ref client) => client,
+            None => return,
+        };
+        let ids = match item.type_.as_str() {
+            "MusicAlbum" => match client.album(&item.id).await {
+                Ok(tracks) => tracks.items.into_iter().map(|track| track.id).collect::<Vec<String>>(),
+                Err(e) => {
+                    self.set_message(format!("Failed to fetch '{}': {}", item.name, e));
+                    return;
+                }
+            },
+            _ if play => self.home[selected..]
+                .iter()
+                .take_while(|(s, _)| *s == section)
+                .filter(|(_, item)| item.type_ == "Audio")
+                .map(|(_, item)| item.id.clone())
+                .collect(),
+            _ => vec![item.id.clone()],
+        };
+        let action = match play {
+            true => Action::PlayIds(ids),
+            false => Action::EnqueueIds(ids),
+        };
+        // through dispatch, so it reaches a daemon or a controlled session too
+        Box::pin(self.dispatch(action)).await;
+    }
+
+    async fn refresh_libraries(&mut self) {
+        let result = match self.client {
+            Some(ref client) => client.music_libraries().await,
+            None => return,
+        };
+        match result {
+            Ok(libraries) => self.libraries = libraries,
+            Err(e) => {
+                self.set_message(format!("Failed to fetch libraries: {}", e));
+                return;
+            }
+        }
+        clamp_selection(&mut self.selected_library, self.libraries.len());
+    }
+
+    /// Starts or stops browsing a library. At least one stays browsed. The choice is saved to
+    /// config.yaml and everything listed is fetched again
+    async fn toggle_library(&mut self, library: &Library) {
+        let client = match self.client {
+            Some(ref mut client) => client,
+            None => return,
+        };
+        let mut browsed = client.libraries().to_vec();
+        match browsed.iter().position(|id| *id == library.id) {
+            Some(index) => {
+                browsed.remove(index);
+            }
+            None => browsed.push(library.id.clone()),
+        }
+        if browsed.is_empty() {
+            self.set_message(String::from("At least one library has to be browsed"));
+            return;
+        }
+        client.set_libraries(browsed.clone());
+
+        // all of them is saved as nothing, so libraries added later show up too
+        let value = match self.libraries.iter().all(|library| browsed.contains(&library.id)) {
+            true => serde_yaml::Value::Null,
+            false => serde_yaml::Value::Sequence(browsed.into_iter().map(serde_yaml::Value::String).collect()),
+        };
+        if let Err(e) = client.profile.save_libraries(value) {
+            self.set_message(format!("Failed to save the libraries: {}", e));
+        }
+
+        self.refresh_artists().await;
+        self.open_genre = None;
+        self.genre_items.clear();
+        self.genres.clear();
+        self.home.clear();
+    }
+
+    fn refresh_profiles(&mut self) {
+        match config::load_effective().and_then(|config| profile::list(&config)) {
+            Ok(profiles) => self.profiles = profiles,
+            Err(e) => {
+                self.set_message(format!("Failed to read the profiles: {}", e));
+                return;
+            }
+        }
+        clamp_selection(&mut self.selected_profile, self.profiles.len());
+    }
+
+    /// Logs in to another profile's server and starts over there: the queue of this one is
+    /// saved and the other's restored. The current profile stays if logging in fails
+    async fn switch_profile(&mut self, name: &str) {
+        if self.daemon.is_some() {
+            self.set_message(String::from("Profiles can't be switched while attached to a daemon"));
+            return;
+        }
+        if self.client.as_ref().is_some_and(|client| client.profile.name == name) {
+            self.set_message(format!("Already using profile '{}'", name));
+            return;
+        }
+        let profile = match config::load_effective().and_then(|config| profile::select(&config, Some(name))) {
+            Ok(profile) => profile,
+            Err(e) => {
+                self.set_message(format!("Failed to switch profile: {}", e));
+                return;
+            }
+        };
+        let mut client = match Client::new(&profile).await {
+            Ok(client) => client,
+            Err(e) => {
+                self.set_message(format!("Failed to log in to '{}': {}", name, e));
+                return;
+            }
+        };
+
+        self.save_queue_on_exit().await;
+        self.clear_queue();
+        self.target_session = None;
+        self.sessions.clear();
+        self.playlists.clear();
+        self.open_playlist = None;
+        self.favorites.clear();
+        self.home.clear();
+        self.genres.clear();
+        self.open_genre = None;
+        self.genre_items.clear();
+        self.libraries.clear();
+        self.tracks.clear();
+        self.open_artist = None;
+        self.selected_track.select(None);
+        self.cover_art = None;
+        self.metadata = None;
+        self.lyrics = (String::new(), vec![]);
+        self.active_song_id = String::new();
+        self.scrobble_this = (String::new(), 0);
+
+        if let Some(websocket) = self.websocket.take() {
+            websocket.abort();
+            self.websocket = Some(websocket::spawn(&client, self.action_sender.clone()));
+        }
+        client.max_bitrate = self.streaming.max_bitrate;
+        self.client = Some(client);
+
+        self.refresh_artists().await;
+        self.refresh_home().await;
+        self.restore_queue().await;
+        self.set_message(format!("Switched to profile '{}'", name));
+    }
+
+    async fn refresh_genres(&mut self) {
+        let result = match self.client {
+            Some(ref client) => client.genres().await,
+            None => return,
+        };
+        match result {
+            Ok(genres) => self.genres = genres,
+            Err(e) => {
+                self.set_message(format!("Failed to fetch genres: {}", e));
+                return;
+            }
+        }
+        clamp_selection(&mut self.selected_genre, self.genres.len());
+    }
+
+    /// Lists the artists and albums of a genre in the left pane and all of its tracks in the
+    /// track list
+    async fn open_genre(&mut self, genre: Genre) {
+        let client = match self.client {
+            Some(ref client) => client,
+            None => return,
+        };
+        let items = match client.genre_items(&genre.id).await {
+            Ok(items) => items,
+            Err(e) => {
+                self.set_message(format!("Failed to fetch '{}': {}", genre.name, e));
+                return;
+            }
+        };
+        match client.genre_tracks(&genre.id, self.sorting.tracks, None).await {
+            Ok(tracks) => {
+                self.tracks = tracks.items;
+                self.open_playlist = None;
+                self.open_artist = None;
+                self.selected_track.select(Some(0));
+            }
+            Err(e) => self.set_message(format!("Failed to fetch tracks of '{}': {}", genre.name, e)),
+        }
+        self.genre_items = items;
+        self.open_genre = Some(genre);
+        self.selected_genre_item.select(Some(0));
+    }
+
+    /// Replaces the queue with a shuffled selection of a genre: the one selected or open in the
+    /// genres list, or the first genre of the selected track
+    async fn play_genre(&mut self) {
+        let genre = match (self.active_section, self.left_view) {
+            (ActiveSection::Artists, LeftView::Genres) => match self.open_genre {
+                Some(ref genre) => Some((genre.id.clone(), genre.name.clone())),
+                None => {
+                    let selected = self.selected_genre.selected().unwrap_or(0);
+                    self.genres.get(selected).map(|genre| (genre.id.clone(), genre.name.clone()))
+                }
+            },
+            (ActiveSection::Tracks, _) => {
+                let selected = self.selected_track.selected().unwrap_or(0);
+                self.tracks
+                    .get(selected)
+                    .and_then(|track| track.genre_items.first())
+                    .map(|genre| (genre.id.clone(), genre.name.clone()))
+            }
+            _ => None,
+        };
+        let (id, name) = match genre {
+            Some(genre) => genre,
+            None => {
+                self.set_message(String::from("Select a genre or a track first"));
+                return;
+            }
+        };
+
+        let result = match self.client {
+            Some(ref client) => client.genre_tracks(&id, SortBy::Random, Some(GENRE_QUEUE_LENGTH)).await,
+            None => return,
+        };
+        match result {
+            Ok(tracks) if !tracks.items.is_empty() => {
+                let ids = tracks.items.into_iter().map(|track| track.id).collect::<Vec<String>>();
+                self.set_message(format!("Playing {} shuffled", name));
+                // through dispatch, so it reaches a daemon or a controlled session too
+                Box::pin(self.dispatch(Action::PlayIds(ids))).await;
+            }
+            Ok(_) => self.set_message(format!("No tracks in '{}'", name)),
+            Err(e) => self.set_message(format!("Failed to fetch tracks of '{}': {}", name, e)),
+        }
+    }
+
+    /// The item a radio started now would be based on: whatever is selected in the focused pane
+    fn radio_seed(&self) -> Option<String> {
+        match self.active_section {
+            ActiveSection::Tracks => {
+                let selected = self.selected_track.selected().unwrap_or(0);
+                self.tracks.get(selected).map(|track| track.id.clone())
+            }
+            ActiveSection::Queue => {
+                let selected = self.selected_queue_item.selected().unwrap_or(0);
+                self.playlist.get(selected).map(|song| song.id.clone())
+            }
+            ActiveSection::Artists => match self.left_view {
+                LeftView::Artists => {
+                    let selected = self.selected_artist.selected().unwrap_or(0);
+                    self.artists.get(selected).map(|artist| artist.id.clone())
+                }
+                LeftView::Playlists => {
+                    let selected = self.selected_playlist.selected().unwrap_or(0);
+                    self.playlists.get(selected).map(|playlist| playlist.id.clone())
+                }
+                LeftView::Favorites => {
+                    let selected = self.selected_favorite.selected().unwrap_or(0);
+                    self.favorites.get(selected).map(|favorite| favorite.id.clone())
+                }
+                LeftView::Genres => match self.open_genre {
+                    Some(ref genre) => match self.selected_genre_item.selected().unwrap_or(0) {
+                        0 => Some(genre.id.clone()),
+                        selected => self.genre_items.get(selected - 1).map(|item| item.id.clone()),
+                    },
+                    None => {
+                        let selected = self.selected_genre.selected().unwrap_or(0);
+                        self.genres.get(selected).map(|genre| genre.id.clone())
+                    }
+                },
+                LeftView::Home => {
+                    let selected = self.selected_home.selected().unwrap_or(0);
+                    self.home.get(selected).map(|(_, item)| item.id.clone())
+                }
+                LeftView::Sessions | LeftView::Libraries | LeftView::Profiles => None,
+            },
+        }
+    }
+
+    /// Replaces the queue with an Instant Mix of an item and keeps it going
+    async fn start_radio(&mut self, seed: &str) {
+        let client = match self.client {
+            Some(ref client) => client,
+            None => return,
+        };
+        match client.instant_mix(seed, RADIO_BATCH).await {
+            Ok(tracks) if !tracks.items.is_empty() => {
+                let songs = tracks
+                    .items
+                    .iter()
+                    .map(|track| Song::from_track(client, track))
+                    .collect::<Vec<Song>>();
+                self.set_message(format!("Radio started with {} tracks, more follow as it plays", songs.len()));
+                self.play(songs);
+                self.radio = true;
+            }
+            Ok(_) => self.set_message(String::from("The server has no mix for this")),
+            Err(e) => self.set_message(format!("Failed to start the radio: {}", e)),
+        }
+    }
+
+    /// Appends the next batch of the radio once the queue is nearly through. Each batch is a mix
+    /// of one of the last songs, so the radio drifts along instead of repeating itself
+    async fn refill_radio(&mut self) {
+        let index = self.current_playback_state.current_index.max(0) as usize;
+        if index + RADIO_REFILL_AT < self.playlist.len() || Instant::now() < self.radio_refill {
+            return;
+        }
+        let client = match self.client {
+            Some(ref client) => client,
+            None => return,
+        };
+        let recent = &self.playlist[self.playlist.len().saturating_sub(RADIO_REFILL_AT)..];
+        let seed = match recent.choose(&mut rand::thread_rng()) {
+            Some(song) => song.id.clone(),
+            None => {
+                self.radio = false;
+                return;
+            }
+        };
+
+        let songs = match client.instant_mix(&seed, RADIO_BATCH).await {
+            Ok(tracks) => tracks
+                .items
+                .iter()
+                .filter(|track| !self.playlist.iter().any(|song| song.id == track.id))
+                .map(|track| Song::from_track(client, track))
+                .collect::<Vec<Song>>(),
+            Err(_) => vec![],
+        };
+        if songs.is_empty() {
+            // nothing new from this one, try another seed in a while
+            self.radio_refill = Instant::now() + Duration::from_secs(30);
+            return;
+        }
+        self.enqueue(songs);
+    }
+
+    /// Favorites or unfavorites whatever is selected. The change shows right away and is
+    /// undone if the server refuses it
+    async fn toggle_favorite(&mut self) {
+        let (id, name, favorite) = match (self.active_section, self.left_view) {
+            (ActiveSection::Tracks, _) => {
+                let selected = self.selected_track.selected().unwrap_or(0);
+                match self.tracks.get(selected) {
+                    Some(track) => (track.id.clone(), track.name.clone(), !track.user_data.is_favorite),
+                    None => return,
+                }
+            }
+            (ActiveSection::Artists, LeftView::Artists) => {
+                let selected = self.selected_artist.selected().unwrap_or(0);
+                match self.artists.get(selected) {
+                    Some(artist) => (artist.id.clone(), artist.name.clone(), !artist.user_data.is_favorite),
+                    None => return,
+                }
+            }
+            (ActiveSection::Artists, LeftView::Favorites) => {
+                let selected = self.selected_favorite.selected().unwrap_or(0);
+                match self.favorites.get(selected) {
+                    Some(favorite) => (favorite.id.clone(), favorite.name.clone(), false),
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+
+        self.set_local_favorite(&id, favorite);
+        let result = match self.client {
+            Some(ref client) => client.set_favorite(&id, favorite).await,
+            None => return,
+        };
+        match result {
+            Ok(()) if self.left_view == LeftView::Favorites => self.refresh_favorites().await,
+            Ok(()) => {}
+            Err(e) => {
+                self.set_local_favorite(&id, !favorite);
+                self.set_message(format!("Failed to update '{}': {}", name, e));
+                if self.left_view == LeftView::Favorites {
+                    self.refresh_favorites().await;
+                }
+            }
+        }
+    }
+
+    /// Updates the favorite flag everywhere the item is shown
+    fn set_local_favorite(&mut self, id: &str, favorite: bool) {
+        for track in self.tracks.iter_mut().filter(|track| track.id == id) {
+            track.user_data.is_favorite = favorite;
+        }
+        for artist in self.artists.iter_mut().filter(|artist| artist.id == id) {
+            artist.user_data.is_favorite = favorite;
+        }
+        if !favorite {
+            self.favorites.retain(|f| f.id != id);
+            let len = self.favorites.len();
+            if self.selected_favorite.selected().is_some_and(|selected| selected >= len) {
+                self.selected_favorite.select(len.checked_sub(1));
+            }
+        }
+    }
+
+    /// Keys while the sort menu is open: move, pick, or close it
+    async fn sort_menu_action(&mut self, action: Action) {
+        let (section, state) = match self.sort_menu {
+            Some((section, ref mut state)) => (section, state),
+            None => return,
+        };
+        let last = SortBy::ALL.len(); // the artists entry comes after the sort orders
+        let selected = state.selected().unwrap_or(0);
+        match action {
+            Action::Down => state.select(Some((selected + 1).min(last))),
+            Action::Up => state.select(Some(selected.saturating_sub(1))),
+            Action::Top => state.select(Some(0)),
+            Action::Bottom => state.select(Some(last)),
+            Action::Select => {
+                self.sort_menu = None;
+                match SortBy::ALL.get(selected) {
+                    Some(sort_by) => match section {
+                        ActiveSection::Artists => {
+                            self.sorting.artists = *sort_by;
+                            self.refresh_artists().await;
+                        }
+                        _ => {
+                            self.sorting.tracks = *sort_by;
+                            self.sort_tracks().await;
+                        }
+                    },
+                    None => {
+                        self.sorting.album_artists = !self.sorting.album_artists;
+                        self.refresh_artists().await;
+                        if self.open_artist.is_some() {
+                            self.sort_tracks().await;
+                        }
+                    }
+                }
+                if let Err(e) = self.sorting.save() {
+                    self.set_message(format!("Failed to save sorting: {}", e));
+                }
+            }
+            Action::SortMenu | Action::Quit | Action::Help => self.sort_menu = None,
+            _ => {}
+        }
+    }
+
+    /// Fetches the artists again in the chosen order, keeping the selected one selected
+    async fn refresh_artists(&mut self) {
+        let result = match self.client {
+            Some(ref client) => client.artists(&self.sorting).await,
+            None => return,
+        };
+        match result {
+            Ok(artists) => {
+                let selected = self.selected_artist.selected().and_then(|i| self.artists.get(i)).map(|a| a.id.clone());
+                self.artists = artists;
+                let index = selected.and_then(|id| self.artists.iter().position(|a| a.id == id));
+                self.selected_artist.select(Some(index.unwrap_or(0)));
+            }
+            Err(e) => self.set_message(format!("Failed to fetch artists: {}", e)),
+        }
+    }
+
+    /// Applies the track sort: an artist's tracks are fetched again, anything else (search
+    /// results, playlists, ...) is sorted here
+    async fn sort_tracks(&mut self) {
+        if let Some(id) = self.open_artist.clone() {
+            self.discography(&id).await;
+            self.selected_track.select(Some(0));
+            return;
+        }
+        match self.sorting.tracks.local_key() {
+            Some(key) => {
+                let _ = columns::sort(&mut self.tracks, key, false);
+            }
+            None => self.tracks.shuffle(&mut rand::thread_rng()),
+        }
+        self.playlist_in_order = false;
+        self.selected_track.select(Some(0));
+    }
+
+    /// For every track, the disc number to show above it. Only the first track of each disc of
+    /// a multi-disc album gets one, and only while each album's tracks are together
+    fn disc_headers(&self) -> Vec<Option<u64>> {
+        let mut headers = vec![None; self.tracks.len()];
+        let mut seen = HashSet::new();
+        let mut multi_disc = HashSet::new();
+        for (i, track) in self.tracks.iter().enumerate() {
+            let previous = i.checked_sub(1).map(|i| &self.tracks[i]);
+            if previous.map(|p| &p.album_id) != Some(&track.album_id) && !seen.insert(&track.album_id) {
+                // an album shows up twice, the list is not grouped by album
+                return vec![None; self.tracks.len()];
+            }
+            if track.parent_index_number > 1 {
+                multi_disc.insert(&track.album_id);
+            }
+        }
+        for (i, track) in self.tracks.iter().enumerate() {
+            if track.parent_index_number == 0 || !multi_disc.contains(&track.album_id) {
+                continue;
+            }
+            let previous = i.checked_sub(1).map(|i| &self.tracks[i]);
+            let same_disc = previous.is_some_and(|p| {
+                p.album_id == track.album_id && p.parent_index_number == track.parent_index_number
+            });
+            if !same_disc {
+                headers[i] = Some(track.parent_index_number);
+            }
+        }
+        headers
+    }
+
+    /// Fetch the discography of an artist
+    /// This will change the active section to tracks
+    async fn discography(&mut self, id: &str) {
+        match self.client {
+            Some(ref client) => {
+                let artist = client.discography(id, &self.sorting).await;
+                match artist {
+                    Ok(artist) => {
+                        self.active_section = ActiveSection::Tracks;
+                        self.tracks = artist.items;
+                        self.open_playlist = None;
+                        self.open_artist = Some(id.to_string());
+                    }
+                    Err(e) => {
+                        println!("Failed to get discography: {:?}", e);
+                    }
+                }
+            }
+            None => {} // this would be bad
+        }
+    }
+
+    /// Replaces the queue and starts playing its first song
+    fn play(&mut self, songs: Vec<Song>) {
+        self.mpv_state.lock().unwrap().should_stop = true;
+        self.playlist = songs;
+        self.radio = false;
+        if self.shuffle && !self.playlist.is_empty() {
+            // the first track still plays first
+            self.unshuffled = self.playlist.clone();
+            self.playlist[1..].shuffle(&mut rand::thread_rng());
+        }
+        self.replace_playlist();
+    }
+
+    /// Stops playback and empties the queue
+    fn clear_queue(&mut self) {
+        let mut mpv = self.mpv_state.lock().unwrap();
+        mpv.should_stop = true;
+        let _ = mpv.mpv.command("stop", &[]);
+        drop(mpv);
+        self.playlist.clear();
+        self.unshuffled.clear();
+        self.radio = false;
+        self.current_playback_state = MpvPlaybackState {
+            percentage: 0.0,
+            duration: 0.0,
+            current_index: 0,
+            paused: false,
+        };
+        self.selected_queue_item.select(None);
+    }
+
+    fn play_index(&mut self, index: usize) {
+        if index < self.playlist.len() {
+            let mpv = self.mpv_state.lock().unwrap();
+            let _ = mpv.mpv.command("playlist-play-index", &[&index.to_string()]);
+        }
+    }
+
+    /// Adds songs to the end of the queue, starting playback if the queue was empty
+    fn enqueue(&mut self, songs: Vec<Song>) {
+        if self.playlist.is_empty() {
+            self.playlist = songs;
+            self.replace_playlist();
+            return;
+        }
+
+        // append to the running mpv playlist without interrupting playback
+        let mpv = self.mpv_state.lock().unwrap();
+        let _ = mpv.mpv.playlist_load_files(
+            &songs
+                .iter()
+                .map(|song| (song.url.as_str(), FileState::AppendPlay, None))
+                .collect::<Vec<(&str, FileState, Option<&str>)>>(),
+        );
+        drop(mpv);
+        if self.shuffle {
+            self.unshuffled.extend(songs.iter().cloned());
+        }
+        self.playlist.extend(songs);
+    }
+
+    /// Writes the queue to the state directory, `last_played` is explained in queue.rs
+    fn save_queue(&mut self, last_played: String) {
+        self.queue_saved = Instant::now();
+        let saved = SavedQueue {
+            songs: self.playlist.clone(),
+            unshuffled: self.unshuffled.clone(),
+            index: self.current_playback_state.current_index.max(0) as usize,
+            position: self.current_playback_state.duration * self.current_playback_state.percentage / 100.0,
+            shuffle: self.shuffle,
+            repeat: self.repeat,
+            volume: self.volume,
+            muted: self.muted,
+            last_played,
+        };
+        let profile = match self.client {
+            Some(ref client) => &client.profile,
+            None => return,
+        };
+        if let Err(e) = queue::save(profile, &saved) {
+            self.set_message(format!("Failed to save the queue: {}", e));
+        }
+    }
+
+    /// Reports the current song stopped, so the server knows where we left off, and saves
+    /// the queue one last time
+    pub async fn save_queue_on_exit(&mut self) {
+        if self.daemon.is_some() {
+            return;
+        }
+        let mut last_played = String::new();
+        if let (Some(client), Some(song)) = (
+            &self.client,
+            self.playlist.get(self.current_playback_state.current_index.max(0) as usize),
+        ) {
+            let ticks = (self.current_playback_state.duration * self.current_playback_state.percentage * 100000.0) as u64;
+            if self.scrobbling.enabled {
+                let _ = client.stopped(song.id.clone(), ticks).await;
+            }
+            if let Ok(user_data) = client.user_data(&song.id).await {
+                last_played = user_data.last_played_date.unwrap_or_default();
+            }
+        }
+        self.save_queue(last_played);
+    }
+
+    /// Puts back the queue of the last run, paused where it was left. If another device played
+    /// the song since, its position is used instead
+    pub async fn restore_queue(&mut self) {
+        let client = match self.client {
+            Some(ref client) => client,
+            None => return,
+        };
+        let saved = match queue::load(&client.profile) {
+            Some(saved) if !saved.songs.is_empty() => saved,
+            _ => return,
+        };
+        let index = saved.index.min(saved.songs.len() - 1);
+
+        let mut position = saved.position;
+        let mut message = None;
+        if !saved.last_played.is_empty() {
+            if let Ok(user_data) = client.user_data(&saved.songs[index].id).await {
+                if user_data.last_played_date.unwrap_or_default() != saved.last_played {
+                    position = user_data.playback_position_ticks as f64 / 10_000_000.0;
+                    message = Some(String::from("Resuming where another device left off"));
+                }
+            }
+        }
+
+        let with_urls = |songs: Vec<Song>| {
+            songs
+                .into_iter()
+                .map(|song| Song { url: client.song_url_sync(song.id.clone()), ..song })
+                .collect::<Vec<Song>>()
+        };
+        self.playlist = with_urls(saved.songs);
+        self.unshuffled = with_urls(saved.unshuffled);
+        self.shuffle = saved.shuffle;
+        self.repeat = saved.repeat;
+        self.volume = saved.volume;
+        self.muted = saved.muted;
+        self.paused = true;
+        self.start_playlist(index, position, true);
+        if let Some(message) = message {
+            self.set_message(message);
+        }
+    }
+
+    /// Persists the pane layout. Moves focus away from a pane that was just hidden
+    fn save_layout(&mut self) {
+        if !self.panes.is_visible(self.active_section, self.screen_width) {
+            self.active_section = ActiveSection::Tracks;
+        }
+        if let Err(e) = self.panes.save() {
+            self.set_message(format!("Failed to save layout: {}", e));
+        }
+    }
+
+    fn update_remote_status(&mut self) {
+        let index = self.current_playback_state.current_index as usize;
+        let song = self.playlist.get(index).cloned();
+        let mut status = remote::Status {
+            position: match song {
+                Some(_) => self.current_playback_state.duration * self.current_playback_state.percentage / 100.0,
+                None => 0.0,
+            },
+            duration: self.current_playback_state.duration,
+            song,
+            paused: self.paused,
+            volume: self.volume,
+            muted: self.muted,
+            shuffle: self.shuffle,
+            repeat: self.repeat,
+            queue_position: index,
+            queue_length: self.playlist.len(),
+            queue_version: 0,
+            queue: vec![],
+        };
+        self.remote_status.send_if_modified(|current| {
+            let queue_changed = current.queue != self.playlist;
+            status.queue_version = current.queue_version + queue_changed as u64;
+            if *current == status && !queue_changed {
+                return false;
+            }
+            if queue_changed {
+                current.queue = self.playlist.clone();
+            }
+            status.queue = std::mem::take(&mut current.queue);
+            *current = status;
+            true
+        });
+    }
+
+    /// Sends the player state to the MPRIS thread if anything changed
+    fn update_mpris(&mut self) {
+        let sender = match self.mpris {
+            Some(ref sender) => sender,
+            None => return,
+        };
+        let mut status = mpris::Status {
+            paused: self.paused,
+            volume: self.volume,
+            shuffle: self.shuffle,
+            repeat: self.repeat,
+            ..Default::default()
+        };
+        if let Some(song) = self.playlist.get(self.current_playback_state.current_index as usize) {
+            status.song_id = song.id.clone();
+            status.title = song.name.clone();
+            status.artist = song.artist.clone();
+            status.album = song.album.clone();
+            status.length = self.current_playback_state.duration;
+            status.position = self.current_playback_state.duration * self.current_playback_state.percentage / 100.0;
+            if let Some(ref client) = self.client {
+                status.art_url = format!("{}/Items/{}/Images/Primary", client.base_url, song.parent_id);
+            }
+        }
+        if status != self.mpris_status {
+            if sender.send(status.clone()).is_err() {
+                // no session bus, don't bother again
+                self.mpris = None;
+            }
+            self.mpris_status = status;
+        }
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        let mpv = self.mpv_state.lock().unwrap();
+        let _ = mpv.mpv.set_property("mute", muted);
+    }
+
+    fn set_volume(&mut self, volume: i64) {
+        self.volume = volume.clamp(0, 100);
+        let mpv = self.mpv_state.lock().unwrap();
+        let _ = mpv.mpv.set_property("volume", self.volume);
+    }
+
+    /// Shuffles everything after the current song, or puts it back in the original order.
+    /// The current song keeps playing
+    fn toggle_shuffle(&mut self) {
+        self.shuffle = !self.shuffle;
+
+        let current = self.current_playback_state.current_index as usize;
+        if self.playlist.len() <= current + 1 {
+            self.unshuffled = self.playlist.clone();
+            return;
+        }
+
+        let mut upcoming = self.playlist.split_off(current + 1);
+        if self.shuffle {
+            self.unshuffled = self.playlist.iter().chain(upcoming.iter()).cloned().collect();
+            upcoming.shuffle(&mut rand::thread_rng());
+        } else {
+            let original = &self.unshuffled;
+            upcoming.sort_by_key(|song| {
+                original.iter().position(|s| s.id == song.id).unwrap_or(usize::MAX)
+            });
+        }
+
+        // swap out the tail of mpv's playlist
+        let mpv = self.mpv_state.lock().unwrap();
+        for i in (current + 1..current + 1 + upcoming.len()).rev() {
+            let _ = mpv.mpv.playlist_remove_index(i);
+        }
+        let _ = mpv.mpv.playlist_load_files(
+            &upcoming
+                .iter()
+                .map(|song| (song.url.as_str(), FileState::Append, None))
+                .collect::<Vec<(&str, FileState, Option<&str>)>>(),
+        );
+        drop(mpv);
+
+        self.playlist.extend(upcoming);
+    }
+
+    fn replace_playlist(&mut self) {
+        self.start_playlist(0, 0.0, false);
+    }
+
+    /// Hands the queue to a fresh mpv, starting `position` seconds into song `index`
+    fn start_playlist(&mut self, index: usize, position: f64, paused: bool) {
+        let _ = {
+            self.mpv_state = Arc::new(Mutex::new(MpvState::new(self.volume))); // Shared state for controlling MPV
+            let mpv = self.mpv_state.lock().unwrap();
+            mpv.set_repeat(self.repeat);
+            let _ = mpv.mpv.set_property("mute", self.muted);
+            let _ = mpv.mpv.set_property("pause", paused);
             drop(mpv);
+            let mpv_state = self.mpv_state.clone();
+            let sender = self.sender.clone();
+            let songs = self.playlist.clone();
+            // println!("Playing playlist: {:?}", songs);
+
+            self.mpv_thread = Some(thread::spawn(move || {
+                Self::t_playlist(songs, mpv_state, sender, index, position)
+            }));
+        };
+    }
+
+    fn t_playlist(
+        songs: Vec<Song>,
+        mpv_state: Arc<Mutex<MpvState>>,
+        sender: Sender<MpvPlaybackState>,
+        start_index: usize,
+        start_position: f64,
+    ) {
+        {
+            let lock = mpv_state.clone();
+            let mpv = match lock.lock() {
+                Ok(mpv) => mpv,
+                Err(_) => {
+                    return;
+                }
+            };
+
+            match mpv.mpv.playlist_clear() {
+                Ok(_) => {}
+                Err(_) => {}
+            }
+
+            mpv.mpv
+                .playlist_load_files(
+                    &songs
+                        .iter()
+                        .map(|song| (song.url.as_str(), FileState::AppendPlay, None))
+                        .collect::<Vec<(&str, FileState, Option<&str>)>>()
+                        .as_slice(),
+                )
+                .unwrap();
+            if start_index > 0 {
+                let _ = mpv.mpv.set_property("playlist-pos", start_index as i64);
+            }
+
+            drop(mpv);
+            // seeking only works once the file is loaded
+            let mut pending_seek = match start_position > 0.0 {
+                true => Some(start_position),
+                false => None,
+            };
 
             loop {
                 // main mpv loop
@@ -1014,6 +3571,13 @@ impl App {
                 let percentage = mpv.mpv.get_property("percent-pos").unwrap_or(0.0);
                 let current_index: i64 = mpv.mpv.get_property("playlist-pos").unwrap_or(0);
                 let duration = mpv.mpv.get_property("duration").unwrap_or(0.0);
+                let paused = mpv.mpv.get_property("pause").unwrap_or(false);
+                if let Some(position) = pending_seek {
+                    if duration > 0.0 && current_index == start_index as i64 {
+                        let _ = mpv.mpv.seek_absolute(position);
+                        pending_seek = None;
+                    }
+                }
 
                 // println!("Playlist pos: {:?}", pos);
                 drop(mpv);
@@ -1023,6 +3587,7 @@ impl App {
                             percentage,
                             duration,
                             current_index,
+                            paused,
                         }
                     })
                     .unwrap();
@@ -1032,29 +3597,164 @@ impl App {
         }
     }
 
-    fn handle_mouse_event(&mut self, _mouse_event: crossterm::event::MouseEvent) {
-        // println!("Mouse event: {:?}", _mouse_event);
+    /// Click focuses a pane and selects a row, double click acts like Enter.
+    /// The wheel scrolls the pane under the cursor, clicking the progress bar seeks
+    async fn handle_mouse_event(&mut self, mouse_event: MouseEvent) {
+        if self.command_line.active || self.settings.is_some() {
+            return;
+        }
+        if self.show_help || self.sort_menu.is_some() {
+            if let MouseEventKind::Down(_) = mouse_event.kind {
+                self.show_help = false;
+                self.sort_menu = None;
+            }
+            return;
+        }
+
+        let position = layout::Position::new(mouse_event.column, mouse_event.row);
+        let section = if self.areas.artists.contains(position) {
+            Some(ActiveSection::Artists)
+        } else if self.areas.tracks.contains(position) {
+            Some(ActiveSection::Tracks)
+        } else if self.areas.queue.contains(position) {
+            Some(ActiveSection::Queue)
+        } else {
+            None
+        };
+
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                if self.areas.progress.contains(position) {
+                    self.seek_to_ratio(position.x);
+                    return;
+                }
+                let section = match section {
+                    Some(section) => section,
+                    None => return,
+                };
+                self.focus(section);
+
+                let left_len = self.left_len();
+                let headers = self.disc_headers();
+                let home_headers = self.home_headers();
+                let left_view = self.left_view;
+                let (area, state, len) = match section {
+                    ActiveSection::Artists => (self.areas.artists, self.left_state(), left_len),
+                    ActiveSection::Tracks => (self.areas.tracks, &mut self.selected_track, self.tracks.len()),
+                    ActiveSection::Queue => (self.areas.queue, &mut self.selected_queue_item, self.playlist.len()),
+                };
+                // first row is the border
+                if position.y <= area.y || position.y >= area.bottom().saturating_sub(1) {
+                    return;
+                }
+                let row = (position.y - area.y - 1) as usize;
+                let index = match section {
+                    ActiveSection::Tracks => item_at_row(&headers, state.offset(), row),
+                    ActiveSection::Artists if left_view == LeftView::Home => {
+                        item_at_row(&home_headers, state.offset(), row)
+                    }
+                    _ => state.offset() + row,
+                };
+                if index >= len {
+                    return;
+                }
+                state.select(Some(index));
+
+                let double_click = match self.last_click {
+                    Some((at, s, i)) => {
+                        s == section && i == index && at.elapsed() < Duration::from_millis(400)
+                    }
+                    None => false,
+                };
+                if double_click {
+                    self.last_click = None;
+                    self.dispatch(Action::Select).await;
+                } else {
+                    self.last_click = Some((Instant::now(), section, index));
+                }
+            }
+            MouseEventKind::ScrollDown | MouseEventKind::ScrollUp => {
+                if let Some(section) = section {
+                    self.focus(section);
+                    match mouse_event.kind {
+                        MouseEventKind::ScrollDown => self.dispatch(Action::Down).await,
+                        _ => self.dispatch(Action::Up).await,
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Makes a pane the active one, remembering where we came from like toggle_section does
+    fn focus(&mut self, section: ActiveSection) {
+        if section == ActiveSection::Queue && self.active_section != ActiveSection::Queue {
+            self.last_section = self.active_section;
+        }
+        self.active_section = section;
+    }
+
+    /// Seeks to the point of the progress bar at column x
+    fn seek_to_ratio(&mut self, x: u16) {
+        let area = self.areas.progress;
+        if area.width == 0 || self.current_playback_state.duration == 0.0 {
+            return;
+        }
+        let ratio = (x - area.x) as f64 / area.width as f64;
+        let mpv = self.mpv_state.lock().unwrap();
+        let _ = mpv.mpv.seek_absolute(self.current_playback_state.duration * ratio);
     }
     fn exit(&mut self) {
         self.exit = true;
     }
 }
 
-struct Controls {}
-impl Widget for &Controls {
+/// The item shown on a row of a list, counting from the first visible item. Items with a
+/// header above them (disc numbers, home sections) take two rows
+fn item_at_row<T>(headers: &[Option<T>], offset: usize, row: usize) -> usize {
+    let mut top = 0;
+    for (index, header) in headers.iter().enumerate().skip(offset) {
+        let height = if header.is_some() { 2 } else { 1 };
+        if row < top + height {
+            return index;
+        }
+        top += height;
+    }
+    headers.len()
+}
+
+/// Keeps a list's selection on an item after the list was reloaded: the last one if it got
+/// shorter, the first one if nothing was selected, none if it is empty
+fn clamp_selection(state: &mut ListState, len: usize) {
+    match state.selected() {
+        _ if len == 0 => state.select(None),
+        Some(selected) if selected >= len => state.select(Some(len - 1)),
+        None => state.select(Some(0)),
+        _ => {}
+    }
+}
+
+struct Controls<'a> {
+    theme: &'a Theme,
+    keymap: &'a KeyMap,
+}
+impl Widget for &Controls<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let instructions = Title::from(Line::from(vec![
-            " Play/Pause ".white().into(),
-            "<Space>".blue().bold(),
-            " Seek+5s ".white().into(),
-            "<S>".blue().bold(),
-            " Seek-5s ".white().into(),
-            "<R>".blue().bold(),
-            " Next Section ".white().into(),
-            "<Tab>".blue().bold(),
-            " Quit ".white().into(),
-            "<Q> ".blue().bold(),
-        ]));
+        let hints = [
+            Action::PlayPause,
+            Action::SeekForward,
+            Action::SeekBackward,
+            Action::NextSection,
+            Action::Help,
+            Action::Quit,
+        ];
+        let mut spans = vec![];
+        for action in hints {
+            spans.push(Span::styled(format!(" {} ", action.description()), self.theme.hint_text));
+            spans.push(Span::styled(self.keymap.hint(&action), self.theme.hint_key));
+        }
+        spans.push(Span::raw(" "));
+        let instructions = Title::from(Line::from(spans));
         Block::default()
             .title("Track")
             .title(
@@ -1063,7 +3763,7 @@ impl Widget for &Controls {
                     .position(Position::Bottom),
             )
             .borders(Borders::ALL)
-            .border_set(border::THICK)
+            .border_set(self.theme.controls_border_type)
             .render(area, buf);
     }
 }
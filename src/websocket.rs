@@ -0,0 +1,258 @@
+//! SERVER WEBSOCKET
+//!
+//! Keeps a WebSocket open to the server's /socket endpoint so that other Jellyfin clients (the
+//! web UI, the phone apps) can use this player as a cast target. After connecting, the session
+//! advertises its capabilities through /Sessions/Capabilities/Full, the server then forwards
+//! remote control messages:
+//!
+//! {"MessageType": "Play", "Data": {"ItemIds": [...], "StartIndex": 0, "PlayCommand": "PlayNow"}}
+//! {"MessageType": "Playstate", "Data": {"Command": "Seek", "SeekPositionTicks": 600000000}}
+//! {"MessageType": "GeneralCommand", "Data": {"Name": "SetVolume", "Arguments": {"Volume": "40"}}}
+//!
+//! Each becomes an Action for App::perform. The connection is re-established when it drops.
+use crate::client::{report_capabilities, Client};
+use crate::keymap::Action;
+use futures_util::{SinkExt, StreamExt};
+use serde_json::Value;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+const MIN_RETRY: Duration = Duration::from_secs(5);
+const MAX_RETRY: Duration = Duration::from_secs(120);
+
+/// ws://host/socket?api_key=...&deviceId=... for a server at http://host
+pub fn socket_url(base_url: &str, access_token: &str, device_id: &str) -> String {
+    let base_url = base_url.trim_end_matches('/');
+    let base_url = match base_url.strip_prefix("https://") {
+        Some(rest) => format!("wss://{}", rest),
+        None => format!("ws://{}", base_url.trim_start_matches("http://")),
+    };
+    format!("{}/socket?api_key={}&deviceId={}", base_url, access_token, device_id)
+}
+
+/// Connects in the background and keeps reconnecting until the app exits, or the returned task
+/// is aborted when switching servers
+pub fn spawn(client: &Client, actions: Sender<Action>) -> JoinHandle<()> {
+    let (base_url, access_token, device_id) =
+        (client.base_url.clone(), client.access_token.clone(), client.device_id.clone());
+    tokio::spawn(async move {
+        let mut retry = MIN_RETRY;
+        loop {
+            if let Ok(()) = session(&base_url, &access_token, &device_id, &actions).await {
+                // a clean close by the server, probably a restart
+                retry = MIN_RETRY;
+            }
+            tokio::time::sleep(retry).await;
+            retry = (retry * 2).min(MAX_RETRY);
+        }
+    })
+}
+
+/// One connection, from connecting until it closes
+async fn session(base_url: &str, access_token: &str, device_id: &str, actions: &Sender<Action>) -> Result<(), String> {
+    let (socket, _) = tokio_tungstenite::connect_async(socket_url(base_url, access_token, device_id))
+        .await
+        .map_err(|e| e.to_string())?;
+    let (mut write, mut read) = socket.split();
+
+    // the session exists once the socket is open, now it can be made controllable. Without
+    // this nothing will be sent our way, but the socket itself is still fine, so it is left to
+    // finish or fail on its own instead of holding up reading
+    tokio::spawn(report_capabilities(base_url.to_string(), access_token.to_string(), device_id.to_string()));
+
+    // the server tells us how often it wants to hear from us with ForceKeepAlive
+    let mut keep_alive = tokio::time::interval(Duration::from_secs(30));
+    loop {
+        tokio::select! {
+            message = read.next() => {
+                let text = match message {
+                    Some(Ok(Message::Text(text))) => text,
+                    Some(Ok(Message::Close(_))) | None => return Ok(()),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return Err(e.to_string()),
+                };
+                let message: Value = match serde_json::from_str(text.as_str()) {
+                    Ok(message) => message,
+                    Err(_) => continue,
+                };
+                if message["MessageType"] == "ForceKeepAlive" {
+                    let seconds = message["Data"].as_u64().unwrap_or(60).max(2);
+                    keep_alive = tokio::time::interval(Duration::from_secs(seconds / 2));
+                    continue;
+                }
+                if let Some(action) = actions_for(&message) {
+                    if actions.send(action).is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            _ = keep_alive.tick() => {
+                let message = serde_json::json!({ "MessageType": "KeepAlive" }).to_string();
+                write.send(Message::text(message)).await.map_err(|e| e.to_string())?;
+            }
+        }
+    }
+}
+
+/// Translates a message from the server into what the player should do. None for messages
+/// this client has nothing to do with
+pub fn actions_for(message: &Value) -> Option<Action> {
+    let data = &message["Data"];
+    match message["MessageType"].as_str().unwrap_or("") {
+        "Play" => {
+            let ids = data["ItemIds"]
+                .as_array()
+                .map(|ids| ids.iter().filter_map(|id| id.as_str().map(String::from)).collect::<Vec<String>>())
+                .unwrap_or_default();
+            if ids.is_empty() {
+                return None;
+            }
+            match data["PlayCommand"].as_str().unwrap_or("PlayNow") {
+                "PlayNow" => {
+                    let start = data["StartIndex"].as_u64().unwrap_or(0) as usize;
+                    Some(Action::PlayIds(ids.into_iter().skip(start).collect()))
+                }
+                // there is no inserting after the current song yet, so both go to the end
+                "PlayNext" | "PlayLast" => Some(Action::EnqueueIds(ids)),
+                _ => None,
+            }
+        }
+        "Playstate" => match data["Command"].as_str().unwrap_or("") {
+            "PlayPause" => Some(Action::PlayPause),
+            "Pause" => Some(Action::SetPaused(true)),
+            "Unpause" => Some(Action::SetPaused(false)),
+            "Stop" => Some(Action::ClearQueue),
+            "NextTrack" => Some(Action::Next),
+            "PreviousTrack" => Some(Action::Previous),
+            "Seek" => data["SeekPositionTicks"]
+                .as_u64()
+                .map(|ticks| Action::SeekTo(ticks as f64 / 10_000_000.0)),
+            _ => None,
+        },
+        "GeneralCommand" => {
+            let arguments = &data["Arguments"];
+            match data["Name"].as_str().unwrap_or("") {
+                // arguments are sent as strings
+                "SetVolume" => arguments["Volume"]
+                    .as_str()
+                    .and_then(|v| v.parse::<i64>().ok())
+                    .map(Action::SetVolume),
+                "VolumeUp" => Some(Action::ChangeVolume(5)),
+                "VolumeDown" => Some(Action::ChangeVolume(-5)),
+                "ToggleMute" => Some(Action::ToggleMute),
+                "Mute" => Some(Action::SetMuted(true)),
+                "Unmute" => Some(Action::SetMuted(false)),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::WebSocketStream;
+
+    /// The next text frame the client sent, as JSON
+    async fn next_message(socket: &mut WebSocketStream<TcpStream>) -> Value {
+        loop {
+            match socket.next().await {
+                Some(Ok(Message::Text(text))) => return serde_json::from_str(text.as_str()).unwrap(),
+                Some(Ok(_)) => continue,
+                other => panic!("expected a message, got {:?}", other),
+            }
+        }
+    }
+
+    /// Stands in for the server: waits for a KeepAlive, sends a command and a ForceKeepAlive,
+    /// waits for the KeepAlive that asks for and closes
+    #[tokio::test]
+    async fn session_against_a_local_socket() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            // the first one is sent right away
+            assert_eq!(next_message(&mut socket).await, json!({ "MessageType": "KeepAlive" }));
+
+            let play_pause = json!({ "MessageType": "Playstate", "Data": { "Command": "PlayPause" } });
+            socket.send(Message::text(play_pause.to_string())).await.unwrap();
+            let force = json!({ "MessageType": "ForceKeepAlive", "Data": 2 });
+            socket.send(Message::text(force.to_string())).await.unwrap();
+            // without ForceKeepAlive the next one would take 30 seconds
+            let again = tokio::time::timeout(Duration::from_secs(5), next_message(&mut socket)).await;
+            assert_eq!(again.ok(), Some(json!({ "MessageType": "KeepAlive" })));
+
+            socket.send(Message::Close(None)).await.unwrap();
+        });
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let result = tokio::time::timeout(Duration::from_secs(10), session(&base_url, "token", "device", &sender)).await;
+        server.await.unwrap();
+        assert_eq!(result.ok(), Some(Ok(())));
+        assert_eq!(receiver.try_iter().collect::<Vec<Action>>(), vec![Action::PlayPause]);
+    }
+
+    #[test]
+    fn play_starts_at_the_start_index() {
+        let message = json!({
+            "MessageType": "Play",
+            "Data": { "ItemIds": ["a", "b", "c"], "PlayCommand": "PlayNow", "StartIndex": 1 },
+        });
+        assert_eq!(actions_for(&message), Some(Action::PlayIds(vec![String::from("b"), String::from("c")])));
+    }
+
+    #[test]
+    fn play_next_and_last_enqueue() {
+        for command in ["PlayNext", "PlayLast"] {
+            let message = json!({ "MessageType": "Play", "Data": { "ItemIds": ["a"], "PlayCommand": command } });
+            assert_eq!(actions_for(&message), Some(Action::EnqueueIds(vec![String::from("a")])));
+        }
+    }
+
+    #[test]
+    fn playstate_commands() {
+        let playstate = |command: &str, data: Value| {
+            let mut message = json!({ "MessageType": "Playstate", "Data": data });
+            message["Data"]["Command"] = json!(command);
+            actions_for(&message)
+        };
+        assert_eq!(playstate("Pause", json!({})), Some(Action::SetPaused(true)));
+        assert_eq!(playstate("NextTrack", json!({})), Some(Action::Next));
+        assert_eq!(playstate("Seek", json!({ "SeekPositionTicks": 300_000_000u64 })), Some(Action::SeekTo(30.0)));
+        assert_eq!(playstate("Seek", json!({})), None);
+    }
+
+    #[test]
+    fn general_commands() {
+        let message = json!({
+            "MessageType": "GeneralCommand",
+            "Data": { "Name": "SetVolume", "Arguments": { "Volume": "40" } },
+        });
+        assert_eq!(actions_for(&message), Some(Action::SetVolume(40)));
+        let message = json!({ "MessageType": "GeneralCommand", "Data": { "Name": "ToggleMute" } });
+        assert_eq!(actions_for(&message), Some(Action::ToggleMute));
+    }
+
+    #[test]
+    fn unknown_and_malformed_messages_do_nothing() {
+        let messages = [
+            json!({ "MessageType": "ForceKeepAlive", "Data": 60 }),
+            json!({ "MessageType": "Play", "Data": { "ItemIds": [], "PlayCommand": "PlayNow" } }),
+            json!({ "MessageType": "Play", "Data": { "ItemIds": ["a"], "PlayCommand": "PlayInstantMix" } }),
+            json!({ "MessageType": "Playstate", "Data": { "Command": "Rewind" } }),
+            json!({ "MessageType": "GeneralCommand", "Data": { "Name": "SetVolume", "Arguments": { "Volume": "loud" } } }),
+            json!({ "Data": {} }),
+            json!("Play"),
+        ];
+        for message in messages {
+            assert_eq!(actions_for(&message), None, "{}", message);
+        }
+    }
+}